@@ -1,7 +1,27 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
-use clap::{Args, Parser, Subcommand};
-use stream_cave::{create_oauth_token, Streams};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use directories::ProjectDirs;
+use stream_cave::{
+    authentication::{
+        create_oauth_token_with_device_code, create_oauth_token_with_scopes, delete_oauth_token,
+        TokenError,
+    },
+    control,
+    event_log::EventLog,
+    events::StreamEvent,
+    http::{self, TwitchEndpoints},
+    notifier,
+    quality::Quality,
+    report::Report,
+    twitch_socket, Latency, Settings, Streams, TokenStorage,
+};
+use twitch_oauth2::UserToken;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -19,6 +39,35 @@ enum Commands {
     Stream(StreamArgs),
     /// Play certain streams
     Play(PlayArgs),
+    /// Play the live stream with the most viewers
+    PlayTop(PlayTopArgs),
+    /// Print the upcoming scheduled streams, soonest first
+    Next(NextArgs),
+    /// Print the JSON schema of a configuration file
+    Schema(SchemaArgs),
+    /// Print the recent events of the daemon, newest first
+    Events(EventsArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Close the player of a stream started by the daemon, it is not played again until it goes
+    /// offline
+    Close(CloseArgs),
+    /// Print the configuration, schedule and token status as JSON for bug reports
+    Report(ReportArgs),
+    /// Send a test notification through every configured notifier
+    NotifyTest(NotifyTestArgs),
+    /// List or clear the streams the daemon considers playing
+    Players(PlayersArgs),
+    /// Time how long resolving the url of a stream takes, to choose the player startup timeout
+    BenchResolve(BenchResolveArgs),
+    /// Send the daemon an event for a stream as if twitch had sent it
+    Simulate(SimulateArgs),
+    /// Stop the daemon from playing streams going live, until resumed
+    Pause(PauseArgs),
+    /// Let the daemon play streams going live again after a pause
+    Resume(PauseArgs),
+    /// Print the profile the daemon plays new streams with, or switch it until the config changes
+    Profile(ProfileArgs),
 }
 
 #[derive(Args)]
@@ -36,6 +85,9 @@ enum TokenActions {
 #[derive(Args)]
 struct TokenActionArgs {
     config: Option<PathBuf>,
+    /// Create the token by entering a code on any device instead of pasting the redirect adress
+    #[arg(long)]
+    device: bool,
 }
 #[derive(Args)]
 struct StreamArgs {
@@ -50,7 +102,13 @@ enum StreamActions {
     Add(AddArgs),
     Remove(RemoveArgs),
     Edit(EditArgs),
-    List,
+    List(ListArgs),
+    /// Find streams that no longer resolve to a channel
+    Clean(CleanArgs),
+    /// Look up the broadcaster ids of all streams again
+    RefreshIds,
+    /// Preview the streams that open and close when a stream goes live
+    Plan(PlanArgs),
 }
 
 #[derive(Args)]
@@ -70,138 +128,1093 @@ struct EditArgs {
     quality_overrides: Option<Vec<String>>,
 }
 
+#[derive(Args)]
+struct ListArgs {
+    /// Also check which streams are live
+    #[arg(short, long)]
+    live: bool,
+}
+
+#[derive(Args)]
+struct PlanArgs {
+    name: String,
+}
+
+#[derive(Args)]
+struct CleanArgs {
+    /// Remove the unresolved streams instead of only listing them
+    #[arg(short, long)]
+    yes: bool,
+}
+
 #[derive(Args)]
 struct PlayArgs {
     stream: String,
     quality: Option<String>,
+    /// Request the low-latency stream
+    #[arg(short, long)]
+    low_latency: bool,
+    /// Print the direct url of the stream instead of playing it
+    #[arg(long)]
+    print_url: bool,
+    /// The config whose player, player path and extra player arguments are used
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// The player to use instead of the player of the config: mpv, streamlink, vlc or custom
+    #[arg(short, long)]
+    player: Option<stream_cave::Player>,
+    /// Extra arguments passed to the player after `--`
+    #[arg(last = true)]
+    player_args: Vec<String>,
+}
+
+#[derive(Args)]
+struct BenchResolveArgs {
+    stream: String,
+    /// The number of times the url is resolved
+    #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    samples: u32,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PlayTopArgs {
+    /// Only consider the streams of this group
+    #[arg(short, long)]
+    group: Option<String>,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct NextArgs {
+    /// Only print the schedule of this stream
+    name: Option<String>,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SchemaArgs {
+    #[arg(value_enum)]
+    format: SchemaFormat,
+}
+
+#[derive(Args)]
+struct EventsArgs {
+    /// Number of events to print
+    #[arg(short = 'n', long, default_value_t = 20)]
+    count: usize,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+#[derive(Args)]
+struct CloseArgs {
+    name: String,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PauseArgs {
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ProfileArgs {
+    /// The profile to switch to, one of the profiles of the config
+    name: Option<String>,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SimulateArgs {
+    #[arg(value_parser = control::SIMULATED_EVENTS)]
+    event: String,
+    name: String,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct PlayersArgs {
+    #[command(subcommand)]
+    action: PlayersActions,
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum PlayersActions {
+    /// Print the streams with an active player
+    List,
+    /// Forget stale players so their streams are played on the next live event
+    Clear(ClearArgs),
+}
+
+#[derive(Args)]
+struct ClearArgs {
+    /// Only clear this stream
+    name: Option<String>,
+}
+
+#[derive(Args)]
+struct ReportArgs {
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct NotifyTestArgs {
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SchemaFormat {
+    /// The config.json settings file
+    Config,
+    /// The schedule.json streams file
+    Schedule,
 }
 
 const CLIENT_ID: &str = "uty2ua26tqh28rzn3jketggzu98t6b";
-const SEARCH_CHANNEL_API: &str = "https://api.twitch.tv/helix/search/channels";
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = Cli::parse();
     let system_paths = directories::ProjectDirs::from("com", "Iridescent", "Stream Cave");
 
     match &args.command {
-        Commands::Play(play) => {
-            let player = stream_cave::Player::Mpv;
-            let website = "https://www.twitch.tv/";
-            let stream = format!("{}{}", website, play.stream.clone());
-            if let Some(quality) = &play.quality {
-                match quality.parse::<u16>() {
-                    Ok(number) => {
-                        stream_cave::get_stream(player, stream, number)
-                            .await
+        Commands::Play(play) => play_stream(play, system_paths.as_ref()).await,
+        Commands::PlayTop(play) => play_top_stream(play, system_paths.as_ref()).await,
+        Commands::Next(next) => print_upcoming_streams(next, system_paths.as_ref()).await,
+        Commands::Token(token) => manage_token(token, system_paths.as_ref()).await,
+        Commands::Stream(stream) => return manage_streams(stream, system_paths.as_ref()).await,
+        Commands::Schema(schema) => print_schema(schema),
+        Commands::Events(events) => print_events(events, system_paths.as_ref()),
+        Commands::Completions(completions) => {
+            write_completions(completions.shell, &mut std::io::stdout());
+        }
+        Commands::Close(close) => close_stream(close, system_paths.as_ref()).await,
+        Commands::Simulate(simulate) => {
+            return simulate_event(simulate, system_paths.as_ref()).await;
+        }
+        Commands::Pause(pause) => return set_paused(true, pause, system_paths.as_ref()).await,
+        Commands::Resume(resume) => return set_paused(false, resume, system_paths.as_ref()).await,
+        Commands::Profile(profile) => return switch_profile(profile, system_paths.as_ref()).await,
+        Commands::Players(players) => return manage_players(players, system_paths.as_ref()).await,
+        Commands::Report(report) => print_report(report, system_paths.as_ref()).await,
+        Commands::NotifyTest(notify) => {
+            return test_notifiers(notify, system_paths.as_ref()).await;
+        }
+        Commands::BenchResolve(bench) => {
+            return bench_resolve(bench, system_paths.as_ref()).await;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+async fn close_stream(close: &CloseArgs, system_paths: Option<&ProjectDirs>) {
+    let config = close.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let command = format!("close {}", close.name);
+    match control::send_command(&control::socket_path(&config), &command).await {
+        Ok(_) => println!("Closed {}", close.name),
+        Err(error) => eprintln!("Error while closing {}: {error}", close.name),
+    }
+}
+
+async fn simulate_event(simulate: &SimulateArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config = simulate.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let command = format!("simulate {} {}", simulate.event, simulate.name);
+    match control::send_command(&control::socket_path(&config), &command).await {
+        Ok(_) => {
+            println!("Sent a {} event for {}", simulate.event, simulate.name);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Error while simulating the event: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn set_paused(
+    paused: bool,
+    args: &PauseArgs,
+    system_paths: Option<&ProjectDirs>,
+) -> ExitCode {
+    let config = args.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let command = if paused { "pause" } else { "resume" };
+    match control::send_command(&control::socket_path(&config), command).await {
+        Ok(_) if paused => println!("Paused playing streams"),
+        Ok(_) => println!("Resumed playing streams"),
+        Err(error) => {
+            eprintln!("Error while sending {command}: {error}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+async fn switch_profile(args: &ProfileArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config = args.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let command = args
+        .name
+        .as_ref()
+        .map_or_else(|| String::from("profile"), |name| format!("profile {name}"));
+    match control::send_command(&control::socket_path(&config), &command).await {
+        Ok(profile) => match &args.name {
+            Some(name) => println!("New streams are played with the {name} profile"),
+            None => println!("{profile}"),
+        },
+        Err(error) => {
+            eprintln!("Error while sending {command}: {error}");
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+async fn manage_players(players: &PlayersArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config = players.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let command = match &players.action {
+        PlayersActions::List => String::from("list"),
+        PlayersActions::Clear(clear) => clear
+            .name
+            .as_ref()
+            .map_or_else(|| String::from("clear"), |name| format!("clear {name}")),
+    };
+    let names = match control::send_command(&control::socket_path(&config), &command).await {
+        Ok(names) => names,
+        Err(error) => {
+            eprintln!("Error while sending {command}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let (none, prefix) = match players.action {
+        PlayersActions::List => ("No players are active", ""),
+        PlayersActions::Clear(_) => ("No players were cleared", "Cleared "),
+    };
+    if names.is_empty() {
+        println!("{none}");
+    }
+    for name in names.split_whitespace() {
+        println!("{prefix}{name}");
+    }
+    ExitCode::SUCCESS
+}
+
+/// Write the completion script of `shell` for all commands to `buffer`.
+fn write_completions(shell: Shell, buffer: &mut impl std::io::Write) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, buffer);
+}
+
+async fn play_stream(play: &PlayArgs, system_paths: Option<&ProjectDirs>) {
+    let settings = play
+        .config
+        .clone()
+        .or_else(|| system_paths.map(|paths| paths.config_dir().to_path_buf()))
+        .and_then(|config| read_settings(&config));
+    let (player, options) = settings.map_or_else(
+        || {
+            (
+                stream_cave::Player::Mpv,
+                stream_cave::player::PlayerOptions::default(),
+            )
+        },
+        |settings| (settings.player, settings.player_options()),
+    );
+    let player = play.player.unwrap_or(player);
+    if player == stream_cave::Player::Custom && options.custom.is_none() {
+        eprintln!("Set custom_player in the config to play with the custom player");
+        return;
+    }
+    let website = TwitchEndpoints::default().website;
+    let stream = format!("{}{}", website, play.stream.clone());
+    let Some(quality) = play_quality(play.quality.as_deref()) else {
+        eprintln!("Please enter a number or a streamlink name for stream quality or \"audio\" for audio only");
+        return;
+    };
+    let latency = if play.low_latency {
+        Latency::Low
+    } else {
+        Latency::Normal
+    };
+    if play.print_url {
+        let resolver =
+            stream_cave::player::resolver_command(player, stream, &quality, latency, &options);
+        if let Err(error) = print_stream_url(resolver, &mut std::io::stdout()).await {
+            eprintln!("Error: {error}");
+        }
+        return;
+    }
+    stream_cave::get_stream(
+        player,
+        stream,
+        &quality,
+        false,
+        latency,
+        &play.player_args,
+        &options,
+    )
+    .await
+    .await
+    .expect("Unable to play stream");
+}
+
+/// Resolve the stream url with the `resolver` command and write it to `buffer`.
+async fn print_stream_url(
+    resolver: tokio::process::Command,
+    buffer: &mut impl std::io::Write,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let url = stream_cave::player::resolve_stream_url(resolver).await?;
+    writeln!(buffer, "{url}")?;
+    Ok(())
+}
+
+/// The quality of the `quality` argument of the play command.
+fn play_quality(quality: Option<&str>) -> Option<Quality> {
+    match quality {
+        None => Some(Quality::Height(1080)),
+        Some("audio") => Some(Quality::Height(0)),
+        Some(quality) => quality.parse().ok(),
+    }
+}
+
+async fn manage_token(token: &TokenArgs, system_paths: Option<&ProjectDirs>) {
+    if let Some(action) = &token.action {
+        match action {
+            TokenActions::Create(arg) => {
+                let config = arg
+                    .config
+                    .as_deref()
+                    .or_else(|| system_paths.map(ProjectDirs::config_dir));
+                if let Some(config) = config {
+                    let (scopes, storage) = read_settings(config).map_or_else(
+                        || (Vec::new(), TokenStorage::File),
+                        |settings| (settings.scopes, settings.token_storage),
+                    );
+                    let token_file = token_file(config);
+                    let created = if arg.device {
+                        create_oauth_token_with_device_code(
+                            CLIENT_ID,
+                            &token_file,
+                            &scopes,
+                            storage,
+                        )
+                        .await
+                    } else {
+                        create_oauth_token_with_scopes(CLIENT_ID, &token_file, &scopes, storage)
                             .await
-                            .expect("Unable to play stream");
-                    }
-                    Err(_) => {
-                        if quality == "audio" {
-                            stream_cave::get_stream(player, stream, 0)
-                                .await
-                                .await
-                                .expect("Unable to play stream");
-                        } else {
-                            eprintln!("Please enter a number for stream quality or \"audio\" for audio only");
-                        }
-                    }
+                    };
+                    created.expect("Error when creating token");
                 }
-            } else {
-                stream_cave::get_stream(player, stream, 1080)
-                    .await
-                    .await
-                    .expect("Unable to play stream");
             }
-        }
-        Commands::Token(token) => {
-            if let Some(action) = &token.action {
-                match action {
-                    TokenActions::Create(arg) => {
-                        if let Some(path) = &arg.config {
-                            create_oauth_token(CLIENT_ID, path.as_path())
-                                .await
-                                .expect("Error when creating token");
-                        } else if let Some(path) = system_paths {
-                            create_oauth_token(CLIENT_ID, path.config_dir())
-                                .await
-                                .expect("Error when creating token");
-                        }
-                    }
-                    TokenActions::Delete(arg) => {
-                        if let Some(path) = &arg.config {
-                            std::fs::remove_file(path.join("user-data.json"))
-                                .expect("Unable to delete file");
-                        } else if let Some(path) = system_paths {
-                            std::fs::remove_file(path.config_dir().join("user-data.json"))
-                                .expect("Unable to delete file");
-                        }
-                    }
+            TokenActions::Delete(arg) => {
+                let config = arg
+                    .config
+                    .as_deref()
+                    .or_else(|| system_paths.map(ProjectDirs::config_dir));
+                if let Some(config) = config {
+                    delete_oauth_token(CLIENT_ID, &token_file(config))
+                        .await
+                        .expect("Unable to delete token");
                 }
             }
         }
-        Commands::Stream(stream) => {
-            let config_option = stream.config.clone().unwrap_or_else(|| {
-                system_paths
-                    .as_ref()
-                    .expect("Unable to enumerate system paths")
-                    .config_dir()
-                    .to_path_buf()
-            });
-            let mut schedule = Streams::read_streams(&config_option);
-
-            match &stream.action {
-                StreamActions::Add(action) => {
-                    let mut user_access_token: Option<twitch_oauth2::tokens::UserToken> = None;
-                    if let Err(error) = stream_cave::authentication::validate_oauth_token(
-                        &mut user_access_token,
-                        &config_option,
-                        false,
-                    )
-                    .await
-                    {
-                        eprintln!("Error {error}.\nPlease retry creating a token.");
-                        return;
-                    }
+    }
+}
+
+async fn manage_streams(stream: &StreamArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config_option = stream.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let _lock = match Streams::lock(&config_option) {
+        Ok(lock) => lock,
+        Err(error) => {
+            eprintln!("Error while locking schedule: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut schedule = Streams::read_streams(&config_option);
+
+    match &stream.action {
+        StreamActions::Add(action) => {
+            let Some(user_access_token) = read_token(&config_option).await else {
+                return ExitCode::FAILURE;
+            };
+            let change = schedule
+                .add_stream(
+                    &action.name,
+                    &action.quality_overrides,
+                    &endpoints(&config_option).search_channels(),
+                    CLIENT_ID,
+                    user_access_token,
+                    read_settings(&config_option)
+                        .map_or(twitch_socket::DEFAULT_MAX_SUBSCRIPTION_COST, |settings| {
+                            settings.max_subscription_cost
+                        }),
+                )
+                .await;
+            return write_change(&schedule, &config_option, change);
+        }
+        StreamActions::Edit(action) => {
+            let change = schedule.edit_stream(&action.name, &action.quality_overrides);
+            return write_change(&schedule, &config_option, change);
+        }
+        StreamActions::Remove(action) => {
+            let change = schedule
+                .remove_stream(&action.name)
+                .map(|_| ())
+                .ok_or_else(|| "Streamer does not exist in file".into());
+            return write_change(&schedule, &config_option, change);
+        }
+        StreamActions::Clean(action) => {
+            clean_streams(schedule, &config_option, action.yes).await;
+        }
+        StreamActions::RefreshIds => refresh_ids(schedule, &config_option).await,
+        StreamActions::Plan(action) => {
+            let schedule =
+                Streams::read_streams_with_includes(&config_option).unwrap_or_else(|error| {
+                    eprintln!("Error reading included schedules: {error}");
                     schedule
-                        .add_stream(
-                            &action.name,
-                            &action.quality_overrides,
-                            SEARCH_CHANNEL_API,
-                            CLIENT_ID,
-                            user_access_token.expect("Expected to find token but found nothing"),
-                        )
-                        .await
-                        .unwrap_or_else(|error| {
-                            eprintln!("Error while performing operation: {error}");
-                        });
-                    schedule.write(&config_option).unwrap_or_else(|error| {
-                        eprintln!("Error while performing operation: {error}");
-                    });
-                }
-                StreamActions::Edit(action) => {
+                });
+            if schedule.get(&action.name).is_none() {
+                eprintln!("Streamer does not exist in file");
+                return ExitCode::FAILURE;
+            }
+            print!("{}", schedule.plan(&action.name));
+        }
+        StreamActions::List(action) => {
+            let schedule =
+                Streams::read_streams_with_includes(&config_option).unwrap_or_else(|error| {
+                    eprintln!("Error reading included schedules: {error}");
                     schedule
-                        .edit_stream(&action.name, &action.quality_overrides)
-                        .unwrap_or_else(|error| {
-                            eprintln!("Error while performing operation: {error}");
-                        });
-                    schedule.write(&config_option).unwrap_or_else(|error| {
-                        eprintln!("Error while performing operation: {error}");
-                    });
-                }
-                StreamActions::Remove(action) => {
-                    if schedule.remove_stream(&action.name).is_none() {
-                        eprintln!("Streamer does not exist in file");
-                    }
-                    schedule.write(&config_option).unwrap_or_else(|error| {
-                        eprintln!("Error while performing operation: {error}");
-                    });
-                }
-                StreamActions::List => {
-                    println!("{schedule}");
+                });
+            println!("{schedule}");
+            if action.live {
+                print_live_status(&schedule, &config_option).await;
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Write `schedule` to the `config` directory after a successful `change`. A failed change is
+/// not written, so the file is left unchanged whenever the command fails.
+fn write_change(
+    schedule: &Streams,
+    config: &Path,
+    change: Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> ExitCode {
+    if let Err(error) = change {
+        eprintln!("Error while performing operation: {error}");
+        if error.downcast_ref::<TokenError>() == Some(&TokenError::Invalid) {
+            eprintln!("Please recreate the token with `stream-cave token create`.");
+        }
+        return ExitCode::FAILURE;
+    }
+    if let Err(error) = schedule.write(config) {
+        eprintln!(
+            "Error: the schedule in {} was not changed, unable to write it: {error}",
+            config.display()
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+async fn clean_streams(mut schedule: Streams, config_option: &Path, yes: bool) {
+    let Some(user_access_token) = read_token(config_option).await else {
+        return;
+    };
+    let unresolved = match schedule
+        .unresolved_streams(
+            &endpoints(config_option).search_channels(),
+            CLIENT_ID,
+            &user_access_token,
+        )
+        .await
+    {
+        Ok(unresolved) => unresolved,
+        Err(error) => {
+            eprintln!("Error while performing operation: {error}");
+            return;
+        }
+    };
+    if unresolved.is_empty() {
+        println!("All streams resolved");
+        return;
+    }
+    for name in &unresolved {
+        if yes {
+            schedule.remove_stream(name);
+            println!("Removed {name}");
+        } else {
+            println!("Unable to resolve {name}");
+        }
+    }
+    if yes {
+        schedule.write(config_option).unwrap_or_else(|error| {
+            eprintln!("Error while performing operation: {error}");
+        });
+    } else {
+        println!("Run again with --yes to remove these streams");
+    }
+}
+
+async fn print_live_status(schedule: &Streams, config_option: &Path) {
+    let Some(user_access_token) = read_token(config_option).await else {
+        return;
+    };
+    let names: Vec<String> = schedule
+        .into_iter()
+        .map(|stream| stream.name.clone())
+        .collect();
+    let concurrency = read_settings(config_option)
+        .map_or(stream_cave::DEFAULT_LIVE_CHECK_CONCURRENCY, |settings| {
+            settings.live_check_concurrency
+        });
+    match stream_cave::check_live_status(
+        &names,
+        concurrency,
+        &endpoints(config_option).streams(),
+        CLIENT_ID,
+        &user_access_token,
+    )
+    .await
+    {
+        Ok(live) => {
+            for (name, is_live) in live {
+                println!("{name}: {}", if is_live { "live" } else { "offline" });
+            }
+        }
+        Err(error) => eprintln!("Error while checking for live streams: {error}"),
+    }
+}
+
+async fn refresh_ids(mut schedule: Streams, config_option: &Path) {
+    let Some(user_access_token) = read_token(config_option).await else {
+        return;
+    };
+    match schedule
+        .refresh_ids(
+            &endpoints(config_option).search_channels(),
+            CLIENT_ID,
+            &user_access_token,
+        )
+        .await
+    {
+        Ok(changes) => {
+            for (name, old_id, new_id) in &changes {
+                println!("Updated {name}: {old_id} -> {new_id}");
+            }
+            if changes.is_empty() {
+                println!("All ids up to date");
+            }
+            schedule.write(config_option).unwrap_or_else(|error| {
+                eprintln!("Error while performing operation: {error}");
+            });
+        }
+        Err(error) => eprintln!("Error while performing operation: {error}"),
+    }
+}
+
+async fn read_token(config: &Path) -> Option<UserToken> {
+    let mut user_access_token: Option<UserToken> = None;
+    if let Err(error) = stream_cave::authentication::validate_oauth_token(
+        &mut user_access_token,
+        &token_file(config),
+        false,
+    )
+    .await
+    {
+        eprintln!("Error {error}.\nPlease retry creating a token.");
+        return None;
+    }
+    user_access_token
+}
+
+async fn bench_resolve(bench: &BenchResolveArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config = bench.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let settings = read_settings(&config).unwrap_or_else(|| Settings::new(&config));
+    let stream = format!("{}{}", settings.endpoints.website, bench.stream);
+    let quality = Quality::Height(settings.profile().1);
+    let options = settings.player_options();
+    let resolver = || {
+        stream_cave::player::resolver_command(
+            settings.player,
+            stream.clone(),
+            &quality,
+            settings.latency,
+            &options,
+        )
+    };
+
+    match time_resolver(resolver, bench.samples).await {
+        Ok(timings) => {
+            if let Err(error) = write_timings(&timings, &mut std::io::stdout()) {
+                eprintln!("Error: {error}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Error while resolving {stream}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Resolve the stream url `samples` times with the commands created by `resolver` and return how
+/// long each resolution took.
+async fn time_resolver(
+    resolver: impl Fn() -> tokio::process::Command,
+    samples: u32,
+) -> Result<Vec<Duration>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut timings = Vec::new();
+    for _ in 0..samples {
+        let start = Instant::now();
+        stream_cave::player::resolve_stream_url(resolver()).await?;
+        timings.push(start.elapsed());
+    }
+    Ok(timings)
+}
+
+/// Write every timing followed by their minimum, average and maximum to `buffer`.
+fn write_timings(timings: &[Duration], buffer: &mut impl std::io::Write) -> std::io::Result<()> {
+    for (sample, timing) in timings.iter().enumerate() {
+        writeln!(
+            buffer,
+            "Sample {}: {:.2}s",
+            sample + 1,
+            timing.as_secs_f64()
+        )?;
+    }
+    let (Some(min), Some(max)) = (timings.iter().min(), timings.iter().max()) else {
+        return Ok(());
+    };
+    let average = timings.iter().sum::<Duration>() / u32::try_from(timings.len()).unwrap_or(1);
+    writeln!(
+        buffer,
+        "min {:.2}s, avg {:.2}s, max {:.2}s",
+        min.as_secs_f64(),
+        average.as_secs_f64(),
+        max.as_secs_f64()
+    )
+}
+
+async fn play_top_stream(play: &PlayTopArgs, system_paths: Option<&ProjectDirs>) {
+    let config = play.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let schedule = Streams::read_streams_with_includes(&config).unwrap_or_else(|error| {
+        eprintln!("Error reading included schedules: {error}");
+        Streams::read_streams(&config)
+    });
+    let Some(user_access_token) = read_token(&config).await else {
+        return;
+    };
+
+    let top = match schedule
+        .top_live_stream(
+            play.group.as_deref(),
+            &endpoints(&config).streams(),
+            CLIENT_ID,
+            &user_access_token,
+        )
+        .await
+    {
+        Ok(Some(top)) => top,
+        Ok(None) => {
+            println!("None of the streams are live");
+            return;
+        }
+        Err(error) => {
+            eprintln!("Error while finding live streams: {error}");
+            return;
+        }
+    };
+
+    let (player, profile, latency, options) = read_settings(&config).map_or_else(
+        || {
+            (
+                stream_cave::Player::Mpv,
+                (
+                    String::from(stream_cave::DEFAULT_PROFILE.0),
+                    stream_cave::DEFAULT_PROFILE.1,
+                ),
+                Latency::Normal,
+                stream_cave::player::PlayerOptions::default(),
+            )
+        },
+        |settings| {
+            (
+                settings.player,
+                settings.profile(),
+                settings.latency,
+                settings.player_options(),
+            )
+        },
+    );
+    let website = endpoints(&config).website;
+    let (stream, viewers) = top;
+    let quality = stream
+        .quality_overides
+        .get(&profile.0)
+        .cloned()
+        .unwrap_or(Quality::Height(profile.1));
+    println!("Playing {} with {viewers} viewers", stream.name);
+    stream_cave::get_stream(
+        player,
+        format!("{website}{}", stream.name),
+        &quality,
+        stream.resume,
+        stream.latency.unwrap_or(latency),
+        &stream.player_args,
+        &options,
+    )
+    .await
+    .await
+    .expect("Unable to play stream");
+}
+
+/// The settings in the config.json of the `config` directory, if it exists. The first settings
+/// read set the timeouts and user agent of the shared http client.
+fn read_settings(config: &Path) -> Option<Settings> {
+    let settings = config
+        .join("config.json")
+        .exists()
+        .then(|| Settings::read_config(&[config.to_path_buf()]).ok())
+        .flatten()?;
+    // The client is only created once, later reads keep it
+    let _ = http::init(settings.timeouts, settings.user_agent.clone());
+    Some(settings)
+}
+
+/// The endpoints configured in the config.json of the `config` directory, or the Twitch
+/// defaults.
+fn endpoints(config: &Path) -> TwitchEndpoints {
+    read_settings(config)
+        .map(|settings| settings.endpoints)
+        .unwrap_or_default()
+}
+
+/// The token file configured in the config.json of the `config` directory, or the
+/// user-data.json in it.
+fn token_file(config: &Path) -> PathBuf {
+    read_settings(config)
+        .and_then(|settings| settings.token_path)
+        .unwrap_or_else(|| config.join("user-data.json"))
+}
+
+async fn print_upcoming_streams(next: &NextArgs, system_paths: Option<&ProjectDirs>) {
+    let config = next.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let schedule = Streams::read_streams_with_includes(&config).unwrap_or_else(|error| {
+        eprintln!("Error reading included schedules: {error}");
+        Streams::read_streams(&config)
+    });
+    let Some(user_access_token) = read_token(&config).await else {
+        return;
+    };
+
+    match schedule
+        .upcoming_streams(
+            next.name.as_deref(),
+            &endpoints(&config).schedule(),
+            CLIENT_ID,
+            &user_access_token,
+        )
+        .await
+    {
+        Ok(upcoming) if upcoming.is_empty() => println!("No streams are scheduled"),
+        Ok(upcoming) => {
+            for (stream, segment) in upcoming {
+                match segment.category {
+                    Some(category) => println!(
+                        "{} {}: {} ({})",
+                        segment.start_time, stream.name, segment.title, category.name
+                    ),
+                    None => println!("{} {}: {}", segment.start_time, stream.name, segment.title),
                 }
             }
         }
+        Err(error) => eprintln!("Error while getting scheduled streams: {error}"),
+    }
+}
+
+fn print_events(events: &EventsArgs, system_paths: Option<&ProjectDirs>) {
+    let config = events.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    for record in EventLog::tail(&config.join("events.log"), events.count) {
+        println!("{record}");
+    }
+}
+
+async fn print_report(report: &ReportArgs, system_paths: Option<&ProjectDirs>) {
+    let config = report.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    match Report::gather(&config).await.to_json() {
+        Ok(text) => println!("{text}"),
+        Err(error) => eprintln!("Error while serializing report: {error}"),
+    }
+}
+
+async fn test_notifiers(notify: &NotifyTestArgs, system_paths: Option<&ProjectDirs>) -> ExitCode {
+    let config = notify.config.clone().unwrap_or_else(|| {
+        system_paths
+            .expect("Unable to enumerate system paths")
+            .config_dir()
+            .to_path_buf()
+    });
+    let configs = read_settings(&config)
+        .map(|settings| settings.notifiers)
+        .unwrap_or_default();
+    if configs.is_empty() {
+        println!("No notifiers are configured");
+        return ExitCode::SUCCESS;
+    }
+
+    let notifiers = notifier::from_config(&configs);
+    let results = notifier::notify_each(&notifiers, &StreamEvent::Test).await;
+    let mut exit_code = ExitCode::SUCCESS;
+    for (config, result) in configs.iter().zip(results) {
+        match result {
+            Ok(()) => println!("{}: ok", config.name()),
+            Err(error) => {
+                println!("{}: failed, {error}", config.name());
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+    }
+    exit_code
+}
+
+fn print_schema(schema: &SchemaArgs) {
+    let schema = match schema.format {
+        SchemaFormat::Config => stream_cave::schema::config_schema(),
+        SchemaFormat::Schedule => stream_cave::schema::schedule_schema(),
     };
+    match serde_json::to_string_pretty(&schema) {
+        Ok(text) => println!("{text}"),
+        Err(error) => eprintln!("Error while serializing schema: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bash_completions() {
+        let mut buffer = Vec::new();
+
+        write_completions(Shell::Bash, &mut buffer);
+        let script = String::from_utf8(buffer).unwrap();
+
+        assert!(!script.is_empty());
+        assert!(script.contains("stream"));
+        assert!(script.contains("play-top"));
+    }
+
+    #[test]
+    fn failed_write_leaves_schedule_unchanged() {
+        let directory = std::env::temp_dir().join("stream-cave-failed-write");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut schedule = Streams::read_streams(Path::new("./tests/resources"));
+        schedule.write(&directory).unwrap();
+        let before = std::fs::read_to_string(directory.join("schedule.json")).unwrap();
+        // The temporary file cannot be created over a directory, even when running as root
+        std::fs::create_dir(directory.join(".schedule.json.tmp")).unwrap();
+
+        assert!(schedule.remove_stream("kaicenat").is_some());
+        let failed_write = write_change(&schedule, &directory, Ok(()));
+        let failed_change = write_change(&schedule, &directory, Err("Invalid quality".into()));
+        let after = std::fs::read_to_string(directory.join("schedule.json")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(ExitCode::FAILURE, failed_write);
+        assert_eq!(ExitCode::FAILURE, failed_change);
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn malformed_override_leaves_schedule_unchanged() {
+        let directory = std::env::temp_dir().join("stream-cave-malformed-override");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut schedule = Streams::read_streams(Path::new("./tests/resources"));
+        schedule.write(&directory).unwrap();
+        let before = std::fs::read(directory.join("schedule.json")).unwrap();
+
+        let exit_codes: Vec<ExitCode> = ["normal", "normal,"]
+            .into_iter()
+            .map(|overrides| {
+                let change = schedule.edit_stream("kaicenat", &Some(vec![overrides.to_string()]));
+                write_change(&schedule, &directory, change)
+            })
+            .collect();
+        let after = std::fs::read(directory.join("schedule.json")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(vec![ExitCode::FAILURE, ExitCode::FAILURE], exit_codes);
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn pass_trailing_args_to_player() {
+        let cli = Cli::try_parse_from([
+            "stream-cave",
+            "play",
+            "kaicenat",
+            "720",
+            "--",
+            "--fullscreen",
+            "--volume=50",
+        ])
+        .unwrap();
+        let Commands::Play(play) = cli.command else {
+            panic!("Expected play command");
+        };
+
+        let command = stream_cave::player::player_command(
+            stream_cave::Player::Mpv,
+            play.stream,
+            &play_quality(play.quality.as_deref()).unwrap(),
+            false,
+            Latency::Normal,
+            &play.player_args,
+            &stream_cave::player::PlayerOptions::default(),
+        );
+        let args: Vec<_> = command.as_std().get_args().collect();
+
+        assert_eq!(["--fullscreen", "--volume=50"], args[args.len() - 2..]);
+        assert!(args.contains(&"--ytdl-format=best[height<=?720]".as_ref()));
+    }
+
+    #[tokio::test]
+    async fn print_resolved_url() {
+        let cli = Cli::try_parse_from(["stream-cave", "play", "kaicenat", "audio", "--print-url"])
+            .unwrap();
+        let Commands::Play(play) = cli.command else {
+            panic!("Expected play command");
+        };
+        let resolver = stream_cave::player::resolver_command(
+            stream_cave::Player::Mpv,
+            play.stream,
+            &play_quality(play.quality.as_deref()).unwrap(),
+            Latency::Normal,
+            &stream_cave::player::PlayerOptions::default(),
+        );
+        let args: Vec<_> = resolver.as_std().get_args().collect();
+        // Stand in for yt-dlp, which is not available when testing
+        let mut fake_resolver = tokio::process::Command::new("sh");
+        fake_resolver
+            .arg("-c")
+            .arg("echo; echo https://video.example/kaicenat/index.m3u8");
+        let mut output = Vec::new();
+        print_stream_url(fake_resolver, &mut output).await.unwrap();
+
+        assert!(play.print_url);
+        assert_eq!("yt-dlp", resolver.as_std().get_program());
+        assert_eq!(["-g", "-f", "bestaudio", "kaicenat"], args[..]);
+        assert_eq!(
+            "https://video.example/kaicenat/index.m3u8\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn report_resolution_timings() {
+        let cli =
+            Cli::try_parse_from(["stream-cave", "bench-resolve", "kaicenat", "-s", "2"]).unwrap();
+        let Commands::BenchResolve(bench) = cli.command else {
+            panic!("Expected bench-resolve command");
+        };
+        // Stand in for the resolver of the player, which is not available when testing
+        let fake_resolver = || {
+            let mut resolver = tokio::process::Command::new("sh");
+            resolver
+                .arg("-c")
+                .arg("sleep 0.2; echo https://video.example/kaicenat/index.m3u8");
+            resolver
+        };
+
+        let timings = time_resolver(fake_resolver, bench.samples).await.unwrap();
+        let mut output = Vec::new();
+        write_timings(&timings, &mut output).unwrap();
+        let report = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!(2, timings.len());
+        assert!(timings
+            .iter()
+            .all(|timing| *timing >= Duration::from_millis(200)));
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("Sample 2: "));
+        assert!(lines[2].starts_with("min "));
+        assert!(
+            Cli::try_parse_from(["stream-cave", "bench-resolve", "kaicenat", "-s", "0"]).is_err()
+        );
+    }
 }
@@ -1,145 +1,725 @@
 use clap::Parser;
 use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
 };
 use stream_cave::{
-    authentication, event_handler, file_watcher, tasks_handler, twitch_socket, Settings, Streams,
+    authentication, control, event_handler,
+    event_log::{self, EventLog, EventLogNotifier, EventRecord, RecordKind},
+    events::StreamEvent,
+    file_watcher, http, notifier, player,
+    quality::Quality,
+    tasks_handler, twitch_socket,
+    vods::{self, ScheduledVod},
+    Latency, Settings, SharedSettings, Streams, UserData,
 };
-use tokio::{sync::mpsc, task};
+use tokio::{
+    signal::{
+        self,
+        unix::{signal as unix_signal, SignalKind},
+    },
+    sync::{broadcast, mpsc},
+    task,
+};
+use twitch_oauth2::UserToken;
 
 const CLIENT_ID: &str = "uty2ua26tqh28rzn3jketggzu98t6b";
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Set directory of config file to use
+    /// Set the config file to use, or the directory of its config.json. `-` reads the config
+    /// from stdin without creating or watching a config file
     #[arg(short, long)]
     config: Option<PathBuf>,
+    /// Read the schedule from this file instead of the schedule directory, `-` for stdin. The
+    /// includes of the schedule are not read
+    #[arg(short, long)]
+    schedule: Option<PathBuf>,
 }
 
+/// The path arguments reading from stdin.
+const STDIN_PATH: &str = "-";
+
 #[tokio::main]
 async fn main() {
     let flags = Args::parse();
+    let config_from_stdin = flags.config.as_deref() == Some(Path::new(STDIN_PATH));
+    if config_from_stdin && flags.schedule.as_deref() == Some(Path::new(STDIN_PATH)) {
+        eprintln!("Fatal: only one of --config and --schedule can be read from stdin");
+        return;
+    }
     let mut paths: Vec<PathBuf> = Vec::new();
 
     if let Some(config_path) = flags.config {
-        paths.push(config_path);
+        // There is no config file to create or watch when reading the config from stdin
+        if !config_from_stdin {
+            paths.push(config_path);
+        }
     } else if let Some(dirs) = directories::ProjectDirs::from("com", "Iridescent", "Stream Cave") {
         paths.push(dirs.config_local_dir().to_owned());
         paths.push(dirs.config_dir().to_owned());
     }
 
-    let settings =
-        Arc::new(Settings::read_config(&paths).expect("Unable to create new config file"));
-    let streams = Arc::new(Mutex::new(Streams::read_streams(&settings.schedule)));
-    run(&settings, &streams).await;
+    let settings = if config_from_stdin {
+        match Settings::from_reader(std::io::stdin().lock()) {
+            Ok(settings) => settings,
+            Err(error) => {
+                eprintln!("Fatal: unable to read the config from stdin: {error}");
+                return;
+            }
+        }
+    } else {
+        Settings::read_config(&paths).expect("Unable to create new config file")
+    };
+    if !http::init(settings.timeouts, settings.user_agent.clone()) {
+        eprintln!("Http client already created, ignoring configured timeouts and user agent");
+    }
+    let source = schedule_source(flags.schedule, &settings.schedule);
+    let Some(streams) = read_schedule(&source) else {
+        return;
+    };
+    let missing_players = missing_players(&settings, &streams, &paths);
+    if !missing_players.is_empty() {
+        for missing in missing_players {
+            eprintln!("Fatal: {missing}");
+        }
+        return;
+    }
+    let schedule = Schedule {
+        streams: Arc::new(Mutex::new(streams)),
+        source,
+    };
+    let event_log = Arc::new(EventLog::new(
+        &settings.schedule.join("events.log"),
+        event_log::DEFAULT_LIMIT,
+    ));
+    let warning = twitch_socket::subscription_cost_warning(
+        &schedule.streams.lock().expect("Mutex lock poisoned"),
+        settings.max_subscription_cost,
+    );
+    if let Some(warning) = warning {
+        eprintln!("Warning: {warning}");
+    }
+    let control_socket = control::socket_path(&settings.schedule);
+    let settings = Arc::new(RwLock::new(settings));
+    if let Some(config_file) = paths
+        .iter()
+        .map(|path| Settings::config_file(path))
+        .find(|config_file| config_file.exists())
+    {
+        task::spawn(file_watcher::config_watcher(
+            config_file,
+            settings.clone(),
+            file_watcher::CONFIG_POLL_INTERVAL,
+        ));
+    }
+    let shutdown = spawn_shutdown_signal();
+    run(&settings, &schedule, &event_log, control_socket, &shutdown).await;
 }
 
-async fn run(settings: &Arc<Settings>, streams: &Arc<Mutex<Streams>>) {
-    const TWITCH_WEBSOCKET_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
-    const TWITCH_API_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
-    const STREAMING_SITE: &str = "https://www.twitch.tv/";
-    const SEARCH_CHANNEL_API: &str = "https://api.twitch.tv/helix/search/channels";
+/// Send a shutdown signal through the returned sender on SIGINT or SIGTERM.
+fn spawn_shutdown_signal() -> broadcast::Sender<()> {
+    let (shutdown_sender, _) = broadcast::channel(1);
+    let signal_sender = shutdown_sender.clone();
+    task::spawn(async move {
+        let terminate = async {
+            match unix_signal(SignalKind::terminate()) {
+                Ok(mut terminate) => {
+                    terminate.recv().await;
+                }
+                Err(error) => {
+                    eprintln!("Error while listening for SIGTERM: {error}");
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            () = terminate => {}
+        }
+        eprintln!("Shutting down, closing the players");
+        let _ = signal_sender.send(());
+    });
+    shutdown_sender
+}
+
+/// The schedule of the daemon and where it is watched for changes.
+struct Schedule {
+    streams: Arc<Mutex<Streams>>,
+    source: file_watcher::ScheduleSource,
+}
+
+/// Check the player of `settings` and the players of the streams in `streams`, returning an
+/// error for each player that is not found with the settings to change.
+fn missing_players(
+    settings: &Settings,
+    streams: &Streams,
+    config_paths: &[PathBuf],
+) -> Vec<String> {
+    let mut players = vec![settings.player];
+    for player in streams.iter().filter_map(|stream| stream.player) {
+        if !players.contains(&player) {
+            players.push(player);
+        }
+    }
+    let options = settings.player_options();
+    players
+        .into_iter()
+        .filter_map(|player| {
+            let error = player::check_player(player, &options).err()?;
+            let mut used_by = Vec::new();
+            if player == settings.player {
+                used_by.push(format!(
+                    "the `player` setting in config.json in one of {config_paths:?}"
+                ));
+            }
+            used_by.extend(
+                streams
+                    .iter()
+                    .filter(|stream| stream.player == Some(player))
+                    .map(|stream| format!("the `player` of {} in the schedule", stream.name)),
+            );
+            Some(format!(
+                "{error}. Install it or change {}",
+                used_by.join(", ")
+            ))
+        })
+        .collect()
+}
+
+/// Where the schedule is read from, the `schedule` flag or the schedule `directory`.
+fn schedule_source(schedule: Option<PathBuf>, directory: &Path) -> file_watcher::ScheduleSource {
+    match schedule {
+        Some(path) if path == Path::new(STDIN_PATH) => file_watcher::ScheduleSource::Stdin,
+        Some(path) => file_watcher::ScheduleSource::File(path),
+        None => file_watcher::ScheduleSource::Directory(directory.to_path_buf()),
+    }
+}
 
+/// Read the schedule from a file or stdin, or with its includes from the schedule directory.
+/// Returns `None` if the schedule file cannot be read.
+fn read_schedule(source: &file_watcher::ScheduleSource) -> Option<Streams> {
+    let read = match source {
+        file_watcher::ScheduleSource::Stdin => {
+            Streams::from_reader(std::io::stdin().lock()).map_err(|error| error.to_string())
+        }
+        file_watcher::ScheduleSource::File(path) => std::fs::File::open(path)
+            .map_err(|error| error.to_string())
+            .and_then(|file| {
+                Streams::from_reader(std::io::BufReader::new(file))
+                    .map_err(|error| error.to_string())
+            }),
+        file_watcher::ScheduleSource::Directory(directory) => {
+            let lock = Streams::lock(directory)
+                .inspect_err(|error| eprintln!("Error while locking schedule: {error}"))
+                .ok();
+            let streams = Streams::read_streams_with_includes(directory).unwrap_or_else(|error| {
+                eprintln!("Error reading included schedules: {error}");
+                Streams::read_streams(directory)
+            });
+            drop(lock);
+            return Some(streams);
+        }
+    };
+    read.inspect_err(|error| eprintln!("Fatal: unable to read the schedule: {error}"))
+        .ok()
+}
+
+async fn run(
+    settings: &SharedSettings,
+    schedule: &Schedule,
+    event_log: &Arc<EventLog>,
+    control_socket: PathBuf,
+    shutdown_sender: &broadcast::Sender<()>,
+) {
+    let mut shutdown = shutdown_sender.subscribe();
+    let streams = &schedule.streams;
+    spawn_vods(
+        settings,
+        streams.lock().expect("Mutex lock poisoned").vods().to_vec(),
+    );
+    // Kept across reconnects so the players of a previous connection are not launched again
+    let active_players = tasks_handler::ActivePlayers::default();
+    let control_players = active_players.clone();
+    let event_injector = control::EventInjector::default();
+    let control_events = event_injector.clone();
+    let control_settings = settings.clone();
+    task::spawn(async move {
+        if let Err(error) = control::control_handler(
+            &control_socket,
+            control_players,
+            control_events,
+            control_settings,
+        )
+        .await
+        {
+            eprintln!(
+                "Error on the control socket, closing streams from the cli is disabled: {error}"
+            );
+        }
+    });
     loop {
-        let mut token: Option<twitch_oauth2::tokens::UserToken> = None;
+        let (token_file, scopes) = {
+            let settings = settings.read().expect("RwLock poisoned");
+            (settings.token_file(), settings.scopes.clone())
+        };
+        let user_access_token = tokio::select! {
+            token = wait_for_token(&token_file, &scopes) => Arc::new(token),
+            Ok(()) = shutdown.recv() => return,
+        };
+        let expiry_warning = spawn_expiry_warning(&user_access_token, event_log);
         loop {
-            match authentication::validate_oauth_token(&mut token, &settings.schedule, true).await {
-                Ok(()) => break,
-                Err(error) => {
-                    eprintln!(
-                        "Error {error}.\nPlease retry creating a token. Re-checking token in 60 seconds"
-                    );
-                    tokio::time::sleep(Duration::from_secs(60)).await;
+            let (mut restart_signal_reciever, task_spawner) = spawn_tasks(
+                settings,
+                schedule,
+                &user_access_token,
+                event_log,
+                &active_players,
+                &event_injector,
+                shutdown_sender,
+            );
+
+            let code = tokio::select! {
+                code = restart_signal_reciever.recv() => code,
+                Ok(()) = shutdown.recv() => {
+                    // Wait for the players to be killed instead of restarting
+                    let _ = task_spawner.await;
+                    return;
                 }
             };
+            if let Some(code) = code {
+                match code {
+                    1 => record(event_log, RecordKind::Reconnect, "Restarting websocket"),
+                    3 => record(
+                        event_log,
+                        RecordKind::Unreachable,
+                        "EventSub unreachable, restarting websocket",
+                    ),
+                    2 => {
+                        record(event_log, RecordKind::AuthError, "User token rejected");
+                        expiry_warning.abort();
+                        break;
+                    }
+                    _ => println!("Unrecognized code"),
+                }
+            } else {
+                return;
+            }
         }
-        let user_access_token = Arc::new(token);
-        loop {
-            let streams = streams.clone();
-            let settings_player = settings.clone();
-            let settings_path = settings.clone();
-
-            //TODO: create function to delete inactive subscriptions
-            let user_access_token_websocket = user_access_token.clone();
-            let user_access_token_exit_handler = user_access_token.clone();
-
-            let (file_watcher_twitch_websocket_sender, twitch_socket_file_watcher_reciever) =
-                mpsc::channel(10);
-            let (file_watcher_event_handler_sender, event_handler_file_watcher_reciever) =
-                mpsc::channel(10);
-            let (twitch_websocket_event_handler_sender, event_handler_twitch_websocket_reciever) =
-                mpsc::channel(10);
-            let (event_handler_task_spawner_sender, task_spawner_event_handler_reciever) =
-                mpsc::channel(10);
-            let (task_spawner_exit_handler_sender, exit_handler_task_spawner_reciever) =
-                mpsc::channel(10);
-            let (exit_handler_event_handler_sender, event_handler_exit_handler_reciever) =
-                mpsc::channel(10);
-            let (restart_signal_sender, mut restart_signal_reciever) = mpsc::channel(1);
-            let restart_signal_sender_exit_handler = restart_signal_sender.clone();
-            let restart_signal_sender_twitch_socket = restart_signal_sender.clone();
+    }
+}
 
-            task::spawn(async move {
-                file_watcher::file_watcher(
-                    file_watcher_twitch_websocket_sender,
-                    file_watcher_event_handler_sender,
-                    &settings_path.schedule,
-                    &streams,
-                )
-                .await;
-            });
-            task::spawn(async move {
-                twitch_socket::twitch_websocket(
-                    twitch_socket_file_watcher_reciever,
-                    twitch_websocket_event_handler_sender,
-                    restart_signal_sender_twitch_socket,
-                    TWITCH_WEBSOCKET_URL,
-                    TWITCH_API_URL,
-                    user_access_token_websocket,
-                    CLIENT_ID,
-                );
-            });
-            task::spawn(async move {
-                event_handler::event_handler(
-                    event_handler_twitch_websocket_reciever,
-                    event_handler_exit_handler_reciever,
-                    event_handler_file_watcher_reciever,
-                    event_handler_task_spawner_sender,
-                );
-            });
-            task::spawn(async move {
-                tasks_handler::task_spawner(
-                    task_spawner_event_handler_reciever,
-                    task_spawner_exit_handler_sender,
-                    settings_player.player,
-                    STREAMING_SITE.to_string(),
+/// Warn to create a new token once `user_access_token` is about to expire.
+fn spawn_expiry_warning(
+    user_access_token: &Arc<Option<UserToken>>,
+    event_log: &Arc<EventLog>,
+) -> task::JoinHandle<()> {
+    let user_access_token = user_access_token.clone();
+    let event_log = event_log.clone();
+    task::spawn(async move {
+        let Some(token) = user_access_token.as_ref() else {
+            return;
+        };
+        let remaining =
+            authentication::expiry_warning(token, authentication::EXPIRY_WARNING_THRESHOLD).await;
+        let message = format!(
+            "The user token expires in {} minutes, please create a new token",
+            remaining.as_secs() / 60
+        );
+        eprintln!("{message}");
+        record(&event_log, RecordKind::Reauthenticate, &message);
+    })
+}
+
+/// Open the scheduled `vods` at their time with the player of the current settings.
+fn spawn_vods(settings: &SharedSettings, vods: Vec<ScheduledVod>) {
+    if vods.is_empty() {
+        return;
+    }
+    let (launch_sender, mut launch_reciever) = mpsc::channel(5);
+    task::spawn(vods::vod_scheduler(vods, SystemTime::now(), launch_sender));
+    let settings = settings.clone();
+    task::spawn(async move {
+        while let Some(vod) = launch_reciever.recv().await {
+            let (player, quality, options) = {
+                let settings = settings.read().expect("RwLock poisoned");
+                let profile_quality = Quality::Height(settings.profile().1);
+                (
+                    settings.player,
+                    vod.quality.unwrap_or(profile_quality),
+                    settings.player_options(),
                 )
-                .await;
-            });
+            };
+            eprintln!("Opening the VOD {} scheduled at {}", vod.url, vod.at);
+            let play = player::get_stream(
+                player,
+                vod.url,
+                &quality,
+                true,
+                Latency::Normal,
+                &[],
+                &options,
+            )
+            .await;
             task::spawn(async move {
-                tasks_handler::exit_handler(
-                    exit_handler_task_spawner_reciever,
-                    exit_handler_event_handler_sender,
-                    restart_signal_sender_exit_handler,
-                    SEARCH_CHANNEL_API.to_string(),
-                    user_access_token_exit_handler,
-                    CLIENT_ID,
-                )
-                .await;
+                if let Err(error) = play.await {
+                    eprintln!("Error while playing VOD: {error}");
+                }
             });
+        }
+    });
+}
 
-            if let Some(code) = restart_signal_reciever.recv().await {
-                match code {
-                    1 => continue,
-                    2 => break,
-                    _ => println!("Unrecognized code"),
+fn record(event_log: &EventLog, kind: RecordKind, message: &str) {
+    if let Err(error) = event_log.record(&EventRecord::new(kind, message.to_string())) {
+        eprintln!("Error while recording event: {error}");
+    }
+}
+
+async fn wait_for_token(token_file: &Path, scopes: &[String]) -> Option<UserToken> {
+    let mut token: Option<UserToken> = None;
+    loop {
+        match authentication::validate_oauth_token(&mut token, token_file, true).await {
+            Ok(()) => {
+                if let Some(user_token) = &token {
+                    let missing = authentication::missing_scopes(user_token, scopes);
+                    if !missing.is_empty() {
+                        eprintln!(
+                            "The token was not granted the scopes {}, please create a new token",
+                            missing.join(", ")
+                        );
+                    }
                 }
-            } else {
-                return;
+                return token;
+            }
+            Err(error) => {
+                eprintln!(
+                    "Error {error}.\nPlease retry creating a token. Re-checking token in 60 seconds"
+                );
+                tokio::time::sleep(Duration::from_mins(1)).await;
             }
         }
     }
 }
+
+fn player_launch(
+    settings: &Settings,
+    user_access_token: Option<&UserToken>,
+) -> tasks_handler::PlayerLaunch {
+    tasks_handler::PlayerLaunch {
+        options: player::PlayerOptions {
+            streamlink: player::StreamlinkOptions {
+                disable_ads: settings.streamlink_ads,
+                auth_config: settings
+                    .streamlink_ads
+                    .then(|| streamlink_auth_config(settings, user_access_token))
+                    .flatten(),
+            },
+            ..settings.player_options()
+        },
+        log_dir: settings.player_log_dir.clone(),
+        recording_dir: settings.recording_dir(),
+        record_max_minutes: settings.record_max_minutes,
+        minimized: settings.launch_minimized,
+        startup_timeout: settings
+            .player_startup_timeout_secs
+            .map(Duration::from_secs),
+        ..tasks_handler::PlayerLaunch::new(settings.player, settings.endpoints.website.clone())
+    }
+}
+
+/// Write the current user token to the streamlink config file next to the token file. The token
+/// file is read again, so players launched after a refresh get the refreshed token.
+fn streamlink_auth_config(
+    settings: &Settings,
+    user_access_token: Option<&UserToken>,
+) -> Option<PathBuf> {
+    let token_file = settings.token_file();
+    let token = UserData::from_file(&token_file)
+        .map(|user_data| user_data.access_token)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .or_else(|| user_access_token.map(|token| token.access_token.secret().to_string()))?;
+    let directory = token_file.parent().unwrap_or_else(|| Path::new("."));
+    player::write_auth_config(directory, &token)
+        .inspect_err(|error| {
+            eprintln!("Error writing the streamlink config, playing without the token: {error}");
+        })
+        .ok()
+}
+
+/// The launch options of the current settings, read again for every player.
+fn player_launcher(
+    settings: &SharedSettings,
+    user_access_token: &Arc<Option<UserToken>>,
+) -> impl Fn() -> tasks_handler::PlayerLaunch + Send {
+    let settings = settings.clone();
+    let user_access_token = user_access_token.clone();
+    move || {
+        player_launch(
+            &settings.read().expect("RwLock poisoned"),
+            user_access_token.as_ref().as_ref(),
+        )
+    }
+}
+
+fn live_details_lookup(
+    settings: &Settings,
+    user_access_token: &Arc<Option<UserToken>>,
+) -> Option<tasks_handler::LiveDetailsLookup> {
+    settings
+        .live_details
+        .then(|| streams_lookup(settings, user_access_token))
+}
+
+/// The streams api of `settings`, used to look up the category of streams going live.
+fn streams_lookup(
+    settings: &Settings,
+    user_access_token: &Arc<Option<UserToken>>,
+) -> tasks_handler::LiveDetailsLookup {
+    tasks_handler::LiveDetailsLookup {
+        streams_api_url: settings.endpoints.streams(),
+        user_access_token: user_access_token.clone(),
+        client_id: CLIENT_ID.to_string(),
+    }
+}
+
+/// Launch streams with the quality of the profile, checking their category with `settings` and
+/// closing the other streams of their exclusive groups in `streams`.
+fn launch_policy(
+    settings: &Settings,
+    streams: &Mutex<Streams>,
+    user_access_token: &Arc<Option<UserToken>>,
+) -> event_handler::LaunchPolicy {
+    event_handler::LaunchPolicy {
+        category_lookup: Some(streams_lookup(settings, user_access_token)),
+        groups: streams
+            .lock()
+            .expect("Mutex lock poisoned")
+            .groups()
+            .to_vec(),
+        ..event_handler::LaunchPolicy::default()
+    }
+}
+
+fn spawn_catch_up(
+    streams: Streams,
+    streams_api_url: String,
+    event_handler_sender: mpsc::Sender<control::SocketEvent>,
+    user_access_token: Arc<Option<UserToken>>,
+) {
+    task::spawn(async move {
+        let Some(token) = user_access_token.as_ref() else {
+            return;
+        };
+        match twitch_socket::catch_up_live_streams(
+            &streams,
+            &event_handler_sender,
+            &streams_api_url,
+            token,
+            CLIENT_ID,
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(launched) => eprintln!("{launched} streams were already live, launching them"),
+            Err(error) => eprintln!("Error while checking for live streams: {error}"),
+        }
+    });
+}
+
+fn spawn_tasks(
+    shared_settings: &SharedSettings,
+    schedule: &Schedule,
+    user_access_token: &Arc<Option<UserToken>>,
+    event_log: &Arc<EventLog>,
+    active_players: &tasks_handler::ActivePlayers,
+    event_injector: &control::EventInjector,
+    shutdown: &broadcast::Sender<()>,
+) -> (mpsc::Receiver<u8>, task::JoinHandle<()>) {
+    let streams = schedule.streams.clone();
+    let schedule_source = schedule.source.clone();
+    let settings = shared_settings.read().expect("RwLock poisoned");
+    let event_handler_settings = shared_settings.clone();
+
+    let user_access_token_websocket = user_access_token.clone();
+    let user_access_token_exit_handler = user_access_token.clone();
+    let task_spawner_players = active_players.clone();
+    let event_handler_players = active_players.clone();
+    let player_launch = player_launcher(shared_settings, user_access_token);
+    let details_lookup = live_details_lookup(&settings, user_access_token);
+    let launch_policy = launch_policy(&settings, &streams, user_access_token);
+    let websocket_url = settings.endpoints.websocket.clone();
+    let subscriptions_url = settings.endpoints.subscriptions();
+    let search_channels_url = settings.endpoints.search_channels();
+    let streams_url = settings.endpoints.streams();
+
+    let (file_watcher_twitch_websocket_sender, twitch_socket_file_watcher_reciever) =
+        mpsc::channel(10);
+    let (file_watcher_event_handler_sender, event_handler_file_watcher_reciever) =
+        mpsc::channel(10);
+    let (twitch_websocket_event_handler_sender, event_handler_twitch_websocket_reciever) =
+        mpsc::channel(10);
+    let (event_handler_task_spawner_sender, task_spawner_event_handler_reciever) =
+        mpsc::channel(10);
+    let (task_spawner_exit_handler_sender, exit_handler_task_spawner_reciever) = mpsc::channel(10);
+    let (exit_handler_event_handler_sender, event_handler_exit_handler_reciever) =
+        mpsc::channel(10);
+    let (task_spawner_notification_handler_sender, notification_handler_task_spawner_reciever) =
+        mpsc::channel(10);
+    let (restart_signal_sender, restart_signal_reciever) = mpsc::channel(1);
+    event_injector.connect(twitch_websocket_event_handler_sender.clone());
+    let restart_signal_sender_exit_handler = restart_signal_sender.clone();
+    let restart_signal_sender_twitch_socket = restart_signal_sender;
+
+    // Players of the previous connection stay in `active_players`, so only the streams that went
+    // live while reconnecting are launched
+    spawn_catch_up(
+        streams.lock().expect("Mutex lock poisoned").clone(),
+        settings.endpoints.streams(),
+        twitch_websocket_event_handler_sender.clone(),
+        user_access_token.clone(),
+    );
+
+    spawn_notifications(
+        shared_settings,
+        event_log,
+        &streams,
+        notification_handler_task_spawner_reciever,
+    );
+    task::spawn(async move {
+        file_watcher::file_watcher(
+            file_watcher_twitch_websocket_sender,
+            file_watcher_event_handler_sender,
+            &schedule_source,
+            &streams,
+        )
+        .await;
+    });
+    twitch_socket::twitch_websocket(
+        twitch_socket_file_watcher_reciever,
+        twitch_websocket_event_handler_sender,
+        restart_signal_sender_twitch_socket,
+        websocket_url,
+        subscriptions_url,
+        user_access_token_websocket,
+        CLIENT_ID,
+        settings.max_reconnect_attempts,
+        twitch_socket::SubscriptionCost::default(),
+        shutdown.subscribe(),
+    );
+    task::spawn(async move {
+        event_handler::event_handler(
+            event_handler_twitch_websocket_reciever,
+            event_handler_exit_handler_reciever,
+            event_handler_file_watcher_reciever,
+            event_handler_task_spawner_sender,
+            event_handler_settings,
+            event_handler_players,
+            launch_policy,
+        );
+    });
+    let task_spawner = task::spawn(tasks_handler::task_spawner(
+        task_spawner_event_handler_reciever,
+        task_spawner_exit_handler_sender,
+        task_spawner_notification_handler_sender,
+        player_launch,
+        details_lookup,
+        task_spawner_players,
+        shutdown.subscribe(),
+    ));
+    task::spawn(tasks_handler::exit_handler(
+        exit_handler_task_spawner_reciever,
+        exit_handler_event_handler_sender,
+        restart_signal_sender_exit_handler,
+        search_channels_url,
+        streams_url,
+        user_access_token_exit_handler,
+        CLIENT_ID,
+    ));
+
+    drop(settings);
+
+    (restart_signal_reciever, task_spawner)
+}
+
+fn spawn_notifications(
+    settings: &SharedSettings,
+    event_log: &Arc<EventLog>,
+    streams: &Arc<Mutex<Streams>>,
+    notification_handler_task_spawner_reciever: mpsc::Receiver<StreamEvent>,
+) {
+    let settings = settings.clone();
+    let streams = streams.clone();
+    let event_log_notifier: Box<dyn notifier::Notifier> = Box::new(EventLogNotifier {
+        log: event_log.clone(),
+    });
+    task::spawn(async move {
+        notifier::notification_handler_with_settings(
+            notification_handler_task_spawner_reciever,
+            settings,
+            vec![event_log_notifier],
+            streams,
+        )
+        .await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{net::TcpListener, time::timeout};
+
+    #[test]
+    fn report_missing_stream_players() {
+        let mut settings = Settings::new(Path::new("./tests/resources"));
+        settings.player = stream_cave::Player::Custom;
+        settings.custom_player = Some(player::CustomPlayer {
+            template: String::from("stream-cave-nonexistent-player {url}"),
+            audio_flag: String::new(),
+        });
+        let mut streams = Streams::read_streams(Path::new("./tests/resources"));
+        streams.get_mut("kaicenat").unwrap().player = Some(stream_cave::Player::Custom);
+
+        let missing = missing_players(&settings, &streams, &[]);
+
+        assert_eq!(1, missing.len());
+        assert!(missing[0].contains("`stream-cave-nonexistent-player`"));
+        assert!(missing[0].contains("the `player` setting in config.json"));
+        assert!(missing[0].contains("the `player` of kaicenat in the schedule"));
+    }
+
+    #[tokio::test]
+    async fn connect_to_overridden_websocket() {
+        let directory = std::env::temp_dir().join("stream-caved-websocket");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut settings = Settings::new(&directory);
+        settings.endpoints.websocket = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let shared_settings = Arc::new(RwLock::new(settings));
+        let schedule = Schedule {
+            streams: Arc::new(Mutex::new(Streams::read_streams(Path::new(
+                "./tests/resources",
+            )))),
+            source: file_watcher::ScheduleSource::Stdin,
+        };
+        let event_log = Arc::new(EventLog::new(&directory.join("events.log"), 10));
+
+        let (_restart_signal_reciever, _task_spawner) = spawn_tasks(
+            &shared_settings,
+            &schedule,
+            &Arc::new(None),
+            &event_log,
+            &tasks_handler::ActivePlayers::default(),
+            &control::EventInjector::default(),
+            &broadcast::channel(1).0,
+        );
+        let connection = timeout(Duration::from_secs(5), listener.accept()).await;
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(connection.unwrap().is_ok());
+    }
+}
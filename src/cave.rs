@@ -1,31 +1,213 @@
 pub mod authentication;
+pub mod control;
 pub mod event_handler;
+pub mod event_log;
+pub mod events;
 pub mod file_watcher;
+pub mod http;
+pub mod keyring;
+#[cfg(test)]
+mod mock_api;
+pub mod notifier;
 pub mod player;
+pub mod quality;
+pub mod report;
+pub mod schema;
 pub mod tasks_handler;
 pub mod twitch_socket;
+pub mod vods;
 
-use serde::{Deserialize, Serialize};
+use futures_util::future::join_all;
+use http::{RequestTimeouts, TwitchEndpoints};
+use keyring::{Keyring, SecretTool};
+use notifier::NotifierConfig;
+use quality::Quality;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::read_to_string,
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
-use twitch_oauth2::UserToken;
+use tokio::sync::Semaphore;
+use twitch_oauth2::{TwitchToken, UserToken};
+use twitch_socket::api_structs::{EventType, ScheduleSegment, StreamData};
+use vods::ScheduledVod;
 
 /// Which player process to use
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Player {
     Mpv,
     Streamlink,
+    Vlc,
+    /// The command of the `custom_player` setting
+    Custom,
+}
+
+impl std::str::FromStr for Player {
+    type Err = String;
+
+    /// Read a player name, ignoring case.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Player;
+    ///
+    /// assert_eq!(Ok(Player::Vlc), "vlc".parse());
+    /// assert_eq!(Ok(Player::Streamlink), "Streamlink".parse());
+    /// assert!("totem".parse::<Player>().is_err());
+    /// ```
+    fn from_str(player: &str) -> Result<Self, Self::Err> {
+        match player.trim().to_lowercase().as_str() {
+            "mpv" => Ok(Self::Mpv),
+            "streamlink" => Ok(Self::Streamlink),
+            "vlc" => Ok(Self::Vlc),
+            "custom" => Ok(Self::Custom),
+            _ => Err(format!(
+                "Unknown player {player}, the players are mpv, streamlink, vlc and custom"
+            )),
+        }
+    }
+}
+
+/// Where the user token is stored
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStorage {
+    /// The token file, in plain text
+    #[default]
+    File,
+    /// The Secret Service keyring through `secret-tool`, the token file only keeps the login.
+    /// Without `secret-tool`, such as on macOS and Windows, the token is written to the token
+    /// file after a warning
+    Keyring,
+}
+
+/// The stream latency requested from the player
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Latency {
+    #[default]
+    Normal,
+    /// Low-latency HLS, for streams that are watched while chatting
+    Low,
 }
 
 /// The configuration settings of the program.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Settings {
     ver: (u8, u8),
     pub player: Player,
+    /// The program of the player, `mpv` or `streamlink` is looked up in `PATH` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_path: Option<PathBuf>,
+    /// Arguments passed to the player of every stream, such as `--hwdec=auto`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_extra_args: Vec<String>,
+    /// The command template of the `Custom` player, see `player::CustomPlayer`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_player: Option<player::CustomPlayer>,
     pub schedule: PathBuf,
-    pub profile: (String, u16),
+    /// The height of the streams played with each profile, by name
+    #[serde(default = "default_profiles")]
+    pub profiles: BTreeMap<String, u16>,
+    /// The profile streams are played with, one of `profiles`
+    #[serde(default = "default_profile_name")]
+    pub default_profile: String,
+    /// The single profile of older config files, moved to `profiles` when read
+    #[serde(default, rename = "profile", skip_serializing)]
+    #[schemars(skip)]
+    legacy_profile: Option<(String, u16)>,
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+    #[serde(default)]
+    pub timeouts: RequestTimeouts,
+    /// Pass `--twitch-disable-ads` and the user token to streamlink, the token is written to
+    /// `streamlink-auth.conf` next to the token file instead of the command line
+    #[serde(default)]
+    pub streamlink_ads: bool,
+    /// The maximum total cost of the event subscriptions allowed for the app
+    #[serde(default = "default_max_subscription_cost")]
+    pub max_subscription_cost: u32,
+    /// The file storing the user token, `user-data.json` in the schedule directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_path: Option<PathBuf>,
+    /// Where new user tokens are stored, tokens that cannot be stored in the keyring are written
+    /// to the token file. The keyring needs `secret-tool` of libsecret, so it is only available
+    /// on Linux desktops with a Secret Service keyring
+    #[serde(default)]
+    pub token_storage: TokenStorage,
+    /// Look up the viewer count and start time of streams going live for the notifications,
+    /// costs one api request per live event
+    #[serde(default)]
+    pub live_details: bool,
+    /// The user agent sent to the Twitch api, `stream-cave/<version>` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// The directory the output of each player is written to as `<name>.log`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_log_dir: Option<PathBuf>,
+    /// The directory the recordings of the streams with `record` are written to, `recordings` in
+    /// the schedule directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    /// Split the recordings of streamlink into numbered segments of this many minutes with
+    /// ffmpeg, recordings are not split when unset. Recordings of mpv are never split
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_max_minutes: Option<u32>,
+    /// The latency of streams without their own latency
+    #[serde(default)]
+    pub latency: Latency,
+    /// The urls of the Twitch api, websocket and website
+    #[serde(default)]
+    pub endpoints: TwitchEndpoints,
+    /// Start players minimized so they do not take the focus from the current window
+    #[serde(default)]
+    pub launch_minimized: bool,
+    /// Seconds a player may take to start playing before it is killed and restarted, players
+    /// are never killed when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_startup_timeout_secs: Option<u64>,
+    /// The OAuth scopes requested when creating the user token, the daemon warns at startup when
+    /// the token was not granted one of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// The number of live status requests sent at once by bulk checks such as `list --live`
+    #[serde(default = "default_live_check_concurrency")]
+    pub live_check_concurrency: usize,
+    /// The number of failed attempts to reconnect to the `EventSub` websocket after which the
+    /// daemon reports it as unreachable, it keeps reconnecting forever when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_reconnect_attempts: Option<u32>,
+}
+
+/// Settings shared by the daemon tasks, updated in place when the config file is reloaded.
+pub type SharedSettings = Arc<RwLock<Settings>>;
+
+const fn default_max_subscription_cost() -> u32 {
+    twitch_socket::DEFAULT_MAX_SUBSCRIPTION_COST
+}
+
+const fn default_notify() -> bool {
+    true
+}
+
+/// The profile of new config files and the height streams are played at with it.
+pub const DEFAULT_PROFILE: (&str, u16) = ("normal", 1080);
+
+fn default_profiles() -> BTreeMap<String, u16> {
+    BTreeMap::from([(String::from(DEFAULT_PROFILE.0), DEFAULT_PROFILE.1)])
+}
+
+fn default_profile_name() -> String {
+    String::from(DEFAULT_PROFILE.0)
+}
+
+/// The number of live status requests sent at once when not configured.
+pub const DEFAULT_LIVE_CHECK_CONCURRENCY: usize = 10;
+
+const fn default_live_check_concurrency() -> usize {
+    DEFAULT_LIVE_CHECK_CONCURRENCY
 }
 
 impl Settings {
@@ -40,26 +222,279 @@ impl Settings {
     ///
     /// assert_eq!(Player::Mpv, settings.player);
     /// assert_eq!(PathBuf::from("./"), settings.schedule);
-    /// assert_eq!((String::from("normal"), 1080), settings.profile);
+    /// assert_eq!((String::from("normal"), 1080), settings.profile());
+    /// assert!(settings.notifiers.is_empty());
+    /// assert!(!settings.streamlink_ads);
+    /// assert!(!settings.live_details);
     /// ```
     #[must_use]
     pub fn new(path: &Path) -> Self {
         let ver = (0, 1);
         let player = Player::Mpv;
+        let player_path = None;
+        let player_extra_args = Vec::new();
+        let custom_player = None;
         let schedule = path.to_path_buf();
-        let profile = (String::from("normal"), 1080);
+        let profiles = default_profiles();
+        let default_profile = default_profile_name();
+        let legacy_profile = None;
+        let notifiers = Vec::new();
+        let timeouts = RequestTimeouts::default();
+        let streamlink_ads = false;
+        let max_subscription_cost = default_max_subscription_cost();
+        let token_path = None;
+        let token_storage = TokenStorage::File;
+        let live_details = false;
+        let user_agent = None;
+        let player_log_dir = None;
+        let output_dir = None;
+        let record_max_minutes = None;
+        let latency = Latency::Normal;
+        let endpoints = TwitchEndpoints::default();
+        let launch_minimized = false;
+        let player_startup_timeout_secs = None;
+        let scopes = Vec::new();
+        let live_check_concurrency = DEFAULT_LIVE_CHECK_CONCURRENCY;
+        let max_reconnect_attempts = None;
 
         Self {
             ver,
             player,
+            player_path,
+            player_extra_args,
+            custom_player,
             schedule,
-            profile,
+            profiles,
+            default_profile,
+            legacy_profile,
+            notifiers,
+            timeouts,
+            streamlink_ads,
+            max_subscription_cost,
+            token_path,
+            token_storage,
+            live_details,
+            user_agent,
+            player_log_dir,
+            output_dir,
+            record_max_minutes,
+            latency,
+            endpoints,
+            launch_minimized,
+            player_startup_timeout_secs,
+            scopes,
+            live_check_concurrency,
+            max_reconnect_attempts,
+        }
+    }
+
+    /// The name and height of the default profile, the `DEFAULT_PROFILE` when the default
+    /// profile is not one of the profiles.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Settings;
+    ///
+    /// let mut settings = Settings::new(Path::new("./"));
+    /// settings.profiles.insert(String::from("mobile"), 480);
+    /// settings.default_profile = String::from("mobile");
+    ///
+    /// assert_eq!((String::from("mobile"), 480), settings.profile());
+    /// ```
+    #[must_use]
+    pub fn profile(&self) -> (String, u16) {
+        self.profiles.get(&self.default_profile).map_or_else(
+            || (String::from(DEFAULT_PROFILE.0), DEFAULT_PROFILE.1),
+            |height| (self.default_profile.clone(), *height),
+        )
+    }
+
+    /// The player program, extra arguments and custom player of the settings, without
+    /// streamlink options.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use stream_cave::Settings;
+    ///
+    /// let mut settings = Settings::new(Path::new("./"));
+    /// settings.player_path = Some(PathBuf::from("/opt/mpv/bin/mpv"));
+    /// settings.player_extra_args.push(String::from("--hwdec=auto"));
+    ///
+    /// let options = settings.player_options();
+    /// assert_eq!(Some(PathBuf::from("/opt/mpv/bin/mpv")), options.path);
+    /// assert_eq!(vec![String::from("--hwdec=auto")], options.extra_args);
+    /// ```
+    #[must_use]
+    pub fn player_options(&self) -> player::PlayerOptions {
+        player::PlayerOptions {
+            path: self.player_path.clone(),
+            extra_args: self.player_extra_args.clone(),
+            custom: self.custom_player.clone(),
+            ..player::PlayerOptions::default()
+        }
+    }
+
+    /// Move the `profile` of older config files to `profiles` and check that the default profile
+    /// is one of the profiles.
+    fn migrate_profile(mut self) -> serde_json::Result<Self> {
+        if let Some((name, height)) = self.legacy_profile.take() {
+            eprintln!(
+                "The profile setting is deprecated, replace it with \"profiles\": {{\"{name}\": {height}}} and \"default_profile\": \"{name}\""
+            );
+            self.profiles = BTreeMap::from([(name.clone(), height)]);
+            self.default_profile = name;
+        }
+        if !self.profiles.contains_key(&self.default_profile) {
+            return Err(serde::de::Error::custom(format!(
+                "The default profile \"{}\" is not one of the profiles",
+                self.default_profile
+            )));
+        }
+        Ok(self)
+    }
+
+    /// Check that the `Custom` player has a valid `custom_player` template.
+    fn check_custom_player(self) -> serde_json::Result<Self> {
+        if self.player != Player::Custom {
+            return Ok(self);
+        }
+        let Some(custom_player) = &self.custom_player else {
+            return Err(serde::de::Error::custom(
+                "The player is Custom but custom_player is not set",
+            ));
+        };
+        custom_player.check().map_err(serde::de::Error::custom)?;
+        Ok(self)
+    }
+
+    /// The file storing the user token.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use stream_cave::Settings;
+    ///
+    /// let mut settings = Settings::new(Path::new("./"));
+    /// assert_eq!(PathBuf::from("./user-data.json"), settings.token_file());
+    ///
+    /// settings.token_path = Some(PathBuf::from("/run/secrets/stream-cave.json"));
+    /// assert_eq!(PathBuf::from("/run/secrets/stream-cave.json"), settings.token_file());
+    /// ```
+    #[must_use]
+    pub fn token_file(&self) -> PathBuf {
+        self.token_path
+            .clone()
+            .unwrap_or_else(|| self.schedule.join("user-data.json"))
+    }
+
+    /// The directory recordings are written to.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use stream_cave::Settings;
+    ///
+    /// let mut settings = Settings::new(Path::new("./"));
+    /// assert_eq!(PathBuf::from("./recordings"), settings.recording_dir());
+    ///
+    /// settings.output_dir = Some(PathBuf::from("/srv/archive"));
+    /// assert_eq!(PathBuf::from("/srv/archive"), settings.recording_dir());
+    /// ```
+    #[must_use]
+    pub fn recording_dir(&self) -> PathBuf {
+        self.output_dir
+            .clone()
+            .unwrap_or_else(|| self.schedule.join("recordings"))
+    }
+
+    /// Apply the `reloaded` settings of a changed config file. Settings only read when the daemon
+    /// starts keep their current value, the names of those that changed are returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use stream_cave::Settings;
+    ///
+    /// let mut settings = Settings::new(Path::new("./"));
+    /// let mut reloaded = Settings::new(Path::new("./"));
+    /// reloaded.profiles.insert(String::from("mobile"), 480);
+    /// reloaded.default_profile = String::from("mobile");
+    /// reloaded.token_path = Some(PathBuf::from("./token.json"));
+    ///
+    /// assert_eq!(vec!["token_path"], settings.reload(reloaded));
+    /// assert_eq!((String::from("mobile"), 480), settings.profile());
+    /// assert_eq!(None, settings.token_path);
+    /// ```
+    pub fn reload(&mut self, mut reloaded: Self) -> Vec<&'static str> {
+        let mut restart_needed = Vec::new();
+        if reloaded.schedule != self.schedule {
+            restart_needed.push("schedule");
+            std::mem::swap(&mut reloaded.schedule, &mut self.schedule);
+        }
+        if reloaded.timeouts != self.timeouts {
+            restart_needed.push("timeouts");
+            reloaded.timeouts = self.timeouts;
+        }
+        if reloaded.max_subscription_cost != self.max_subscription_cost {
+            restart_needed.push("max_subscription_cost");
+            reloaded.max_subscription_cost = self.max_subscription_cost;
+        }
+        if reloaded.token_path != self.token_path {
+            restart_needed.push("token_path");
+            std::mem::swap(&mut reloaded.token_path, &mut self.token_path);
+        }
+        if reloaded.live_details != self.live_details {
+            restart_needed.push("live_details");
+            reloaded.live_details = self.live_details;
+        }
+        if reloaded.user_agent != self.user_agent {
+            restart_needed.push("user_agent");
+            std::mem::swap(&mut reloaded.user_agent, &mut self.user_agent);
+        }
+        if reloaded.endpoints != self.endpoints {
+            restart_needed.push("endpoints");
+            std::mem::swap(&mut reloaded.endpoints, &mut self.endpoints);
+        }
+        if reloaded.scopes != self.scopes {
+            restart_needed.push("scopes");
+            std::mem::swap(&mut reloaded.scopes, &mut self.scopes);
+        }
+        *self = reloaded;
+        restart_needed
+    }
+
+    /// The config file at `path`, either a json file or the `config.json` in the directory
+    /// `path`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use stream_cave::Settings;
+    ///
+    /// assert_eq!(PathBuf::from("./config.json"), Settings::config_file(Path::new("./")));
+    /// assert_eq!(
+    ///     PathBuf::from("/etc/stream-cave/myconfig.json"),
+    ///     Settings::config_file(Path::new("/etc/stream-cave/myconfig.json"))
+    /// );
+    /// ```
+    #[must_use]
+    pub fn config_file(path: &Path) -> PathBuf {
+        if path.is_file()
+            || path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+        {
+            path.to_path_buf()
+        } else {
+            path.join("config.json")
         }
     }
 
     /// Populate settings with configurations from a file.
-    /// Takes a slice of directories to look for config files.
-    /// Creates a new config file in the last directory if no
+    /// Takes a slice of directories or config files to look for config files, see `config_file`.
+    /// Creates a new config file in the last path if no
     /// config files are found.
     /// # Examples
     /// ```no_run
@@ -77,36 +512,38 @@ impl Settings {
     /// if writing to the file fails
     pub fn read_config(paths: &[PathBuf]) -> std::io::Result<Self> {
         for path in paths {
-            if path.as_path().join("config.json").exists() {
-                let config = std::fs::read_to_string(path.join("config.json"));
+            let config_file = Self::config_file(path);
+            let directory = config_file.parent().unwrap_or_else(|| Path::new(""));
+            if config_file.exists() {
+                let config = std::fs::read_to_string(&config_file);
                 match config {
-                    Ok(settings) => match serde_json::from_str(&settings) {
+                    Ok(settings) => match Self::from_reader(settings.as_bytes()) {
                         Ok(json) => return Ok(json),
                         Err(error) => {
                             eprintln!("Error deserializing data: {error}");
-                            return Ok(Self::new(path));
+                            return Ok(Self::new(directory));
                         }
                     },
                     Err(error) => {
                         eprintln!("Error opening config file: {error}");
-                        return Ok(Self::new(path));
+                        return Ok(Self::new(directory));
                     }
                 }
             }
         }
 
-        let local_path = paths.last().map_or_else(|| Path::new(""), |path| path);
+        let local_path = paths.last().map_or_else(|| Path::new(""), PathBuf::as_path);
+        let config_file = Self::config_file(local_path);
+        let directory = config_file.parent().unwrap_or_else(|| Path::new(""));
 
-        let new_settings = Self::new(local_path);
+        let new_settings = Self::new(directory);
         if let Ok(data) = serde_json::to_string(&new_settings) {
-            match std::fs::create_dir(local_path) {
+            match std::fs::create_dir(directory) {
                 Ok(()) => {
-                    let local_path = local_path.join("config.json");
-                    std::fs::write(&local_path, data)?;
+                    write_with_backup(&config_file, &data)?;
                 }
                 Err(ref error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
-                    let local_path = local_path.join("config.json");
-                    std::fs::write(&local_path, data)?;
+                    write_with_backup(&config_file, &data)?;
                 }
                 Err(error) => return Err(error),
             }
@@ -114,12 +551,133 @@ impl Settings {
 
         Ok(new_settings)
     }
+
+    /// Read settings from the config json in `reader`, such as stdin. Nothing is written, unlike
+    /// `read_config` no config file is created. The `profile` of older config files is read as
+    /// the only profile.
+    ///
+    /// # Errors
+    /// Will return an error if `reader` fails or does not contain valid settings, if the default
+    /// profile is not one of the profiles or if the `Custom` player has no valid template.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Settings;
+    ///
+    /// let json = serde_json::to_string(&Settings::new(Path::new("./"))).unwrap();
+    /// let settings = Settings::from_reader(json.as_bytes()).unwrap();
+    ///
+    /// assert_eq!(Path::new("./"), settings.schedule);
+    /// ```
+    pub fn from_reader(reader: impl std::io::Read) -> serde_json::Result<Self> {
+        serde_json::from_reader::<_, Self>(reader)?
+            .migrate_profile()?
+            .check_custom_player()
+    }
+}
+
+/// Write `data` to `path` without ever leaving it partially written. The data is written and
+/// synced to a temporary file in the same directory which is then renamed over `path`, the
+/// previous contents of `path` are kept in a `.bak` file next to it.
+fn write_with_backup(path: &Path, data: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+    let backup_path = path.with_file_name(format!("{file_name}.bak"));
+
+    let result = std::fs::File::create(&temp_path).and_then(|mut file| {
+        file.write_all(data.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(error) = result {
+        let _ = std::fs::remove_file(&temp_path);
+        return Err(error);
+    }
+    if path.exists() {
+        std::fs::copy(path, backup_path)?;
+    }
+    std::fs::rename(&temp_path, path)
+}
+
+/// How `Streams::merge` resolves a stream or group in both schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the existing stream unchanged
+    KeepMine,
+    /// Replace the existing stream with the merged one
+    TakeTheirs,
+    /// Keep the existing stream and add the quality overrides and the streams to open and close
+    /// on that it is missing
+    MergeOverrides,
+}
+
+/// Append the names in `other` missing from `names`.
+fn merge_names(names: &mut Vec<String>, other: Vec<String>) {
+    for name in other {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+}
+
+/// The exclusive lock of a schedule taken by `Streams::lock`, released when dropped.
+#[derive(Debug)]
+pub struct ScheduleLock {
+    _file: std::fs::File,
 }
 
 /// Contains settings for the twitch streams to watch
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[allow(clippy::struct_field_names)]
 pub struct Streams {
     streams: Vec<StreamConfig>,
+    /// Other schedule files, relative to the schedule directory, whose streams are merged in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<PathBuf>,
+    /// Names of the streams read from included files, these are not written back
+    #[serde(skip)]
+    included: Vec<String>,
+    /// Named groups of streams
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<StreamGroup>,
+    /// VODs opened once at a set time, only read from the primary schedule file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    vods: Vec<ScheduledVod>,
+}
+
+/// A named group of streams
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct StreamGroup {
+    pub name: String,
+    /// The names of the streams in the group
+    pub streams: Vec<String>,
+    /// Only play one stream of the group at a time, a stream going live closes the players of
+    /// the other streams
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclusive: bool,
+}
+
+/// The streams opened and closed by a stream going live, see `Streams::plan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LivePlan {
+    pub opens: Vec<String>,
+    pub closes: Vec<String>,
+}
+
+impl std::fmt::Display for LivePlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "streams to open:")?;
+        for name in &self.opens {
+            writeln!(f, "  {name}")?;
+        }
+        writeln!(f, "streams to close:")?;
+        for name in &self.closes {
+            writeln!(f, "  {name}")?;
+        }
+        Ok(())
+    }
 }
 
 impl Streams {
@@ -135,9 +693,181 @@ impl Streams {
     pub const fn new() -> Self {
         Self {
             streams: Vec::new(),
+            include: Vec::new(),
+            included: Vec::new(),
+            groups: Vec::new(),
+            vods: Vec::new(),
+        }
+    }
+
+    /// Check if a stream named `name` is configured.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// assert!(!Streams::new().contains("kaicenat"));
+    /// ```
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Get the configuration of the stream named `name`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::Streams;
+    /// use std::path::Path;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./"));
+    ///
+    /// if let Some(stream) = streams.get("kaicenat") {
+    ///     println!("{stream}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&StreamConfig> {
+        self.streams.iter().find(|stream| stream.name == name)
+    }
+
+    /// Get the configuration of the stream named `name` for changing it.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::Streams;
+    /// use std::path::Path;
+    ///
+    /// let mut streams = Streams::read_streams(Path::new("./"));
+    ///
+    /// if let Some(stream) = streams.get_mut("kaicenat") {
+    ///     stream.resume = true;
+    /// }
+    /// ```
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut StreamConfig> {
+        self.streams.iter_mut().find(|stream| stream.name == name)
+    }
+
+    /// Iterate over the configured streams.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// assert_eq!(0, Streams::new().iter().count());
+    /// ```
+    pub fn iter(&self) -> std::slice::Iter<'_, StreamConfig> {
+        self.streams.iter()
+    }
+
+    /// The VODs scheduled to be opened once.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// assert!(Streams::new().vods().is_empty());
+    /// ```
+    #[must_use]
+    pub fn vods(&self) -> &[ScheduledVod] {
+        &self.vods
+    }
+
+    /// The named groups of streams.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// assert!(Streams::new().groups().is_empty());
+    /// ```
+    #[must_use]
+    pub fn groups(&self) -> &[StreamGroup] {
+        &self.groups
+    }
+
+    /// The streams that would open and close if the stream `name` went live, without opening or
+    /// closing anything.
+    ///
+    /// A stream opens when one of its `streams_to_open_on` is playing, so opened streams can open
+    /// further streams. A stream closes when one of its `streams_to_close_on` is playing or when
+    /// it shares an exclusive group with a playing stream. Streams that open are never closed.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// let plan = Streams::new().plan("kaicenat");
+    ///
+    /// assert!(plan.opens.is_empty());
+    /// assert!(plan.closes.is_empty());
+    /// ```
+    #[must_use]
+    pub fn plan(&self, name: &str) -> LivePlan {
+        let mut playing = vec![name.to_string()];
+        let mut index = 0;
+        while let Some(trigger) = playing.get(index).cloned() {
+            for stream in &self.streams {
+                if stream.streams_to_open_on.contains(&trigger) && !playing.contains(&stream.name) {
+                    playing.push(stream.name.clone());
+                }
+            }
+            index += 1;
+        }
+
+        let closes = self
+            .streams
+            .iter()
+            .filter(|stream| !playing.contains(&stream.name))
+            .filter(|stream| {
+                stream
+                    .streams_to_close_on
+                    .iter()
+                    .any(|trigger| playing.contains(trigger))
+                    || self.groups.iter().any(|group| {
+                        group.exclusive
+                            && group.streams.contains(&stream.name)
+                            && group.streams.iter().any(|peer| playing.contains(peer))
+                    })
+            })
+            .map(|stream| stream.name.clone())
+            .collect();
+        LivePlan {
+            opens: playing.split_off(1),
+            closes,
         }
     }
 
+    /// Take the exclusive lock of the schedule in directory `path`, waiting for other holders to
+    /// release it. Hold the lock while reading, changing and writing the schedule so that
+    /// concurrent edits do not overwrite each other. The lock is released when dropped.
+    ///
+    /// # Errors
+    /// Will return an error if the lock file cannot be created or locked.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::Streams;
+    /// use std::path::Path;
+    ///
+    /// let path = Path::new("./");
+    /// let lock = Streams::lock(&path).unwrap();
+    /// let mut streams = Streams::read_streams(&path);
+    /// streams.remove_stream("kaicenat");
+    /// streams.write(&path).unwrap();
+    /// drop(lock);
+    /// ```
+    pub fn lock(path: &Path) -> std::io::Result<ScheduleLock> {
+        std::fs::create_dir_all(path)?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.join("schedule.lock"))?;
+        file.lock()?;
+        Ok(ScheduleLock { _file: file })
+    }
+
     /// Read stream settings from file in directory
     ///
     /// # Examples
@@ -167,31 +897,104 @@ impl Streams {
         }
     }
 
-    /// Write data in struct to file
+    /// Read stream settings from the schedule json in `reader`, such as stdin. The `include` list
+    /// is not read.
     ///
     /// # Errors
-    /// Will return an error if the Streams struct cannot be serialized, if the cannot be created
-    /// or the file cannot be written to.
+    /// Will return an error if `reader` fails or does not contain a valid schedule.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::from_reader(r#"{"streams":[]}"#.as_bytes()).unwrap();
+    ///
+    /// assert_eq!(0, streams.iter().count());
+    /// ```
+    pub fn from_reader(reader: impl std::io::Read) -> serde_json::Result<Self> {
+        serde_json::from_reader(reader)
+    }
+
+    /// Read stream settings from file in directory, merging in the streams of every file in its
+    /// `include` list. Included files are read relative to `path` and their own includes are
+    /// ignored.
+    ///
+    /// # Errors
+    /// Will return an error if an included file cannot be read or deserialized, or if a stream
+    /// name appears more than once across the files.
     ///
     /// # Examples
     /// ```no_run
-    /// use std::path::Path;
     /// use stream_cave::Streams;
+    /// use std::path::Path;
     ///
     /// let path = Path::new("./");
-    /// let streams = Streams::new();
+    /// let streams = Streams::read_streams_with_includes(&path).unwrap();
+    /// ```
+    pub fn read_streams_with_includes(
+        path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut streams = Self::read_streams(path);
+        let mut names: Vec<String> = Vec::new();
+        for stream in &streams.streams {
+            if names.contains(&stream.name) {
+                return Err(
+                    format!("Duplicate stream \"{}\" in schedule.json", stream.name).into(),
+                );
+            }
+            names.push(stream.name.clone());
+        }
+
+        for include in streams.include.clone() {
+            let file = std::fs::read_to_string(path.join(&include))?;
+            let included: Self = serde_json::from_str(&file)?;
+            for stream in included.streams {
+                if names.contains(&stream.name) {
+                    return Err(format!(
+                        "Duplicate stream \"{}\" in included file {}",
+                        stream.name,
+                        include.display()
+                    )
+                    .into());
+                }
+                names.push(stream.name.clone());
+                streams.included.push(stream.name.clone());
+                streams.streams.push(stream);
+            }
+        }
+        Ok(streams)
+    }
+
+    /// Write data in struct to file. Streams read from included files are left out so the
+    /// included files are not duplicated into the primary schedule.
+    ///
+    /// # Errors
+    /// Will return an error if the Streams struct cannot be serialized, if the cannot be created
+    /// or the file cannot be written to.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let path = Path::new("./");
+    /// let streams = Streams::new();
     /// streams.write(&path).unwrap();
     /// ```
     pub fn write(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let data = serde_json::to_string(self)?;
+        let mut primary = self.clone();
+        primary
+            .streams
+            .retain(|stream| !self.included.contains(&stream.name));
+        let data = serde_json::to_string(&primary)?;
         match std::fs::create_dir(path) {
             Ok(()) => {
                 let local_path = path.join("schedule.json");
-                std::fs::write(&local_path, data)?;
+                write_with_backup(&local_path, &data)?;
             }
             Err(ref error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
                 let local_path = path.join("schedule.json");
-                std::fs::write(&local_path, data)?;
+                write_with_backup(&local_path, &data)?;
             }
             Err(error) => return Err(error.into()),
         }
@@ -200,12 +1003,14 @@ impl Streams {
 
     /// Add a new streamer to the struct
     ///
-    /// # Errors
-    /// Will return an error on failure of the request to get the stream ID, failure to Deserialize
-    /// the search results, and on failure to parse the id of the streamer.
+    /// The stream is not added when its subscriptions would raise the subscription cost of the
+    /// schedule over `max_total_cost`.
     ///
-    /// # Panics
-    /// Will panic when failing to parse a profile quality.
+    /// # Errors
+    /// Will return an error if it canot parse the `quality_overides` vector, when the subscription
+    /// cost would exceed `max_total_cost`, on failure of the request to get the stream ID, failure
+    /// to Deserialize the search results, and on failure to parse the id of the streamer. A
+    /// `TokenError::Invalid` is returned when twitch rejects `user_access_token`.
     ///
     /// # Examples
     /// ```no_run
@@ -221,11 +1026,11 @@ impl Streams {
     /// let path = Path::new("./");
     /// let mut token = None;
     /// validate_oauth_token(&mut token,
-    /// &path, false).await.unwrap();
+    /// &path.join("user-data.json"), false).await.unwrap();
     ///
     /// let mut streams = Streams::new();
     ///
-    /// streams.add_stream("kaicenat", &None, API_SEARCH_URL, CLIENT_ID, token.unwrap()).await.unwrap();
+    /// streams.add_stream("kaicenat", &None, API_SEARCH_URL, CLIENT_ID, token.unwrap(), 10).await.unwrap();
     /// }
     /// ```
     pub async fn add_stream(
@@ -235,58 +1040,64 @@ impl Streams {
         api_url: &str,
         client_id: &str,
         user_access_token: UserToken,
+        max_total_cost: u32,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let mut id = 0;
-        let request = reqwest::Client::new()
-            .get(api_url)
-            .query(&[("query", name)])
-            .bearer_auth(user_access_token.access_token.as_str())
-            .header("Client-Id", client_id)
-            .send()
-            .await?;
-
-        if request.status() == 200 {
-            let search_results = request
-                .json::<crate::twitch_socket::api_structs::StreamSearch>()
-                .await?;
-
-            let Some(stream_status) = search_results
-                .data
-                .iter()
-                .find(|data_set| data_set.broadcaster_login == name)
-            else {
-                eprintln!("Unable to find streamer.");
-                return Ok(());
-            };
-            id = stream_status.id.parse::<u32>()?;
-        };
-
         let quality_overides = quality_overides
-            .as_ref()
-            .map_or_else(Vec::new, |overrides| {
-                overrides
-                    .iter()
-                    .map(|to_parse| {
-                        let overrides_split: Vec<&str> = to_parse.split(',').collect();
-                        let profile = overrides_split[0].to_string();
-                        let quality = overrides_split[1]
-                            .parse::<u16>()
-                            .expect("Unable to parse quality");
-                        (profile, quality)
-                    })
-                    .collect()
-            });
+            .iter()
+            .flatten()
+            .map(|to_parse| {
+                let (profile, quality) = to_parse
+                    .split_once(',')
+                    .ok_or_else(|| format!("Expected <profile>,<quality>, got {to_parse}"))?;
+                Ok((profile.to_string(), quality.parse::<Quality>()?))
+            })
+            .collect::<Result<BTreeMap<_, _>, String>>()?;
 
-        self.streams.push(StreamConfig {
-            name: name.to_string(),
-            id,
+        let mut stream = StreamConfig {
             quality_overides,
-            streams_to_open_on: Vec::new(),
-            streams_to_close_on: Vec::new(),
-        });
+            ..StreamConfig::new(name, 0)
+        };
+        let total_cost = self
+            .subscription_cost()
+            .saturating_add(stream.subscription_cost());
+        if total_cost > max_total_cost {
+            return Err(format!(
+                "Adding {name} would raise the subscription cost to {total_cost}, over the limit of \
+                {max_total_cost}. Remove a stream first or raise `max_subscription_cost` if the \
+                limit of the app was raised"
+            )
+            .into());
+        }
+
+        let Some(id) = resolve_broadcaster_id(name, api_url, client_id, &user_access_token).await?
+        else {
+            eprintln!("Unable to find streamer.");
+            return Ok(());
+        };
+        stream.id = id;
+        self.streams.push(stream);
         Ok(())
     }
 
+    /// The total cost of the subscriptions to the events of every stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./tests/resources"));
+    ///
+    /// assert_eq!(6, streams.subscription_cost());
+    /// ```
+    #[must_use]
+    pub fn subscription_cost(&self) -> u32 {
+        self.streams
+            .iter()
+            .map(StreamConfig::subscription_cost)
+            .fold(0, u32::saturating_add)
+    }
+
     /// Edit the settings of a certain stream
     ///
     /// # Errors
@@ -306,11 +1117,11 @@ impl Streams {
     /// let path = Path::new("./");
     /// let mut token = None;
     /// validate_oauth_token(&mut token,
-    /// &path, false).await.unwrap();
+    /// &path.join("user-data.json"), false).await.unwrap();
     ///
     /// let mut streams = Streams::new();
     ///
-    /// streams.add_stream("kaicenat", &None, API_SEARCH_URL, CLIENT_ID, token.unwrap()).await.unwrap();
+    /// streams.add_stream("kaicenat", &None, API_SEARCH_URL, CLIENT_ID, token.unwrap(), 10).await.unwrap();
     ///
     /// streams.edit_stream("kaicenat", &Some(vec![String::from("normal,720")])).unwrap();
     /// }
@@ -321,30 +1132,18 @@ impl Streams {
         quality_overides: &Option<Vec<String>>,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         if let Some(overrides) = quality_overides {
-            let profile_overrides: Vec<(String, u16)> = overrides
+            let profile_overrides: BTreeMap<String, Quality> = overrides
                 .iter()
                 .map(|to_parse| {
-                    let overrides_split: Vec<&str> = to_parse.split(',').collect();
-                    let profile = overrides_split[0].to_string();
-                    match overrides_split[1].parse::<u16>() {
-                        Ok(quality) => Ok((profile, quality)),
-                        Err(error) => Err(error),
-                    }
+                    let (profile, quality) = to_parse
+                        .split_once(',')
+                        .ok_or_else(|| format!("Expected <profile>,<quality>, got {to_parse}"))?;
+                    Ok((profile.to_string(), quality.parse::<Quality>()?))
                 })
-                .collect::<Result<Vec<_>, _>>()?;
-
-            if let Some(streamer) = self.streams.iter_mut().find(|config| config.name == name) {
-                for profile_override in profile_overrides {
-                    if let Some(found_profile) = streamer
-                        .quality_overides
-                        .iter_mut()
-                        .find(|(profile, _)| *profile == profile_override.0)
-                    {
-                        *found_profile = profile_override;
-                    } else {
-                        streamer.quality_overides.push(profile_override);
-                    }
-                }
+                .collect::<Result<BTreeMap<_, _>, String>>()?;
+
+            if let Some(streamer) = self.get_mut(name) {
+                streamer.quality_overides.extend(profile_overrides);
             }
         }
         Ok(())
@@ -372,146 +1171,2208 @@ impl Streams {
             None => None,
         }
     }
-}
 
-impl Default for Streams {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+    /// Merge the streams and groups of `other` into the schedule. Streams and groups only in
+    /// `other` are added, `strategy` decides what happens to the ones in both schedules.
+    ///
+    /// The includes of `other` are not merged as they are relative to its own directory.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::{MergeStrategy, Streams};
+    ///
+    /// let mut streams = Streams::read_streams(Path::new("./"));
+    /// let laptop = Streams::read_streams(Path::new("./laptop"));
+    ///
+    /// streams.merge(laptop, MergeStrategy::MergeOverrides);
+    /// ```
+    pub fn merge(&mut self, other: Self, strategy: MergeStrategy) {
+        for stream in other.streams {
+            let Some(mine) = self.get_mut(&stream.name) else {
+                self.streams.push(stream);
+                continue;
+            };
+            match strategy {
+                MergeStrategy::KeepMine => {}
+                MergeStrategy::TakeTheirs => *mine = stream,
+                MergeStrategy::MergeOverrides => {
+                    if mine.id == 0 {
+                        mine.id = stream.id;
+                    }
+                    for (profile, quality) in stream.quality_overides {
+                        mine.quality_overides.entry(profile).or_insert(quality);
+                    }
+                    merge_names(&mut mine.streams_to_open_on, stream.streams_to_open_on);
+                    merge_names(&mut mine.streams_to_close_on, stream.streams_to_close_on);
+                }
+            }
+        }
 
-impl std::fmt::Display for Streams {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for streams in &self.streams {
-            write!(f, "{streams}")?;
+        for group in other.groups {
+            let Some(mine) = self.groups.iter_mut().find(|mine| mine.name == group.name) else {
+                self.groups.push(group);
+                continue;
+            };
+            match strategy {
+                MergeStrategy::KeepMine => {}
+                MergeStrategy::TakeTheirs => *mine = group,
+                MergeStrategy::MergeOverrides => merge_names(&mut mine.streams, group.streams),
+            }
         }
-        Ok(())
     }
-}
-
-/// Individual twitch stream settings
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
-pub struct StreamConfig {
-    pub name: String,
-    pub id: u32,
-    pub quality_overides: Vec<(String, u16)>,
-    pub streams_to_close_on: Vec<String>,
-    pub streams_to_open_on: Vec<String>,
-}
 
-impl std::fmt::Display for StreamConfig {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}:\n  profile quality:\n", self.name)?;
-        for (profile, quality) in &self.quality_overides {
-            writeln!(f, "    {profile} : {quality}")?;
-        }
-        writeln!(f, "  streams to close on:")?;
-        for close_on_streams in &self.streams_to_close_on {
-            writeln!(f, "    {close_on_streams}")?;
-        }
-        writeln!(f, "  streams to open on:")?;
-        for open_on_streams in &self.streams_to_open_on {
-            writeln!(f, "    {open_on_streams}")?;
+    /// Find the streams that can no longer be found by name with the search api, such as
+    /// channels that were renamed or banned.
+    ///
+    /// # Errors
+    /// Will return an error on failure of a search request or failure to deserialize the search
+    /// results.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::Streams;
+    /// use stream_cave::authentication::validate_oauth_token;
+    /// use std::path::Path;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    /// const API_SEARCH_URL: &str = "https://api.twitch.tv/helix/search/channels";
+    /// const CLIENT_ID: &str = "AAAAAAAAAAAA";
+    /// let path = Path::new("./");
+    /// let mut token = None;
+    /// validate_oauth_token(&mut token,
+    /// &path.join("user-data.json"), false).await.unwrap();
+    ///
+    /// let streams = Streams::read_streams(&path);
+    ///
+    /// let unresolved = streams.unresolved_streams(API_SEARCH_URL, CLIENT_ID, &token.unwrap()).await.unwrap();
+    /// }
+    /// ```
+    pub async fn unresolved_streams(
+        &self,
+        api_url: &str,
+        client_id: &str,
+        user_access_token: &UserToken,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut unresolved = Vec::new();
+        for stream in &self.streams {
+            if resolve_broadcaster_id(&stream.name, api_url, client_id, user_access_token)
+                .await?
+                .is_none()
+            {
+                unresolved.push(stream.name.clone());
+            }
         }
-
-        Ok(())
+        Ok(unresolved)
     }
-}
 
-/// Twitch oauth2 token settings
-#[derive(Deserialize, Serialize)]
-pub struct UserData {
-    pub access_token: String,
-    pub login: String,
-    pub user_id: String,
-}
+    /// Look up the broadcaster id of every stream with the search api and update the stored ids.
+    /// Streams that cannot be found keep their current id. Returns the name, old id and new id of
+    /// every updated stream.
+    ///
+    /// # Errors
+    /// Will return an error on failure of a search request, failure to deserialize the search
+    /// results and failure to parse the id of a streamer.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::Streams;
+    /// use stream_cave::authentication::validate_oauth_token;
+    /// use std::path::Path;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    /// const API_SEARCH_URL: &str = "https://api.twitch.tv/helix/search/channels";
+    /// const CLIENT_ID: &str = "AAAAAAAAAAAA";
+    /// let path = Path::new("./");
+    /// let mut token = None;
+    /// validate_oauth_token(&mut token,
+    /// &path.join("user-data.json"), false).await.unwrap();
+    ///
+    /// let mut streams = Streams::read_streams(&path);
+    ///
+    /// let changes = streams.refresh_ids(API_SEARCH_URL, CLIENT_ID, &token.unwrap()).await.unwrap();
+    /// }
+    /// ```
+    pub async fn refresh_ids(
+        &mut self,
+        api_url: &str,
+        client_id: &str,
+        user_access_token: &UserToken,
+    ) -> Result<Vec<(String, u32, u32)>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut changes = Vec::new();
+        for stream in &mut self.streams {
+            let Some(id) =
+                resolve_broadcaster_id(&stream.name, api_url, client_id, user_access_token).await?
+            else {
+                eprintln!("Unable to find {}, keeping id {}", stream.name, stream.id);
+                continue;
+            };
+            if id != stream.id {
+                changes.push((stream.name.clone(), stream.id, id));
+                stream.id = id;
+            }
+        }
+        Ok(changes)
+    }
 
-impl UserData {
-    /// Read twitch token from storage
+    /// Find the live stream with the most viewers, only considering the streams of `group` when
+    /// given. Returns the stream and its viewer count.
     ///
     /// # Errors
-    /// Will return an error if it cannot find or open the file in path, and if the file cannot be
-    /// deserialized.
+    /// Will return an error if `group` does not exist, on failure of a streams request or on
+    /// failure to deserialize the streams.
     ///
     /// # Examples
     /// ```no_run
+    /// use stream_cave::Streams;
+    /// use stream_cave::authentication::validate_oauth_token;
     /// use std::path::Path;
-    /// use stream_cave::UserData;
     ///
-    /// let path = Path::new("./user-data.json");
-    /// let data = UserData::from_file(&path).unwrap();
+    /// #[tokio::main]
+    /// async fn main() {
+    /// const STREAMS_API: &str = "https://api.twitch.tv/helix/streams";
+    /// const CLIENT_ID: &str = "AAAAAAAAAAAA";
+    /// let path = Path::new("./");
+    /// let mut token = None;
+    /// validate_oauth_token(&mut token,
+    /// &path.join("user-data.json"), false).await.unwrap();
+    ///
+    /// let streams = Streams::read_streams(&path);
+    ///
+    /// let top = streams.top_live_stream(None, STREAMS_API, CLIENT_ID, &token.unwrap()).await.unwrap();
+    /// }
     /// ```
-    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let file = read_to_string(path)?;
-        Ok(serde_json::from_str(&file)?)
+    pub async fn top_live_stream(
+        &self,
+        group: Option<&str>,
+        api_url: &str,
+        client_id: &str,
+        user_access_token: &UserToken,
+    ) -> Result<Option<(&StreamConfig, u32)>, Box<dyn std::error::Error + Send + Sync>> {
+        let candidates: Vec<&StreamConfig> = match group {
+            Some(group) => {
+                let Some(group) = self.groups.iter().find(|streams| streams.name == group) else {
+                    return Err(format!("No group named {group}").into());
+                };
+                self.streams
+                    .iter()
+                    .filter(|stream| group.streams.contains(&stream.name))
+                    .collect()
+            }
+            None => self.streams.iter().collect(),
+        };
+
+        let top = query_live_streams(&candidates, api_url, client_id, user_access_token)
+            .await?
+            .into_iter()
+            .map(|(stream, data)| (stream, data.viewer_count))
+            .reduce(|top, stream| if stream.1 > top.1 { stream } else { top });
+        Ok(top)
     }
-    /// Create `UserData` from `UserToken`
+
+    /// Find the streams that are currently live.
+    ///
+    /// # Errors
+    /// Will return an error on failure of a streams request or on failure to deserialize the
+    /// streams.
     ///
     /// # Examples
     /// ```no_run
-    /// use stream_cave::UserData;
+    /// use stream_cave::Streams;
     /// use stream_cave::authentication::validate_oauth_token;
-    /// use twitch_oauth2::UserToken;
     /// use std::path::Path;
     ///
     /// #[tokio::main]
     /// async fn main() {
+    /// const STREAMS_API: &str = "https://api.twitch.tv/helix/streams";
+    /// const CLIENT_ID: &str = "AAAAAAAAAAAA";
     /// let path = Path::new("./");
     /// let mut token = None;
     /// validate_oauth_token(&mut token,
-    /// &path, false).await.unwrap();
+    /// &path.join("user-data.json"), false).await.unwrap();
     ///
-    /// let user_data = UserData::from_token(token.as_ref().unwrap());
+    /// let streams = Streams::read_streams(&path);
+    ///
+    /// for (stream, data) in streams.live_streams(STREAMS_API, CLIENT_ID, &token.unwrap()).await.unwrap() {
+    ///     println!("{} is live for {} viewers", stream.name, data.viewer_count);
+    /// }
     /// }
     /// ```
-    #[must_use]
-    pub fn from_token(token: &UserToken) -> Self {
-        Self {
-            access_token: token.access_token.secret().to_string(),
-            login: token.login.to_string(),
-            user_id: token.user_id.to_string(),
-        }
+    pub async fn live_streams(
+        &self,
+        api_url: &str,
+        client_id: &str,
+        user_access_token: &UserToken,
+    ) -> Result<Vec<(&StreamConfig, StreamData)>, Box<dyn std::error::Error + Send + Sync>> {
+        let candidates: Vec<&StreamConfig> = self.streams.iter().collect();
+        query_live_streams(&candidates, api_url, client_id, user_access_token).await
     }
-    /// Write token to file
+
+    /// Find the scheduled streams of the stream named `name`, or of all streams, sorted by their
+    /// start time. Canceled segments, streams without a schedule and streams without a resolved
+    /// id are skipped.
     ///
     /// # Errors
-    /// Will return an error if the data cannot be serialized or if it cannot be written to the
-    /// file in path.
+    /// Will return an error if no stream is named `name`, on failure of a schedule request or on
+    /// failure to deserialize the schedule.
     ///
     /// # Examples
     /// ```no_run
-    /// use stream_cave::UserData;
+    /// use stream_cave::Streams;
     /// use stream_cave::authentication::validate_oauth_token;
-    /// use twitch_oauth2::UserToken;
     /// use std::path::Path;
     ///
     /// #[tokio::main]
     /// async fn main() {
+    /// const SCHEDULE_API: &str = "https://api.twitch.tv/helix/schedule";
+    /// const CLIENT_ID: &str = "AAAAAAAAAAAA";
     /// let path = Path::new("./");
     /// let mut token = None;
     /// validate_oauth_token(&mut token,
-    /// &path, false).await.unwrap();
+    /// &path.join("user-data.json"), false).await.unwrap();
     ///
-    /// let user_data = UserData::from_token(token.as_ref().unwrap());
+    /// let streams = Streams::read_streams(&path);
     ///
-    /// user_data.save(&path.join("user_data.json")).unwrap();
+    /// for (stream, segment) in streams.upcoming_streams(None, SCHEDULE_API, CLIENT_ID, &token.unwrap()).await.unwrap() {
+    ///     println!("{} {}: {}", segment.start_time, stream.name, segment.title);
+    /// }
     /// }
     /// ```
-    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let token_string = serde_json::to_string(self)?;
-        let Some(parent) = path.parent() else {
-            return Err(String::from("Invalid path string").into());
+    pub async fn upcoming_streams(
+        &self,
+        name: Option<&str>,
+        api_url: &str,
+        client_id: &str,
+        user_access_token: &UserToken,
+    ) -> Result<Vec<(&StreamConfig, ScheduleSegment)>, Box<dyn std::error::Error + Send + Sync>>
+    {
+        let candidates: Vec<&StreamConfig> = match name {
+            Some(name) => vec![self
+                .get(name)
+                .ok_or_else(|| format!("No stream named {name}"))?],
+            None => self.streams.iter().collect(),
         };
 
-        match std::fs::create_dir(parent) {
-            Ok(()) => {
-                std::fs::write(path, token_string)?;
+        let mut upcoming = Vec::new();
+        for stream in candidates.into_iter().filter(|stream| stream.id != 0) {
+            let response = http::client()
+                .get(api_url)
+                .query(&[
+                    ("broadcaster_id", stream.id.to_string().as_str()),
+                    ("first", "25"),
+                ])
+                .bearer_auth(user_access_token.access_token.as_str())
+                .header("Client-Id", client_id)
+                .send()
+                .await?;
+            // Channels that never set up a schedule are not found
+            if response.status() == 404 {
+                continue;
             }
-            Err(ref error) if error.kind() == std::io::ErrorKind::AlreadyExists => {
-                std::fs::write(path, token_string)?;
+            if response.status() != 200 {
+                return Err(format!(
+                    "Error {} while getting the schedule of {}: {}",
+                    response.status(),
+                    stream.name,
+                    response.text().await?
+                )
+                .into());
             }
-            Err(error) => return Err(error.into()),
+
+            let schedule = response
+                .json::<twitch_socket::api_structs::ScheduleResponse>()
+                .await?;
+            upcoming.extend(
+                schedule
+                    .data
+                    .segments
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|segment| segment.canceled_until.is_none())
+                    .map(|segment| (stream, segment)),
+            );
         }
-        Ok(())
+        // The start times are all in UTC so they sort as strings
+        upcoming.sort_by(|first, second| first.1.start_time.cmp(&second.1.start_time));
+        Ok(upcoming)
+    }
+}
+
+/// Query the live status of `candidates` in chunks of the maximum page size of the streams api.
+async fn query_live_streams<'a>(
+    candidates: &[&'a StreamConfig],
+    api_url: &str,
+    client_id: &str,
+    user_access_token: &UserToken,
+) -> Result<Vec<(&'a StreamConfig, StreamData)>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut live_streams = Vec::new();
+    for chunk in candidates.chunks(100) {
+        let mut query: Vec<(&str, &str)> = chunk
+            .iter()
+            .map(|stream| ("user_login", stream.name.as_str()))
+            .collect();
+        query.push(("first", "100"));
+        let response = http::client()
+            .get(api_url)
+            .query(&query)
+            .bearer_auth(user_access_token.access_token.as_str())
+            .header("Client-Id", client_id)
+            .send()
+            .await?;
+        if response.status() != 200 {
+            return Err(format!(
+                "Error {} while getting live streams: {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        let live = response
+            .json::<twitch_socket::api_structs::StreamsResponse>()
+            .await?;
+        for data in live.data {
+            if let Some(stream) = chunk.iter().find(|stream| stream.name == data.user_login) {
+                live_streams.push((*stream, data));
+            }
+        }
+    }
+    Ok(live_streams)
+}
+
+/// Check whether each of the channels `names` is live, with one streams request per channel and
+/// at most `concurrency` requests in flight at once.
+///
+/// # Errors
+/// Will return an error on failure of a streams request or on failure to deserialize the
+/// streams.
+///
+/// # Examples
+/// ```no_run
+/// use stream_cave::authentication::validate_oauth_token;
+/// use stream_cave::{check_live_status, DEFAULT_LIVE_CHECK_CONCURRENCY};
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() {
+/// const STREAMS_API: &str = "https://api.twitch.tv/helix/streams";
+/// const CLIENT_ID: &str = "AAAAAAAAAAAA";
+/// let mut token = None;
+/// validate_oauth_token(&mut token,
+/// &Path::new("./user-data.json"), false).await.unwrap();
+///
+/// let names = vec![String::from("kaicenat"), String::from("jynxzi")];
+/// let live = check_live_status(&names, DEFAULT_LIVE_CHECK_CONCURRENCY, STREAMS_API, CLIENT_ID,
+/// &token.unwrap()).await.unwrap();
+/// }
+/// ```
+pub async fn check_live_status(
+    names: &[String],
+    concurrency: usize,
+    api_url: &str,
+    client_id: &str,
+    user_access_token: &UserToken,
+) -> Result<BTreeMap<String, bool>, Box<dyn std::error::Error + Send + Sync>> {
+    let semaphore = Semaphore::new(concurrency.max(1));
+    let checks = names.iter().map(|name| {
+        let semaphore = &semaphore;
+        async move {
+            let _permit = semaphore.acquire().await?;
+            let response = http::client()
+                .get(api_url)
+                .query(&[("user_login", name.as_str())])
+                .bearer_auth(user_access_token.access_token.as_str())
+                .header("Client-Id", client_id)
+                .send()
+                .await?;
+            if response.status() != 200 {
+                return Err(format!(
+                    "Error {} while checking if {name} is live: {}",
+                    response.status(),
+                    response.text().await?
+                )
+                .into());
+            }
+
+            let live = response
+                .json::<twitch_socket::api_structs::StreamsResponse>()
+                .await?;
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>((
+                name.clone(),
+                live.data.iter().any(|data| data.user_login == *name),
+            ))
+        }
+    });
+    join_all(checks).await.into_iter().collect()
+}
+
+/// Search for the channel with the login `name`.
+async fn search_channel(
+    name: &str,
+    api_url: &str,
+    client_id: &str,
+    user_access_token: &UserToken,
+) -> Result<Option<twitch_socket::api_structs::SearchData>, Box<dyn std::error::Error + Send + Sync>>
+{
+    let response = http::client()
+        .get(api_url)
+        .query(&[("query", name)])
+        .bearer_auth(user_access_token.access_token.as_str())
+        .header("Client-Id", client_id)
+        .send()
+        .await?;
+
+    if response.status() == 401 {
+        return Err(authentication::TokenError::Invalid.into());
+    }
+    if response.status() != 200 {
+        return Err(format!(
+            "Error {} while searching for {name}: {}",
+            response.status(),
+            response.text().await?
+        )
+        .into());
+    }
+
+    let search_results = response
+        .json::<twitch_socket::api_structs::StreamSearch>()
+        .await?;
+    Ok(search_results
+        .data
+        .into_iter()
+        .find(|data_set| data_set.broadcaster_login == name))
+}
+
+/// Look up the broadcaster id of the channel with the login `name` with the search api. Returns
+/// `None` when no channel has that login.
+///
+/// # Errors
+/// Will return an error on failure of the search request, failure to deserialize the search
+/// results and failure to parse the id of the streamer. A `TokenError::Invalid` is returned when
+/// twitch rejects `user_access_token`.
+///
+/// # Examples
+/// ```no_run
+/// use stream_cave::authentication::validate_oauth_token;
+/// use stream_cave::resolve_broadcaster_id;
+/// use std::path::Path;
+///
+/// #[tokio::main]
+/// async fn main() {
+/// const API_SEARCH_URL: &str = "https://api.twitch.tv/helix/search/channels";
+/// const CLIENT_ID: &str = "AAAAAAAAAAAA";
+/// let path = Path::new("./");
+/// let mut token = None;
+/// validate_oauth_token(&mut token,
+/// &path.join("user-data.json"), false).await.unwrap();
+///
+/// let id = resolve_broadcaster_id("kaicenat", API_SEARCH_URL, CLIENT_ID, &token.unwrap()).await.unwrap();
+/// }
+/// ```
+pub async fn resolve_broadcaster_id(
+    name: &str,
+    api_url: &str,
+    client_id: &str,
+    user_access_token: &UserToken,
+) -> Result<Option<u32>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(search_channel(name, api_url, client_id, user_access_token)
+        .await?
+        .map(|channel| channel.id.parse::<u32>())
+        .transpose()?)
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> IntoIterator for &'a Streams {
+    type Item = &'a StreamConfig;
+    type IntoIter = std::slice::Iter<'a, StreamConfig>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl std::fmt::Display for Streams {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for streams in &self.streams {
+            write!(f, "{streams}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Individual twitch stream settings
+#[derive(Deserialize, Serialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+pub struct StreamConfig {
+    pub name: String,
+    pub id: u32,
+    #[serde(deserialize_with = "deserialize_quality_overrides")]
+    #[schemars(schema_with = "quality_overrides_schema")]
+    pub quality_overides: BTreeMap<String, Quality>,
+    pub streams_to_close_on: Vec<String>,
+    pub streams_to_open_on: Vec<String>,
+    /// Extra arguments passed to the player for this stream
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub player_args: Vec<String>,
+    /// The player playing this stream, the player of the settings when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<Player>,
+    /// Record the stream to the `output_dir` of the settings. Mpv records while playing the
+    /// stream, streamlink records it instead of playing it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub record: bool,
+    /// The types of stream.online events that start the stream, only `live` when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub launch_on: Vec<EventType>,
+    /// Let the player resume from the last playback position, for streams that are VODs
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub resume: bool,
+    /// The latency of this stream, the latency of the settings when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<Latency>,
+    /// The events that close the player of this stream
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub close_when: Vec<CloseRule>,
+    /// Only play the stream while the streamer is in one of these categories, any category when
+    /// empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories_allow: Vec<String>,
+    /// Do not play the stream while the streamer is in one of these categories
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories_block: Vec<String>,
+    /// The quality to reopen the player at when the streamer switches to a category, such as
+    /// `Just Chatting`. Categories are compared ignoring case
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub category_quality_overrides: BTreeMap<String, Quality>,
+    /// The mpv profile to play this stream with, ignored by other players
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mpv_profile: Option<String>,
+    /// The mpv configuration directory to play this stream with, ignored by other players
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mpv_config_dir: Option<PathBuf>,
+    /// Send notifications for this stream, it is played either way
+    #[serde(default = "default_notify")]
+    pub notify: bool,
+}
+
+/// An event that closes the player of a stream, the player is not restarted afterwards
+#[derive(Deserialize, Serialize, JsonSchema, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseRule {
+    /// The stream went offline, players of scheduled streams are always closed when they go
+    /// offline
+    Offline,
+    /// The streamer switched to this category, such as `Just Chatting`
+    Category(String),
+}
+
+impl CloseRule {
+    /// Check if the rule matches the `event` sent by the websocket, categories are compared
+    /// ignoring case.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::control::SocketEvent;
+    /// use stream_cave::CloseRule;
+    ///
+    /// let rule = CloseRule::Category(String::from("Just Chatting"));
+    /// let update = SocketEvent {
+    ///     category: Some(String::from("just chatting")),
+    ///     ..SocketEvent::new("update", "kaicenat", "KaiCenat")
+    /// };
+    ///
+    /// assert!(rule.matches(&update));
+    /// assert!(!rule.matches(&SocketEvent::new("offline", "kaicenat", "KaiCenat")));
+    /// ```
+    #[must_use]
+    pub fn matches(&self, event: &control::SocketEvent) -> bool {
+        match self {
+            Self::Offline => event.kind == "offline",
+            Self::Category(category) => {
+                event.kind == "update"
+                    && event
+                        .category
+                        .as_deref()
+                        .is_some_and(|value| category.eq_ignore_ascii_case(value))
+            }
+        }
+    }
+}
+
+impl StreamConfig {
+    /// Create the settings of the stream `name` with the broadcaster `id`, played at the quality
+    /// of the profiles and launched when it goes live.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::StreamConfig;
+    ///
+    /// let kaicenat = StreamConfig::new("kaicenat", 641_972_806);
+    ///
+    /// assert!(kaicenat.quality_overides.is_empty());
+    /// assert!(kaicenat.notify);
+    /// ```
+    #[must_use]
+    pub fn new(name: &str, id: u32) -> Self {
+        Self {
+            name: name.to_string(),
+            id,
+            quality_overides: BTreeMap::new(),
+            streams_to_close_on: Vec::new(),
+            streams_to_open_on: Vec::new(),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            launch_on: Vec::new(),
+            resume: false,
+            latency: None,
+            close_when: Vec::new(),
+            categories_allow: Vec::new(),
+            categories_block: Vec::new(),
+            category_quality_overrides: BTreeMap::new(),
+            mpv_profile: None,
+            mpv_config_dir: None,
+            notify: true,
+        }
+    }
+
+    /// The `EventSub` subscription types needed for the stream. `stream.offline` is always
+    /// subscribed to for closing the player, `channel.update` only for the close rules and
+    /// category qualities using it.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./tests/resources"));
+    /// let kaicenat = streams.get("kaicenat").unwrap();
+    ///
+    /// assert_eq!(vec!["stream.online", "stream.offline"], kaicenat.subscription_types());
+    /// ```
+    #[must_use]
+    pub fn subscription_types(&self) -> Vec<&'static str> {
+        let mut types = vec!["stream.online", "stream.offline"];
+        if self
+            .close_when
+            .iter()
+            .any(|rule| matches!(rule, CloseRule::Category(_)))
+            || !self.category_quality_overrides.is_empty()
+        {
+            types.push("channel.update");
+        }
+        types
+    }
+
+    /// The cost of the subscriptions of `subscription_types`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./tests/resources"));
+    ///
+    /// assert_eq!(2, streams.get("kaicenat").unwrap().subscription_cost());
+    /// ```
+    #[must_use]
+    pub fn subscription_cost(&self) -> u32 {
+        u32::try_from(self.subscription_types().len()).map_or(u32::MAX, |count| {
+            count.saturating_mul(twitch_socket::STREAM_ONLINE_COST)
+        })
+    }
+
+    /// Check if both configurations are of the same stream, either by name or by a resolved id.
+    /// Unlike `==` the other settings of the streams are not compared.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./tests/resources"));
+    /// let kaicenat = streams.get("kaicenat").unwrap();
+    /// let mut renamed = kaicenat.clone();
+    /// renamed.name = String::from("kai");
+    ///
+    /// assert!(kaicenat.is_same_stream(&renamed));
+    /// ```
+    #[must_use]
+    pub fn is_same_stream(&self, other: &Self) -> bool {
+        self.name == other.name || (self.id != 0 && self.id == other.id)
+    }
+
+    /// Check if the settings of the stream differ from `other`, ignoring the name and id
+    /// identifying the stream. Use `is_same_stream` to check the identity.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::Streams;
+    ///
+    /// let streams = Streams::read_streams(Path::new("./tests/resources"));
+    /// let kaicenat = streams.get("kaicenat").unwrap();
+    /// let mut resolved = kaicenat.clone();
+    /// resolved.id = 1;
+    ///
+    /// assert!(!kaicenat.settings_changed(&resolved));
+    /// ```
+    #[must_use]
+    pub fn settings_changed(&self, other: &Self) -> bool {
+        // Destructured so new settings cannot be missed
+        let Self {
+            name: _,
+            id: _,
+            quality_overides,
+            streams_to_close_on,
+            streams_to_open_on,
+            player_args,
+            player,
+            record,
+            launch_on,
+            resume,
+            latency,
+            close_when,
+            categories_allow,
+            categories_block,
+            category_quality_overrides,
+            mpv_profile,
+            mpv_config_dir,
+            notify,
+        } = self;
+        *quality_overides != other.quality_overides
+            || *streams_to_close_on != other.streams_to_close_on
+            || *streams_to_open_on != other.streams_to_open_on
+            || *player_args != other.player_args
+            || *player != other.player
+            || *record != other.record
+            || *launch_on != other.launch_on
+            || *resume != other.resume
+            || *latency != other.latency
+            || *close_when != other.close_when
+            || *categories_allow != other.categories_allow
+            || *categories_block != other.categories_block
+            || *category_quality_overrides != other.category_quality_overrides
+            || *mpv_profile != other.mpv_profile
+            || *mpv_config_dir != other.mpv_config_dir
+            || *notify != other.notify
+    }
+
+    /// Check if the stream has an allowlist or blocklist of categories.
+    #[must_use]
+    pub const fn filters_categories(&self) -> bool {
+        !self.categories_allow.is_empty() || !self.categories_block.is_empty()
+    }
+
+    /// Check if the stream is played while the streamer is in `category`. Categories are
+    /// compared ignoring case.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::StreamConfig;
+    ///
+    /// let mut config: StreamConfig = serde_json::from_str(
+    ///     r#"{"name":"kaicenat","id":0,"quality_overides":{},"streams_to_close_on":[],"streams_to_open_on":[]}"#,
+    /// )
+    /// .unwrap();
+    /// config.categories_block.push(String::from("Just Chatting"));
+    ///
+    /// assert!(!config.allows_category("just chatting"));
+    /// assert!(config.allows_category("Minecraft"));
+    /// ```
+    #[must_use]
+    pub fn allows_category(&self, category: &str) -> bool {
+        let listed = |categories: &[String]| {
+            categories
+                .iter()
+                .any(|listed| listed.eq_ignore_ascii_case(category))
+        };
+        (self.categories_allow.is_empty() || listed(&self.categories_allow))
+            && !listed(&self.categories_block)
+    }
+
+    /// The quality to play the stream at while the streamer is in `category`, `None` when the
+    /// category has no override. Categories are compared ignoring case.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::{quality::Quality, StreamConfig};
+    ///
+    /// let mut config: StreamConfig = serde_json::from_str(
+    ///     r#"{"name":"kaicenat","id":0,"quality_overides":{},"streams_to_close_on":[],"streams_to_open_on":[]}"#,
+    /// )
+    /// .unwrap();
+    /// config
+    ///     .category_quality_overrides
+    ///     .insert(String::from("Just Chatting"), Quality::Height(480));
+    ///
+    /// assert_eq!(Some(Quality::Height(480)), config.category_quality("just chatting"));
+    /// assert_eq!(None, config.category_quality("Minecraft"));
+    /// ```
+    #[must_use]
+    pub fn category_quality(&self, category: &str) -> Option<Quality> {
+        self.category_quality_overrides
+            .iter()
+            .find(|(listed, _)| listed.eq_ignore_ascii_case(category))
+            .map(|(_, quality)| quality.clone())
+    }
+}
+
+/// Read the quality overrides either as a map or as the array of profile and quality pairs used
+/// by older schedule files. Later pairs replace earlier pairs of the same profile.
+fn deserialize_quality_overrides<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Quality>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum QualityOverrides {
+        Map(BTreeMap<String, Quality>),
+        Pairs(Vec<(String, Quality)>),
+    }
+
+    Ok(match QualityOverrides::deserialize(deserializer)? {
+        QualityOverrides::Map(map) => map,
+        QualityOverrides::Pairs(pairs) => pairs.into_iter().collect(),
+    })
+}
+
+/// The schema of both forms read by `deserialize_quality_overrides`.
+fn quality_overrides_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+        "oneOf": [
+            generator.subschema_for::<BTreeMap<String, Quality>>(),
+            generator.subschema_for::<Vec<(String, Quality)>>(),
+        ]
+    })
+}
+
+impl std::fmt::Display for StreamConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:\n  profile quality:\n", self.name)?;
+        for (profile, quality) in &self.quality_overides {
+            writeln!(f, "    {profile} : {quality}")?;
+        }
+        writeln!(f, "  streams to close on:")?;
+        for close_on_streams in &self.streams_to_close_on {
+            writeln!(f, "    {close_on_streams}")?;
+        }
+        writeln!(f, "  streams to open on:")?;
+        for open_on_streams in &self.streams_to_open_on {
+            writeln!(f, "    {open_on_streams}")?;
+        }
+        if !self.player_args.is_empty() {
+            writeln!(f, "  player arguments: {}", self.player_args.join(" "))?;
+        }
+        if let Some(player) = self.player {
+            writeln!(f, "  player: {player:?}")?;
+        }
+        if self.record {
+            writeln!(f, "  recorded")?;
+        }
+        if !self.launch_on.is_empty() {
+            let launch_on: Vec<_> = self.launch_on.iter().map(|event| event.as_str()).collect();
+            writeln!(f, "  launch on: {}", launch_on.join(", "))?;
+        }
+        if self.resume {
+            writeln!(f, "  resume playback")?;
+        }
+        if let Some(latency) = self.latency {
+            writeln!(f, "  latency: {latency:?}")?;
+        }
+        if !self.notify {
+            writeln!(f, "  notifications disabled")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Twitch oauth2 token settings
+#[derive(Deserialize, Serialize)]
+pub struct UserData {
+    pub access_token: String,
+    pub login: String,
+    pub user_id: String,
+    /// The scopes granted to the token, empty for tokens created before scopes were stored
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// The refresh token of tokens created with the authorization code flow, tokens of the
+    /// implicit flow cannot be refreshed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// The client the token was created for, needed to refresh the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// The secret of the client, needed to refresh the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    /// The token is stored in the keyring under the login, the other fields are left empty
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub in_keyring: bool,
+}
+
+impl UserData {
+    /// Read twitch token from storage, from the keyring when the file in path only keeps the login
+    ///
+    /// # Errors
+    /// Will return an error if it cannot find or open the file in path, if the file cannot be
+    /// deserialized and if the token cannot be read from the keyring.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::UserData;
+    ///
+    /// let path = Path::new("./user-data.json");
+    /// let data = UserData::from_file(&path).unwrap();
+    /// ```
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::from_file_in(path, &SecretTool)
+    }
+
+    /// Read twitch token from storage like `from_file`, reading the token from `keyring`.
+    ///
+    /// # Errors
+    /// Will return an error if it cannot find or open the file in path, if the file cannot be
+    /// deserialized and if the token cannot be read from `keyring`.
+    pub fn from_file_in(
+        path: &Path,
+        keyring: &dyn Keyring,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let file = read_to_string(path)?;
+        let user_data: Self = serde_json::from_str(&file)?;
+        if user_data.in_keyring {
+            return Self::read_keyring(keyring, &user_data.login);
+        }
+        Ok(user_data)
+    }
+
+    /// The contents of the file in path, without reading the keyring.
+    fn read_stored(path: &Path) -> Option<Self> {
+        read_to_string(path)
+            .ok()
+            .and_then(|file| serde_json::from_str(&file).ok())
+    }
+
+    /// Where the token of the file in path is stored, `TokenStorage::File` if the file cannot be
+    /// read.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::{TokenStorage, UserData};
+    ///
+    /// assert_eq!(TokenStorage::File, UserData::storage(Path::new("./missing.json")));
+    /// ```
+    #[must_use]
+    pub fn storage(path: &Path) -> TokenStorage {
+        if Self::read_stored(path).is_some_and(|user_data| user_data.in_keyring) {
+            TokenStorage::Keyring
+        } else {
+            TokenStorage::File
+        }
+    }
+
+    /// Read the token of `login` from the keyring with `secret-tool`.
+    ///
+    /// # Errors
+    /// Will return an error if `secret-tool` cannot be run, there is no token of `login` in the
+    /// keyring or the stored token cannot be deserialized.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::UserData;
+    ///
+    /// let data = UserData::from_keyring("kaicenat").unwrap();
+    /// ```
+    pub fn from_keyring(login: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Self::read_keyring(&SecretTool, login)
+    }
+
+    fn read_keyring(
+        keyring: &dyn Keyring,
+        login: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(token) = keyring.lookup(login)? else {
+            return Err(format!("No token of {login} was found in the keyring").into());
+        };
+        Ok(serde_json::from_str(&token)?)
+    }
+
+    /// Store the token in the keyring under its login with `secret-tool`.
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be serialized, if `secret-tool` cannot be run or
+    /// if it fails to store the token.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::UserData;
+    ///
+    /// let user_data = UserData::from_file(Path::new("./user-data.json")).unwrap();
+    ///
+    /// user_data.save_to_keyring().unwrap();
+    /// ```
+    pub fn save_to_keyring(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        SecretTool.store(&self.login, &serde_json::to_string(self)?)
+    }
+
+    /// Delete the file in path, and the token in the keyring if the file only keeps its login.
+    ///
+    /// # Errors
+    /// Will return an error if the token cannot be removed from the keyring or the file cannot be
+    /// deleted.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::UserData;
+    ///
+    /// UserData::remove(Path::new("./user-data.json")).unwrap();
+    /// ```
+    pub fn remove(path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Self::remove_in(path, &SecretTool)
+    }
+
+    /// Delete the file in path like `remove`, removing the token from `keyring`.
+    ///
+    /// # Errors
+    /// Will return an error if the token cannot be removed from `keyring` or the file cannot be
+    /// deleted.
+    pub fn remove_in(
+        path: &Path,
+        keyring: &dyn Keyring,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(stored) = Self::read_stored(path).filter(|user_data| user_data.in_keyring) {
+            keyring.clear(&stored.login)?;
+        }
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// Remove the token of `login` from the keyring with `secret-tool`.
+    ///
+    /// # Errors
+    /// Will return an error if `secret-tool` cannot be run or fails to remove the token.
+    pub fn remove_from_keyring(
+        login: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        SecretTool.clear(login)
+    }
+
+    /// Save the token to `storage`. For the keyring only the login, user id and scopes are
+    /// written to the file in path, so `from_file` finds the token in the keyring.
+    ///
+    /// When the keyring cannot be used the whole token is written to the file after a warning.
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be serialized or if it cannot be written to the
+    /// file in path.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::{TokenStorage, UserData};
+    ///
+    /// let path = Path::new("./user-data.json");
+    /// let user_data = UserData::from_file(&path).unwrap();
+    ///
+    /// user_data.save_with(&path, TokenStorage::Keyring).unwrap();
+    /// ```
+    pub fn save_with(
+        &self,
+        path: &Path,
+        storage: TokenStorage,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.save_in(path, storage, &SecretTool)
+    }
+
+    /// Save the token to `storage` like `save_with`, storing it in `keyring`.
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be serialized or if it cannot be written to the
+    /// file in path.
+    pub fn save_in(
+        &self,
+        path: &Path,
+        storage: TokenStorage,
+        keyring: &dyn Keyring,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if storage == TokenStorage::Keyring {
+            match keyring.store(&self.login, &serde_json::to_string(self)?) {
+                Ok(()) => {
+                    return Self {
+                        access_token: String::new(),
+                        login: self.login.clone(),
+                        user_id: self.user_id.clone(),
+                        scopes: self.scopes.clone(),
+                        refresh_token: None,
+                        client_id: None,
+                        client_secret: None,
+                        in_keyring: true,
+                    }
+                    .save(path);
+                }
+                Err(error) => eprintln!(
+                    "Warning: unable to store the token in the keyring, writing it to {} instead: {error}",
+                    path.display()
+                ),
+            }
+        }
+        self.save(path)
+    }
+    /// Create `UserData` from `UserToken`. The client id is only kept for tokens with a refresh
+    /// token, the client secret cannot be read from the token and is left unset.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::UserData;
+    /// use stream_cave::authentication::validate_oauth_token;
+    /// use twitch_oauth2::UserToken;
+    /// use std::path::Path;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    /// let path = Path::new("./");
+    /// let mut token = None;
+    /// validate_oauth_token(&mut token,
+    /// &path.join("user-data.json"), false).await.unwrap();
+    ///
+    /// let user_data = UserData::from_token(token.as_ref().unwrap());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn from_token(token: &UserToken) -> Self {
+        Self {
+            access_token: token.access_token.secret().to_string(),
+            login: token.login.to_string(),
+            user_id: token.user_id.to_string(),
+            scopes: token.scopes().iter().map(ToString::to_string).collect(),
+            refresh_token: token
+                .refresh_token
+                .as_ref()
+                .map(|refresh_token| refresh_token.secret().to_string()),
+            client_id: token
+                .refresh_token
+                .as_ref()
+                .map(|_| token.client_id().to_string()),
+            client_secret: None,
+            in_keyring: false,
+        }
+    }
+    /// Write token to file
+    ///
+    /// # Errors
+    /// Will return an error if the data cannot be serialized or if it cannot be written to the
+    /// file in path.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use stream_cave::UserData;
+    /// use stream_cave::authentication::validate_oauth_token;
+    /// use twitch_oauth2::UserToken;
+    /// use std::path::Path;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    /// let path = Path::new("./");
+    /// let mut token = None;
+    /// validate_oauth_token(&mut token,
+    /// &path.join("user-data.json"), false).await.unwrap();
+    ///
+    /// let user_data = UserData::from_token(token.as_ref().unwrap());
+    ///
+    /// user_data.save(&path.join("user_data.json")).unwrap();
+    /// }
+    /// ```
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let token_string = serde_json::to_string(self)?;
+        let Some(parent) = path.parent() else {
+            return Err(String::from("Invalid path string").into());
+        };
+
+        std::fs::create_dir_all(parent)?;
+        std::fs::write(path, token_string)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_FORMAT_STREAM: &str = r#"{
+        "name": "kaicenat",
+        "id": 641972806,
+        "quality_overides": [["normal", 480], ["low-data", 0], ["normal", 720]],
+        "streams_to_close_on": [],
+        "streams_to_open_on": []
+    }"#;
+
+    #[test]
+    fn read_old_quality_overrides_format() {
+        let config: StreamConfig = serde_json::from_str(OLD_FORMAT_STREAM).unwrap();
+
+        assert_eq!(
+            BTreeMap::from([
+                (String::from("low-data"), Quality::Height(0)),
+                (String::from("normal"), Quality::Height(720))
+            ]),
+            config.quality_overides
+        );
+    }
+
+    #[test]
+    fn write_migrates_quality_overrides() {
+        let config: StreamConfig = serde_json::from_str(OLD_FORMAT_STREAM).unwrap();
+        let written = serde_json::to_value(&config).unwrap();
+
+        assert_eq!(
+            serde_json::json!({ "low-data": 0, "normal": 720 }),
+            written["quality_overides"]
+        );
+        let reread: StreamConfig = serde_json::from_value(written).unwrap();
+        assert_eq!(config, reread);
+    }
+
+    #[test]
+    fn edit_replaces_existing_override() {
+        let mut streams = Streams {
+            streams: vec![serde_json::from_str(OLD_FORMAT_STREAM).unwrap()],
+            ..Streams::new()
+        };
+
+        streams
+            .edit_stream(
+                "kaicenat",
+                &Some(vec![String::from("normal,360"), String::from("mobile,160")]),
+            )
+            .unwrap();
+
+        assert_eq!(
+            BTreeMap::from([
+                (String::from("low-data"), Quality::Height(0)),
+                (String::from("mobile"), Quality::Height(160)),
+                (String::from("normal"), Quality::Height(360)),
+            ]),
+            streams.streams[0].quality_overides
+        );
+    }
+
+    #[test]
+    fn merge_included_schedules() {
+        let streams =
+            Streams::read_streams_with_includes(Path::new("./tests/resources/includes")).unwrap();
+
+        let names: Vec<&str> = streams
+            .streams
+            .iter()
+            .map(|stream| stream.name.as_str())
+            .collect();
+        assert_eq!(vec!["kaicenat", "jynxzi", "hasanabi"], names);
+    }
+
+    #[test]
+    fn detect_duplicate_included_stream() {
+        let error =
+            Streams::read_streams_with_includes(Path::new("./tests/resources/includes-duplicate"))
+                .unwrap_err();
+
+        assert!(error.to_string().contains("\"jynxzi\""));
+    }
+
+    #[test]
+    fn write_leaves_out_included_streams() {
+        let path = std::env::temp_dir().join("stream-cave-write-includes");
+        let streams =
+            Streams::read_streams_with_includes(Path::new("./tests/resources/includes")).unwrap();
+
+        streams.write(&path).unwrap();
+        let written = Streams::read_streams(&path);
+        std::fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(1, written.streams.len());
+        assert_eq!("kaicenat", written.streams[0].name);
+        assert_eq!(
+            vec![PathBuf::from("esports.json"), PathBuf::from("vtubers.json")],
+            written.include
+        );
+    }
+
+    #[test]
+    fn write_keeps_backup() {
+        let path = std::env::temp_dir().join("stream-cave-write-backup");
+        let _ = std::fs::remove_dir_all(&path);
+        let mut streams = Streams::new();
+        streams
+            .streams
+            .push(StreamConfig::new("kaicenat", 641_972_806));
+        streams.write(&path).unwrap();
+        streams
+            .streams
+            .push(StreamConfig::new("jynxzi", 411_377_640));
+
+        streams.write(&path).unwrap();
+        let written = Streams::read_streams(&path);
+        let backup: Streams =
+            serde_json::from_str(&std::fs::read_to_string(path.join("schedule.json.bak")).unwrap())
+                .unwrap();
+        let temp_left = path.join(".schedule.json.tmp").exists();
+
+        std::fs::create_dir(path.join(".schedule.json.tmp")).unwrap();
+        let failed_write = Streams::new().write(&path);
+        let after_failure = Streams::read_streams(&path);
+        std::fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(2, written.streams.len());
+        assert_eq!(1, backup.streams.len());
+        assert!(!temp_left);
+        assert!(failed_write.is_err());
+        assert_eq!(2, after_failure.streams.len());
+    }
+
+    #[test]
+    fn read_config_from_file_path() {
+        let directory = std::env::temp_dir().join("stream-cave-config-file");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let config_file = directory.join("myconfig.json");
+        let mut settings = Settings::new(&directory);
+        settings.profiles.insert(String::from("mobile"), 480);
+        settings.default_profile = String::from("mobile");
+        std::fs::write(&config_file, serde_json::to_string(&settings).unwrap()).unwrap();
+        std::fs::write(
+            directory.join("config.json"),
+            serde_json::to_string(&Settings::new(&directory)).unwrap(),
+        )
+        .unwrap();
+
+        let from_file = Settings::read_config(&[config_file]).unwrap();
+        let from_directory = Settings::read_config(std::slice::from_ref(&directory)).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!((String::from("mobile"), 480), from_file.profile());
+        assert_eq!((String::from("normal"), 1080), from_directory.profile());
+    }
+
+    #[test]
+    fn migrate_legacy_profile() {
+        let legacy =
+            r#"{"ver": [0, 1], "player": "Mpv", "schedule": "./", "profile": ["mobile", 480]}"#;
+        let missing =
+            r#"{"ver": [0, 1], "player": "Mpv", "schedule": "./", "default_profile": "mobile"}"#;
+
+        let settings = Settings::from_reader(legacy.as_bytes()).unwrap();
+        let written = serde_json::to_value(&settings).unwrap();
+        let error = Settings::from_reader(missing.as_bytes()).unwrap_err();
+
+        assert_eq!(
+            BTreeMap::from([(String::from("mobile"), 480)]),
+            settings.profiles
+        );
+        assert_eq!((String::from("mobile"), 480), settings.profile());
+        assert!(written.get("profile").is_none());
+        assert_eq!("mobile", written["default_profile"]);
+        assert!(error
+            .to_string()
+            .contains(r#"The default profile "mobile" is not one of the profiles"#));
+    }
+
+    #[test]
+    fn custom_player_template() {
+        let config = |custom: &str| {
+            format!(r#"{{"ver": [0, 1], "player": "Custom", "schedule": "./"{custom}}}"#)
+        };
+        let valid = config(
+            r#", "custom_player": {"template": "iptvplayer {url} --quality {quality} {audio_flag}", "audio_flag": "--audio-only"}"#,
+        );
+
+        let settings = Settings::from_reader(valid.as_bytes()).unwrap();
+        let options = settings.player_options();
+        let command = |height| {
+            player::player_command(
+                Player::Custom,
+                String::from("https://twitch.tv/jynxzi"),
+                &Quality::Height(height),
+                false,
+                Latency::Normal,
+                &[String::from("--fullscreen")],
+                &options,
+            )
+        };
+        let args = |command: &tokio::process::Command| -> Vec<String> {
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+        let error = |custom: &str| {
+            Settings::from_reader(config(custom).as_bytes())
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!("iptvplayer", command(720).as_std().get_program());
+        assert_eq!(
+            [
+                "https://twitch.tv/jynxzi",
+                "--quality",
+                "720p",
+                "--fullscreen"
+            ],
+            args(&command(720))[..]
+        );
+        assert_eq!(
+            [
+                "https://twitch.tv/jynxzi",
+                "--quality",
+                "audio_only",
+                "--audio-only",
+                "--fullscreen"
+            ],
+            args(&command(0))[..]
+        );
+        assert!(error("").contains("custom_player is not set"));
+        assert!(
+            error(r#", "custom_player": {"template": "iptvplayer --quality {quality}"}"#)
+                .contains("is missing the {url} placeholder")
+        );
+        assert!(
+            error(r#", "custom_player": {"template": "iptvplayer {url} {volume}"}"#)
+                .contains("Unknown placeholder {volume}")
+        );
+        assert!(error(r#", "custom_player": {"template": " "}"#).contains("template is empty"));
+    }
+
+    /// A keyring kept in memory, so the tests never touch the keyring of the machine.
+    #[derive(Default)]
+    struct MemoryKeyring(std::sync::Mutex<std::collections::HashMap<String, String>>);
+
+    impl Keyring for MemoryKeyring {
+        fn lookup(
+            &self,
+            login: &str,
+        ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(self.0.lock().unwrap().get(login).cloned())
+        }
+
+        fn store(
+            &self,
+            login: &str,
+            token: &str,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(login.to_string(), token.to_string());
+            Ok(())
+        }
+
+        fn clear(&self, login: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.0.lock().unwrap().remove(login);
+            Ok(())
+        }
+    }
+
+    /// A keyring that cannot be used, such as without `secret-tool`.
+    struct MissingKeyring;
+
+    impl Keyring for MissingKeyring {
+        fn lookup(
+            &self,
+            _login: &str,
+        ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+            Err("secret-tool not found".into())
+        }
+
+        fn store(
+            &self,
+            _login: &str,
+            _token: &str,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err("secret-tool not found".into())
+        }
+
+        fn clear(&self, _login: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err("secret-tool not found".into())
+        }
+    }
+
+    #[test]
+    fn fall_back_to_token_file_without_keyring() {
+        let directory = std::env::temp_dir().join("stream-cave-keyring");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        let user_data = UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+            in_keyring: false,
+        };
+
+        user_data
+            .save_in(&token_file, TokenStorage::Keyring, &MissingKeyring)
+            .unwrap();
+        let fallback = UserData::from_file_in(&token_file, &MissingKeyring).unwrap();
+        let fallback_storage = UserData::storage(&token_file);
+        std::fs::write(
+            &token_file,
+            r#"{"access_token":"","login":"kaicenat","user_id":"641972806","in_keyring":true}"#,
+        )
+        .unwrap();
+        let keyring_storage = UserData::storage(&token_file);
+        let missing = UserData::from_file_in(&token_file, &MemoryKeyring::default());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("abcdef", fallback.access_token);
+        assert_eq!(TokenStorage::File, fallback_storage);
+        assert_eq!(TokenStorage::Keyring, keyring_storage);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn store_token_in_keyring() {
+        let directory = std::env::temp_dir().join("stream-cave-memory-keyring");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        let keyring = MemoryKeyring::default();
+        let user_data = UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: vec![String::from("user:read:follows")],
+            refresh_token: Some(String::from("ghijkl")),
+            client_id: Some(String::from("AAAA")),
+            client_secret: None,
+            in_keyring: false,
+        };
+
+        user_data
+            .save_in(&token_file, TokenStorage::Keyring, &keyring)
+            .unwrap();
+        let stored = std::fs::read_to_string(&token_file).unwrap();
+        let storage = UserData::storage(&token_file);
+        let read = UserData::from_file_in(&token_file, &keyring).unwrap();
+        UserData::remove_in(&token_file, &keyring).unwrap();
+        let removed = !token_file.exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(!stored.contains("abcdef"));
+        assert_eq!(TokenStorage::Keyring, storage);
+        assert_eq!("abcdef", read.access_token);
+        assert_eq!(Some(String::from("ghijkl")), read.refresh_token);
+        assert!(removed);
+        assert!(keyring.lookup("kaicenat").unwrap().is_none());
+    }
+
+    #[test]
+    fn read_config_and_schedule_from_reader() {
+        let mut settings = Settings::new(Path::new("/srv/stream-cave"));
+        settings.profiles.insert(String::from("mobile"), 480);
+        settings.default_profile = String::from("mobile");
+        let config = serde_json::to_vec(&settings).unwrap();
+        let schedule = std::fs::read("./tests/resources/schedule.json").unwrap();
+
+        let from_config = Settings::from_reader(std::io::Cursor::new(config)).unwrap();
+        let from_schedule = Streams::from_reader(std::io::Cursor::new(schedule)).unwrap();
+        let invalid = Streams::from_reader(&b"["[..]);
+
+        assert_eq!((String::from("mobile"), 480), from_config.profile());
+        assert_eq!(Path::new("/srv/stream-cave"), from_config.schedule);
+        assert_eq!(
+            Streams::read_streams(Path::new("./tests/resources"))
+                .iter()
+                .collect::<Vec<_>>(),
+            from_schedule.iter().collect::<Vec<_>>()
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn look_up_streams_by_name() {
+        let mut streams = Streams::read_streams(Path::new("./tests/resources"));
+
+        assert!(streams.contains("kaicenat"));
+        assert!(!streams.contains("KaiCenat"));
+        assert_eq!(
+            Some("jynxzi"),
+            streams.get("jynxzi").map(|stream| stream.name.as_str())
+        );
+        assert!(streams.get("not_configured").is_none());
+        streams.get_mut("hasanabi").unwrap().resume = true;
+        assert!(streams.get("hasanabi").unwrap().resume);
+        assert_eq!(streams.streams.len(), streams.iter().count());
+    }
+
+    #[test]
+    fn concurrent_writers_serialize() {
+        let path = std::env::temp_dir().join("stream-cave-lock");
+        let _ = std::fs::remove_dir_all(&path);
+        Streams::new().write(&path).unwrap();
+
+        let writers: Vec<_> = [("kaicenat", 641_972_806), ("jynxzi", 411_377_640)]
+            .into_iter()
+            .map(|(name, id)| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let _lock = Streams::lock(&path).unwrap();
+                    let mut streams = Streams::read_streams(&path);
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    streams.streams.push(StreamConfig::new(name, id));
+                    streams.write(&path).unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let written = Streams::read_streams(&path);
+        std::fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(2, written.streams.len());
+    }
+
+    #[tokio::test]
+    async fn play_top_live_stream() {
+        use mock_api::{helix_response, stream_data_with_viewers, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let mut data = Vec::new();
+            if request.contains("user_login=kaicenat") {
+                data.push(stream_data_with_viewers("kaicenat", 641_972_806, 100));
+            }
+            if request.contains("user_login=jynxzi") {
+                data.push(stream_data_with_viewers("jynxzi", 411_377_640, 5000));
+            }
+            (200, helix_response(&data))
+        })
+        .await;
+        let mut streams = Streams::new();
+        streams
+            .streams
+            .push(StreamConfig::new("kaicenat", 641_972_806));
+        streams
+            .streams
+            .push(StreamConfig::new("jynxzi", 411_377_640));
+        streams
+            .streams
+            .push(StreamConfig::new("caseoh_", 267_160_288));
+        streams.groups.push(StreamGroup {
+            name: String::from("variety"),
+            streams: vec![String::from("kaicenat"), String::from("caseoh_")],
+            exclusive: false,
+        });
+        let token = mock_api::unchecked_token();
+
+        let top = streams
+            .top_live_stream(None, &mock.url("/helix/streams"), "", &token)
+            .await
+            .unwrap();
+        let top_variety = streams
+            .top_live_stream(Some("variety"), &mock.url("/helix/streams"), "", &token)
+            .await
+            .unwrap();
+        let missing_group = streams
+            .top_live_stream(Some("esports"), &mock.url("/helix/streams"), "", &token)
+            .await;
+
+        assert_eq!(
+            Some(("jynxzi", 5000)),
+            top.map(|(stream, viewers)| (stream.name.as_str(), viewers))
+        );
+        assert_eq!(
+            Some(("kaicenat", 100)),
+            top_variety.map(|(stream, viewers)| (stream.name.as_str(), viewers))
+        );
+        assert!(missing_group.is_err());
+    }
+
+    #[tokio::test]
+    async fn bound_live_checks_in_flight() {
+        use mock_api::{helix_response, stream_data, MockApi};
+
+        let mock = MockApi::start_with_delay(std::time::Duration::from_millis(50), |request| {
+            if request.contains("user_login=jynxzi") {
+                (200, helix_response(&[stream_data("jynxzi", 411_377_640)]))
+            } else {
+                (200, helix_response(&[]))
+            }
+        })
+        .await;
+        let names: Vec<String> = ["kaicenat", "jynxzi", "caseoh_", "xqc", "zackrawrr"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let token = mock_api::unchecked_token();
+
+        let live = check_live_status(&names, 2, &mock.url("/helix/streams"), "", &token)
+            .await
+            .unwrap();
+
+        assert_eq!(5, mock.requests().len());
+        assert_eq!(2, mock.max_in_flight());
+        assert_eq!(5, live.len());
+        assert_eq!(Some(&true), live.get("jynxzi"));
+        assert_eq!(Some(&false), live.get("kaicenat"));
+    }
+
+    #[tokio::test]
+    async fn sort_upcoming_streams() {
+        use mock_api::{helix_response, schedule_response, MockApi};
+
+        let mock = MockApi::start(|request| {
+            if request.contains("broadcaster_id=641972806") {
+                (
+                    200,
+                    schedule_response(
+                        "kaicenat",
+                        &["2024-03-02T18:00:00Z", "2024-03-04T18:00:00Z"],
+                    ),
+                )
+            } else if request.contains("broadcaster_id=411377640") {
+                (200, schedule_response("jynxzi", &["2024-03-03T20:00:00Z"]))
+            } else {
+                (404, helix_response(&[]))
+            }
+        })
+        .await;
+        let mut streams = Streams::new();
+        streams
+            .streams
+            .push(StreamConfig::new("kaicenat", 641_972_806));
+        streams
+            .streams
+            .push(StreamConfig::new("jynxzi", 411_377_640));
+        streams
+            .streams
+            .push(StreamConfig::new("caseoh_", 267_160_288));
+        let token = mock_api::unchecked_token();
+
+        let upcoming = streams
+            .upcoming_streams(None, &mock.url("/helix/schedule"), "", &token)
+            .await
+            .unwrap();
+        let unscheduled = streams
+            .upcoming_streams(Some("caseoh_"), &mock.url("/helix/schedule"), "", &token)
+            .await
+            .unwrap();
+        let missing = streams
+            .upcoming_streams(Some("hasanabi"), &mock.url("/helix/schedule"), "", &token)
+            .await;
+
+        assert_eq!(
+            vec![
+                ("kaicenat", "2024-03-02T18:00:00Z"),
+                ("jynxzi", "2024-03-03T20:00:00Z"),
+                ("kaicenat", "2024-03-04T18:00:00Z"),
+            ],
+            upcoming
+                .iter()
+                .map(|(stream, segment)| (stream.name.as_str(), segment.start_time.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert!(unscheduled.is_empty());
+        assert!(missing.is_err());
+    }
+
+    fn merge_schedules(strategy: MergeStrategy) -> Streams {
+        let mut kaicenat = StreamConfig::new("kaicenat", 0);
+        kaicenat
+            .quality_overides
+            .insert(String::from("normal"), Quality::Height(720));
+        kaicenat.streams_to_open_on.push(String::from("jynxzi"));
+        let mut mine = Streams {
+            streams: vec![kaicenat, StreamConfig::new("jynxzi", 411_377_640)],
+            groups: vec![StreamGroup {
+                name: String::from("favorites"),
+                streams: vec![String::from("kaicenat")],
+                exclusive: false,
+            }],
+            ..Streams::new()
+        };
+        let mut their_kaicenat = StreamConfig::new("kaicenat", 641_972_806);
+        their_kaicenat
+            .quality_overides
+            .insert(String::from("normal"), Quality::Height(480));
+        their_kaicenat
+            .quality_overides
+            .insert(String::from("mobile"), Quality::Height(360));
+        their_kaicenat
+            .streams_to_open_on
+            .extend([String::from("jynxzi"), String::from("caseoh_")]);
+        let theirs = Streams {
+            streams: vec![their_kaicenat, StreamConfig::new("caseoh_", 267_160_288)],
+            groups: vec![StreamGroup {
+                name: String::from("favorites"),
+                streams: vec![String::from("caseoh_")],
+                exclusive: false,
+            }],
+            ..Streams::new()
+        };
+
+        mine.merge(theirs, strategy);
+        mine
+    }
+
+    #[test]
+    fn merge_keeping_mine() {
+        let merged = merge_schedules(MergeStrategy::KeepMine);
+
+        let names: Vec<&str> = merged.iter().map(|stream| stream.name.as_str()).collect();
+        assert_eq!(vec!["kaicenat", "jynxzi", "caseoh_"], names);
+        let kaicenat = merged.get("kaicenat").unwrap();
+        assert_eq!(0, kaicenat.id);
+        assert_eq!(
+            BTreeMap::from([(String::from("normal"), Quality::Height(720))]),
+            kaicenat.quality_overides
+        );
+        assert_eq!(vec![String::from("kaicenat")], merged.groups[0].streams);
+    }
+
+    #[test]
+    fn merge_taking_theirs() {
+        let merged = merge_schedules(MergeStrategy::TakeTheirs);
+
+        assert_eq!(3, merged.iter().count());
+        let kaicenat = merged.get("kaicenat").unwrap();
+        assert_eq!(641_972_806, kaicenat.id);
+        assert_eq!(
+            Some(&Quality::Height(480)),
+            kaicenat.quality_overides.get("normal")
+        );
+        assert_eq!(vec![String::from("caseoh_")], merged.groups[0].streams);
+        assert_eq!(411_377_640, merged.get("jynxzi").unwrap().id);
+    }
+
+    #[test]
+    fn merge_combining_overrides() {
+        let merged = merge_schedules(MergeStrategy::MergeOverrides);
+
+        assert_eq!(3, merged.iter().count());
+        let kaicenat = merged.get("kaicenat").unwrap();
+        assert_eq!(641_972_806, kaicenat.id);
+        assert_eq!(
+            BTreeMap::from([
+                (String::from("mobile"), Quality::Height(360)),
+                (String::from("normal"), Quality::Height(720))
+            ]),
+            kaicenat.quality_overides
+        );
+        assert_eq!(
+            vec![String::from("jynxzi"), String::from("caseoh_")],
+            kaicenat.streams_to_open_on
+        );
+        assert_eq!(
+            vec![String::from("kaicenat"), String::from("caseoh_")],
+            merged.groups[0].streams
+        );
+    }
+
+    #[test]
+    fn override_change_keeps_identity() {
+        let streams = Streams::read_streams(Path::new("./tests/resources"));
+        let kaicenat = streams.get("kaicenat").unwrap();
+        let jynxzi = streams.get("jynxzi").unwrap();
+        let mut overridden = kaicenat.clone();
+        overridden
+            .quality_overides
+            .insert(String::from("normal"), Quality::Height(1080));
+        let mut new_id = kaicenat.clone();
+        new_id.id = 1;
+        let mut unresolved = jynxzi.clone();
+        unresolved.id = 0;
+        let mut other_unresolved = kaicenat.clone();
+        other_unresolved.name = String::from("caseoh_");
+        other_unresolved.id = 0;
+
+        assert!(kaicenat.is_same_stream(&overridden));
+        assert!(kaicenat.settings_changed(&overridden));
+        assert_ne!(kaicenat, &new_id);
+        assert!(kaicenat.is_same_stream(&new_id));
+        assert!(!kaicenat.settings_changed(&new_id));
+        assert!(!kaicenat.is_same_stream(jynxzi));
+        assert!(!unresolved.is_same_stream(&other_unresolved));
+    }
+
+    #[tokio::test]
+    async fn search_overridden_api_base() {
+        use mock_api::{helix_response, search_data, MockApi};
+
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                helix_response(&[search_data("kaicenat", 641_972_806, true)]),
+            )
+        })
+        .await;
+        let mut settings = Settings::new(Path::new("./"));
+        settings.endpoints.api_base = mock.url("/mock/helix");
+        let mut streams = Streams::new();
+
+        streams
+            .add_stream(
+                "kaicenat",
+                &None,
+                &settings.endpoints.search_channels(),
+                "AAAA",
+                mock_api::unchecked_token(),
+                twitch_socket::DEFAULT_MAX_SUBSCRIPTION_COST,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(641_972_806, streams.streams[0].id);
+        assert!(mock.requests()[0].starts_with("GET /mock/helix/search/channels?query=kaicenat "));
+    }
+
+    #[tokio::test]
+    async fn add_with_rejected_token() {
+        use mock_api::MockApi;
+
+        let mock = MockApi::start(|_| (401, String::from(r#"{"error":"Unauthorized"}"#))).await;
+        let mut streams = Streams::new();
+
+        let error = streams
+            .add_stream(
+                "kaicenat",
+                &None,
+                &mock.url("/search/channels"),
+                "AAAA",
+                mock_api::unchecked_token(),
+                twitch_socket::DEFAULT_MAX_SUBSCRIPTION_COST,
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            Some(&authentication::TokenError::Invalid),
+            error.downcast_ref::<authentication::TokenError>()
+        );
+        assert!(streams.streams.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuse_streams_over_subscription_cost() {
+        use mock_api::{helix_response, search_data, MockApi};
+
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                helix_response(&[search_data("caseoh_", 267_160_288, true)]),
+            )
+        })
+        .await;
+        let mut streams = Streams::read_streams(Path::new("./tests/resources"));
+
+        let error = streams
+            .add_stream(
+                "caseoh_",
+                &None,
+                &mock.url("/search/channels"),
+                "AAAA",
+                mock_api::unchecked_token(),
+                7,
+            )
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            "Adding caseoh_ would raise the subscription cost to 8, over the limit of 7. Remove a \
+            stream first or raise `max_subscription_cost` if the limit of the app was raised",
+            error.to_string()
+        );
+        assert_eq!(3, streams.streams.len());
+        assert!(mock.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn find_unresolved_streams() {
+        use mock_api::{helix_response, search_data, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let data = if request.contains("query=kaicenat") {
+                vec![search_data("kaicenat", 641_972_806, false)]
+            } else if request.contains("query=jynxzi") {
+                vec![
+                    search_data("jynxzi2", 1, false),
+                    search_data("jynxzi", 411_377_640, true),
+                ]
+            } else {
+                vec![search_data("hasanabi2", 2, false)]
+            };
+            (200, helix_response(&data))
+        })
+        .await;
+        let mut streams = Streams {
+            streams: vec![
+                StreamConfig::new("kaicenat", 641_972_806),
+                StreamConfig::new("hasanabi", 0),
+                StreamConfig::new("jynxzi", 411_377_640),
+            ],
+            ..Streams::new()
+        };
+
+        let unresolved = streams
+            .unresolved_streams(
+                &mock.url("/search/channels"),
+                "AAAA",
+                &mock_api::unchecked_token(),
+            )
+            .await
+            .unwrap();
+        for name in &unresolved {
+            streams.remove_stream(name);
+        }
+
+        assert_eq!(vec![String::from("hasanabi")], unresolved);
+        let names: Vec<&str> = streams
+            .streams
+            .iter()
+            .map(|stream| stream.name.as_str())
+            .collect();
+        assert_eq!(vec!["kaicenat", "jynxzi"], names);
+    }
+
+    #[tokio::test]
+    async fn refresh_unknown_id() {
+        use mock_api::{helix_response, search_data, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let data = if request.contains("query=kaicenat") {
+                vec![search_data("kaicenat", 641_972_806, false)]
+            } else {
+                Vec::new()
+            };
+            (200, helix_response(&data))
+        })
+        .await;
+        let mut streams = Streams {
+            streams: vec![
+                StreamConfig::new("kaicenat", 0),
+                StreamConfig::new("hasanabi", 5),
+            ],
+            ..Streams::new()
+        };
+
+        let changes = streams
+            .refresh_ids(
+                &mock.url("/search/channels"),
+                "AAAA",
+                &mock_api::unchecked_token(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(vec![(String::from("kaicenat"), 0, 641_972_806)], changes);
+        assert_eq!(641_972_806, streams.streams[0].id);
+        assert_eq!(5, streams.streams[1].id);
+    }
+
+    #[test]
+    fn plan_dependent_opens_and_closes() {
+        let mut jynxzi = StreamConfig::new("jynxzi", 411_377_640);
+        jynxzi.streams_to_open_on.push(String::from("kaicenat"));
+        let mut caseoh = StreamConfig::new("caseoh_", 267_160_288);
+        caseoh.streams_to_open_on.push(String::from("jynxzi"));
+        let mut kaicenat = StreamConfig::new("kaicenat", 641_972_806);
+        kaicenat.streams_to_open_on.push(String::from("caseoh_"));
+        kaicenat.streams_to_close_on.push(String::from("jynxzi"));
+        let mut hasanabi = StreamConfig::new("hasanabi", 207_813_352);
+        hasanabi.streams_to_close_on.push(String::from("caseoh_"));
+        let streams = Streams {
+            streams: vec![
+                kaicenat,
+                jynxzi,
+                caseoh,
+                hasanabi,
+                StreamConfig::new("xqc", 71_092_938),
+                StreamConfig::new("shroud", 37_402_112),
+            ],
+            groups: vec![
+                StreamGroup {
+                    name: String::from("favorites"),
+                    streams: vec![String::from("kaicenat"), String::from("xqc")],
+                    exclusive: true,
+                },
+                StreamGroup {
+                    name: String::from("fps"),
+                    streams: vec![String::from("kaicenat"), String::from("shroud")],
+                    exclusive: false,
+                },
+            ],
+            ..Streams::new()
+        };
+
+        let plan = streams.plan("kaicenat");
+
+        assert_eq!(vec!["jynxzi", "caseoh_"], plan.opens);
+        assert_eq!(vec!["hasanabi", "xqc"], plan.closes);
+        assert!(streams.plan("shroud").opens.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolve_found_and_missing_ids() {
+        use mock_api::{helix_response, search_data, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let data = if request.contains("query=kaicenat") {
+                vec![
+                    search_data("kaicenat2", 1, false),
+                    search_data("kaicenat", 641_972_806, false),
+                ]
+            } else {
+                vec![search_data("hasanabi2", 2, false)]
+            };
+            (200, helix_response(&data))
+        })
+        .await;
+        let api_url = mock.url("/search/channels");
+        let token = mock_api::unchecked_token();
+
+        let found = resolve_broadcaster_id("kaicenat", &api_url, "AAAA", &token)
+            .await
+            .unwrap();
+        let missing = resolve_broadcaster_id("hasanabi", &api_url, "AAAA", &token)
+            .await
+            .unwrap();
+
+        assert_eq!(Some(641_972_806), found);
+        assert_eq!(None, missing);
+    }
+
+    #[tokio::test]
+    async fn resolve_with_rejected_token() {
+        use mock_api::MockApi;
+
+        let mock = MockApi::start(|_| (401, String::from(r#"{"error":"Unauthorized"}"#))).await;
+
+        let error = resolve_broadcaster_id(
+            "kaicenat",
+            &mock.url("/search/channels"),
+            "AAAA",
+            &mock_api::unchecked_token(),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            Some(&authentication::TokenError::Invalid),
+            error.downcast_ref::<authentication::TokenError>()
+        );
     }
 }
@@ -1,15 +1,70 @@
-use crate::UserData;
+use crate::{http, TokenStorage, UserData};
 use core::panic;
-use std::{path::Path, time::Duration};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::Future, path::Path, time::Duration};
+use tokio::sync::oneshot;
 
 use twitch_oauth2::{
     tokens::{errors::ValidationError, UserToken},
-    url, ImplicitUserTokenBuilder,
+    url, AccessToken, ClientId, ClientSecret, ImplicitUserTokenBuilder, RefreshToken, Scope,
+    TwitchToken,
 };
 
-/// Validate the given the token found in the directory. If the token is valid modify
+/// How often the token validation is retried after a failed request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The number of retries after a failed request, `None` to retry forever
+    pub max_retries: Option<u32>,
+    /// Time to wait before each retry
+    pub interval: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry every 60 seconds until the request succeeds, used by the daemon.
+    pub const FOREVER: Self = Self {
+        max_retries: None,
+        interval: Duration::from_mins(1),
+    };
+    /// Retry 3 times every 5 seconds, used by the cli.
+    pub const FEW: Self = Self {
+        max_retries: Some(3),
+        interval: Duration::from_secs(5),
+    };
+}
+
+/// Errors of the token validation that can be reported to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    /// The validation request failed on every attempt
+    Network { attempts: u32 },
+    /// Twitch rejected the token of a request, it expired or was revoked
+    Invalid,
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Network { attempts } => write!(
+                f,
+                "Unable to reach twitch to validate the token after {attempts} attempts"
+            ),
+            Self::Invalid => write!(f, "The token was rejected by twitch"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Validate the token stored in the `token_path` file. If the token is valid modify
 /// `user_access_token`.
 ///
+/// Rejected tokens with a refresh token and client id are refreshed and the new token is saved
+/// where the old token was stored. Other rejected tokens, such as those of the implicit flow,
+/// have to be created again.
+///
+/// When `retry` is set the function waits for a missing token to be created and retries failed
+/// requests forever, otherwise failed requests are retried a few times.
+///
 /// # Errors
 /// The function can fail due to various errors most notably network errors and an invalid token.
 ///
@@ -24,66 +79,265 @@ use twitch_oauth2::{
 /// use std::path::Path;
 ///
 /// let mut token = None;
-/// let path = Path::new("./");
+/// let path = Path::new("./user-data.json");
 ///
 /// validate_oauth_token(&mut token, &path, true).await.unwrap();
 /// }
 /// ```
 pub async fn validate_oauth_token(
     user_access_token: &mut Option<UserToken>,
-    path: &Path,
+    token_path: &Path,
+    retry: bool,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    let policy = if retry {
+        RetryPolicy::FOREVER
+    } else {
+        RetryPolicy::FEW
+    };
+    validate_oauth_token_with_policy(user_access_token, token_path, retry, policy).await
+}
+
+/// Validate the token stored in the `token_path` file retrying failed requests as set by
+/// `policy`. If the token is valid modify `user_access_token`.
+///
+/// When `retry` is set the function waits for a missing token to be created.
+///
+/// # Errors
+/// The function can fail due to various errors most notably an invalid token. When the requests
+/// fail more often than `policy` allows a `TokenError::Network` is returned.
+///
+/// # Panics
+/// If there token authentication returns an unkown error the function will panic
+///
+/// # Examples
+/// ```no_run
+/// #[tokio::main]
+/// async fn main() {
+/// use stream_cave::authentication::{validate_oauth_token_with_policy, RetryPolicy};
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// let mut token = None;
+/// let path = Path::new("./user-data.json");
+/// let policy = RetryPolicy { max_retries: Some(1), interval: Duration::from_secs(1) };
+///
+/// validate_oauth_token_with_policy(&mut token, &path, false, policy).await.unwrap();
+/// }
+/// ```
+pub async fn validate_oauth_token_with_policy(
+    user_access_token: &mut Option<UserToken>,
+    token_path: &Path,
     retry: bool,
+    policy: RetryPolicy,
 ) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
+    validate_with_client(
+        &http::client(),
+        user_access_token,
+        token_path,
+        retry,
+        policy,
+    )
+    .await
+}
 
+async fn validate_with_client<C: twitch_oauth2::client::Client>(
+    client: &C,
+    user_access_token: &mut Option<UserToken>,
+    token_path: &Path,
+    retry: bool,
+    policy: RetryPolicy,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
+    let mut failed_requests = 0;
     loop {
-        if path.join("user-data.json").exists() {
-            let user_data = UserData::from_file(&path.join("user-data.json"))?;
-            let token_result = UserToken::from_token(&client, user_data.access_token.into()).await;
+        if token_path.exists() {
+            let user_data = UserData::from_file(token_path)?;
+            let token_result =
+                UserToken::from_token(client, user_data.access_token.clone().into()).await;
             match token_result {
                 Ok(token) => {
                     *user_access_token = Some(token);
                     return Ok(());
                 }
-                Err(error) => {
-                    match error {
-                        ValidationError::NotAuthorized => {
-                            eprintln!("Token not authorized please create new token, trying again in 60 seconds.");
-                            tokio::time::sleep(Duration::from_secs(60)).await;
-                        }
-                        ValidationError::Request(_) => {
-                            eprintln!("Request error when authenticating token, trying again in 60 seconds.");
-                            tokio::time::sleep(Duration::from_secs(60)).await;
+                Err(error) => match error {
+                    ValidationError::NotAuthorized => {
+                        if let Some(token) =
+                            refresh_saved_token(&http::client(), TOKEN_URL, &user_data, token_path)
+                                .await
+                        {
+                            *user_access_token = Some(token);
+                            return Ok(());
                         }
-                        ValidationError::InvalidToken(token_error) => {
-                            return Err(token_error.into());
-                        }
-                        ValidationError::RequestParseError(request_error) => {
-                            return Err(request_error.into());
+                        eprintln!("Token not authorized please create new token, trying again in 60 seconds.");
+                        tokio::time::sleep(Duration::from_mins(1)).await;
+                    }
+                    ValidationError::Request(_) => {
+                        failed_requests += 1;
+                        if policy.max_retries.is_some_and(|max| failed_requests > max) {
+                            return Err(TokenError::Network {
+                                attempts: failed_requests,
+                            }
+                            .into());
                         }
-                        _ => panic!("Unkown error on parsing twitch token validation errors"),
+                        eprintln!(
+                            "Request error when authenticating token, trying again in {} seconds.",
+                            policy.interval.as_secs()
+                        );
+                        tokio::time::sleep(policy.interval).await;
                     }
-                }
+                    ValidationError::InvalidToken(token_error) => {
+                        return Err(token_error.into());
+                    }
+                    ValidationError::RequestParseError(request_error) => {
+                        return Err(request_error.into());
+                    }
+                    _ => panic!("Unkown error on parsing twitch token validation errors"),
+                },
             }
         } else if !retry {
             return Err(String::from("Token does not exist").into());
         } else {
             eprintln!("No existing token, Please create token. Rechecking in 60 seconds.");
-            tokio::time::sleep(Duration::from_secs(60)).await;
+            tokio::time::sleep(Duration::from_mins(1)).await;
         }
     }
 }
 
-/// Create a twitch oauth2 token using implicit grant flow.
+/// Refresh the token of `user_data` at `token_url` and save the refreshed token to `token_path`.
+/// Returns `None` when the token cannot be refreshed, after printing why.
+async fn refresh_saved_token(
+    client: &reqwest::Client,
+    token_url: &str,
+    user_data: &UserData,
+    token_path: &Path,
+) -> Option<UserToken> {
+    let token = match refresh_user_token(client, token_url, user_data).await? {
+        Ok(token) => token,
+        Err(error) => {
+            eprintln!("Error refreshing the token: {error}");
+            return None;
+        }
+    };
+    let refreshed = UserData {
+        client_secret: user_data.client_secret.clone(),
+        ..UserData::from_token(&token)
+    };
+    if let Err(error) = refreshed.save_with(token_path, UserData::storage(token_path)) {
+        eprintln!("Error saving the refreshed token: {error}");
+    }
+    eprintln!("Refreshed the rejected token");
+    Some(token)
+}
+
+/// The token returned by a refresh.
+#[derive(Deserialize, Debug)]
+struct RefreshedToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+    #[serde(default)]
+    scope: Vec<String>,
+}
+
+/// Refresh the token of `user_data` at `token_url`. Returns `None` for tokens without a refresh
+/// token or client id.
 ///
-/// # Errors
-/// The function can return error due to an invalid url, a failure in token creation and a failure
-/// in token validation.
+/// The client secret is only sent when one is stored, tokens of public clients such as those of
+/// the device code flow are refreshed without it. The refreshed token keeps the user of
+/// `user_data`, it was just issued by twitch so it is not validated again.
+async fn refresh_user_token(
+    client: &reqwest::Client,
+    token_url: &str,
+    user_data: &UserData,
+) -> Option<Result<UserToken, Box<dyn std::error::Error + Sync + Send>>> {
+    let (Some(refresh_token), Some(client_id)) = (&user_data.refresh_token, &user_data.client_id)
+    else {
+        return None;
+    };
+    let mut form = vec![
+        ("client_id", client_id.as_str()),
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token.as_str()),
+    ];
+    if let Some(client_secret) = &user_data.client_secret {
+        form.push(("client_secret", client_secret));
+    }
+    let refreshed = async {
+        let refreshed: RefreshedToken = client
+            .post(token_url)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(UserToken::from_existing_unchecked(
+            AccessToken::new(refreshed.access_token),
+            refreshed.refresh_token.map(RefreshToken::new),
+            ClientId::new(client_id.clone()),
+            user_data.client_secret.clone().map(ClientSecret::new),
+            user_data.login.clone().into(),
+            user_data.user_id.clone().into(),
+            Some(refreshed.scope.into_iter().map(Scope::from).collect()),
+            refreshed.expires_in.map(Duration::from_secs),
+        ))
+    };
+    Some(refreshed.await)
+}
+
+/// Whether a usable token is stored, without the secret.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStatus {
+    /// The token was accepted by twitch
+    Present,
+    /// No token is stored
+    Absent,
+    /// The token was rejected by twitch
+    Expired,
+    /// Twitch could not be reached to check the token
+    Unverified,
+}
+
+/// Check the token stored in the `token_path` file once, without retrying or waiting.
 ///
-/// # Panics
-/// Panics can hapen when the entered url does not have the proper query url structure
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::{token_status, TokenStatus};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let status = token_status(Path::new("./user-data.json")).await;
+///
+///     assert_eq!(TokenStatus::Present, status);
+/// }
+/// ```
+pub async fn token_status(token_path: &Path) -> TokenStatus {
+    token_status_with_client(&http::client(), token_path).await
+}
+
+pub(crate) async fn token_status_with_client<C: twitch_oauth2::client::Client>(
+    client: &C,
+    token_path: &Path,
+) -> TokenStatus {
+    let Ok(user_data) = UserData::from_file(token_path) else {
+        return TokenStatus::Absent;
+    };
+    match UserToken::from_token(client, user_data.access_token.into()).await {
+        Ok(_) => TokenStatus::Present,
+        Err(ValidationError::NotAuthorized | ValidationError::InvalidToken(_)) => {
+            TokenStatus::Expired
+        }
+        Err(_) => TokenStatus::Unverified,
+    }
+}
+
+/// Create a twitch oauth2 token using implicit grant flow and save it to the `token_path` file in
+/// plain text.
+///
+/// # Errors
+/// The function can return error due to an invalid url, a redirect url without a token or an
+/// error, a failure in token creation and a failure in token validation.
 ///
 ///# Examples
 ///```no_run
@@ -93,35 +347,442 @@ pub async fn validate_oauth_token(
 /// use stream_cave::create_oauth_token;
 ///
 /// let client_id = "someclientid";
-/// let path = Path::new("./");
+/// let path = Path::new("./user-data.json");
 ///
 /// create_oauth_token(client_id, &path).await.unwrap();
 /// }
 ///```
 pub async fn create_oauth_token(
     client_id: &str,
-    path: &Path,
+    token_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    create_oauth_token_with_scopes(client_id, token_path, &[], TokenStorage::File).await
+}
+
+/// Create a twitch oauth2 token requesting `scopes` using implicit grant flow and save it to
+/// `storage`, see `UserData::save_with`. The granted scopes are saved with the token.
+///
+/// # Errors
+/// The function can return error due to an invalid url, a redirect url without a token or an
+/// error, a failure in token creation and a failure in token validation.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::create_oauth_token_with_scopes;
+/// use stream_cave::TokenStorage;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let path = Path::new("./user-data.json");
+///     let scopes = [String::from("user:read:follows")];
+///
+///     create_oauth_token_with_scopes("someclientid", &path, &scopes, TokenStorage::File)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn create_oauth_token_with_scopes(
+    client_id: &str,
+    token_path: &Path,
+    scopes: &[String],
+    storage: TokenStorage,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = http::client();
+
+    let mut token = token_builder(client_id, scopes)?;
+
+    let response = authorize(&mut token, |_| prompt_redirect_address()).await?;
+
+    let user_token = match response {
+        RedirectResponse::Token {
+            access_token,
+            state,
+        } => {
+            let state_decoded = percent_encoding::percent_decode_str(&state).decode_utf8()?;
+            token
+                .get_user_token(
+                    &client,
+                    Some(&state_decoded),
+                    Some(&access_token),
+                    None,
+                    None,
+                )
+                .await?
+        }
+        RedirectResponse::Error {
+            error,
+            error_description,
+        } => {
+            token
+                .get_user_token(&client, None, None, Some(&error), Some(&error_description))
+                .await?
+        }
+    };
+
+    UserData::from_token(&user_token).save_with(token_path, storage)?;
+
+    Ok(())
+}
+
+/// The device authorization endpoint of twitch.
+pub const DEVICE_URL: &str = "https://id.twitch.tv/oauth2/device";
+/// The token endpoint of twitch.
+pub const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+/// How much the poll interval grows after a `slow_down` response.
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// The code the user enters on the verification page of the device code flow.
+#[derive(Deserialize, Debug)]
+struct DeviceAuthorization {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+/// The token returned once the device code was authorized.
+#[derive(Deserialize, Debug)]
+struct DeviceToken {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+/// The error returned while polling for the token of a device code.
+#[derive(Deserialize, Debug)]
+struct PollError {
+    message: String,
+}
+
+/// Create a twitch oauth2 token requesting `scopes` using the device code flow and save it to
+/// `storage`, see `UserData::save_with`.
+///
+/// A code and the page to enter it on are printed, so the token can be created from any browser,
+/// such as for a headless server.
+///
+/// # Errors
+/// The function can return error if twitch cannot be reached, the code is denied or expires
+/// before it is entered, and if the token cannot be validated or saved.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::create_oauth_token_with_device_code;
+/// use stream_cave::TokenStorage;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let path = Path::new("./user-data.json");
+///
+///     create_oauth_token_with_device_code("someclientid", &path, &[], TokenStorage::Keyring)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn create_oauth_token_with_device_code(
+    client_id: &str,
+    token_path: &Path,
+    scopes: &[String],
+    storage: TokenStorage,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = http::client();
+    let token = device_code_token(&client, DEVICE_URL, TOKEN_URL, client_id, scopes).await?;
+    let user_token = UserToken::from_existing(
+        &client,
+        AccessToken::new(token.access_token),
+        token.refresh_token.map(RefreshToken::new),
+        None,
+    )
+    .await?;
+
+    UserData::from_token(&user_token).save_with(token_path, storage)?;
+
+    Ok(())
+}
+
+/// Request a device code from `device_url`, print it and poll `token_url` until the code is
+/// authorized. The poll interval grows by `SLOW_DOWN_INCREMENT` on every `slow_down` response.
+async fn device_code_token(
+    client: &reqwest::Client,
+    device_url: &str,
+    token_url: &str,
+    client_id: &str,
+    scopes: &[String],
+) -> Result<DeviceToken, Box<dyn std::error::Error + Send + Sync>> {
+    let scopes = scopes.join(" ");
+    let device: DeviceAuthorization = client
+        .post(device_url)
+        .form(&[("client_id", client_id), ("scopes", &scopes)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    println!(
+        "Go to {} and enter the code {}",
+        device.verification_uri, device.user_code
+    );
+
+    let expires = tokio::time::Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = Duration::from_secs(device.interval);
+    loop {
+        tokio::time::sleep(interval).await;
+        if tokio::time::Instant::now() >= expires {
+            return Err("The code expired before it was entered, create the token again".into());
+        }
+        let response = client
+            .post(token_url)
+            .form(&[
+                ("client_id", client_id),
+                ("scopes", &scopes),
+                ("device_code", &device.device_code),
+                ("grant_type", DEVICE_CODE_GRANT),
+            ])
+            .send()
+            .await?;
+        if response.status().is_success() {
+            return Ok(response.json().await?);
+        }
+        let error: PollError = response.json().await?;
+        match error.message.as_str() {
+            "authorization_pending" => {}
+            "slow_down" => interval += SLOW_DOWN_INCREMENT,
+            message => return Err(format!("The code was not authorized: {message}").into()),
+        }
+    }
+}
+
+/// The token revocation endpoint of twitch.
+pub const REVOKE_URL: &str = "https://id.twitch.tv/oauth2/revoke";
+
+/// Revoke the token saved in `token_path` with twitch and delete it, see `UserData::remove`.
+///
+/// The token is deleted even if it cannot be revoked, such as when offline, after a warning that
+/// it may still be active.
+///
+/// # Errors
+/// Will return an error if the token cannot be deleted.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::delete_oauth_token;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let path = Path::new("./user-data.json");
+///
+///     delete_oauth_token("someclientid", &path).await.unwrap();
+/// }
+/// ```
+pub async fn delete_oauth_token(
+    client_id: &str,
+    token_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    delete_token(&http::client(), REVOKE_URL, client_id, token_path).await
+}
+
+/// Revoke the token saved in `token_path` at `revoke_url`, using the client id it was created
+/// with when saved, and delete it.
+async fn delete_token(
+    client: &reqwest::Client,
+    revoke_url: &str,
+    client_id: &str,
+    token_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let revoked = match UserData::from_file(token_path) {
+        Ok(user_data) => {
+            let client_id = user_data.client_id.as_deref().unwrap_or(client_id);
+            revoke_token(client, revoke_url, client_id, &user_data.access_token).await
+        }
+        Err(error) => Err(error),
+    };
+    if let Err(error) = revoked {
+        eprintln!("Warning: unable to revoke the token, it may still be active: {error}");
+    }
+    UserData::remove(token_path)
+}
+
+async fn revoke_token(
+    client: &reqwest::Client,
+    revoke_url: &str,
+    client_id: &str,
+    access_token: &str,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
+    client
+        .post(revoke_url)
+        .form(&[("client_id", client_id), ("token", access_token)])
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
 
+fn token_builder(
+    client_id: &str,
+    scopes: &[String],
+) -> Result<ImplicitUserTokenBuilder, url::ParseError> {
     let id = twitch_oauth2::ClientId::new(client_id.to_string());
     let redirect_url = url::Url::parse("https://iridescentsun.com")?;
-    let mut token = ImplicitUserTokenBuilder::new(id, redirect_url).force_verify(true);
+    let scopes = scopes
+        .iter()
+        .map(|scope| Scope::parse(scope.clone()))
+        .collect();
+    Ok(ImplicitUserTokenBuilder::new(id, redirect_url)
+        .set_scopes(scopes)
+        .force_verify(true))
+}
+
+/// The scopes of `required` that were not granted to `token`.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::{missing_scopes, validate_oauth_token};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut token = None;
+///     validate_oauth_token(&mut token, Path::new("./user-data.json"), false).await.unwrap();
+///
+///     let required = [String::from("user:read:follows")];
+///     assert!(missing_scopes(&token.unwrap(), &required).is_empty());
+/// }
+/// ```
+#[must_use]
+pub fn missing_scopes(token: &UserToken, required: &[String]) -> Vec<String> {
+    required
+        .iter()
+        .filter(|scope| {
+            !token
+                .scopes()
+                .iter()
+                .any(|granted| granted.as_str() == *scope)
+        })
+        .cloned()
+        .collect()
+}
+
+/// How long before the user token expires the daemon warns to create a new one.
+pub const EXPIRY_WARNING_THRESHOLD: Duration = Duration::from_hours(1);
+
+/// Wait until `token` expires within `threshold` and return the time it has left. Returns right
+/// away when the token already expires within `threshold`.
+///
+/// Tokens of the implicit grant flow cannot be refreshed, so a new token has to be created
+/// before the remaining time runs out.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::authentication::{expiry_warning, validate_oauth_token, EXPIRY_WARNING_THRESHOLD};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut token = None;
+///     validate_oauth_token(&mut token, Path::new("./user-data.json"), false).await.unwrap();
+///
+///     let remaining = expiry_warning(&token.unwrap(), EXPIRY_WARNING_THRESHOLD).await;
+///     println!("The token expires in {} minutes", remaining.as_secs() / 60);
+/// }
+/// ```
+pub async fn expiry_warning(token: &UserToken, threshold: Duration) -> Duration {
+    let expires_in = token.expires_in();
+    let warn_in = expires_in.saturating_sub(threshold);
+    tokio::time::sleep(warn_in).await;
+    expires_in.min(threshold)
+}
 
-    let (url, _) = token.generate_url();
+/// How often a malformed address can be pasted before the token creation fails.
+const PASTE_ATTEMPTS: u32 = 3;
+/// How long to wait for the address to be pasted.
+const PASTE_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// Print the authorization page of `token` and read the resulting address with `read_input`,
+/// which is given the current page.
+///
+/// Malformed addresses are asked for again with the same page. A new page is only generated when
+/// the address belongs to a different authorization, as its state cannot be accepted.
+async fn authorize<F, Fut>(
+    token: &mut ImplicitUserTokenBuilder,
+    mut read_input: F,
+) -> Result<RedirectResponse, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: FnMut(&url::Url) -> Fut,
+    Fut: Future<Output = std::io::Result<String>>,
+{
+    let (mut url, _) = token.generate_url();
     println!("Go to this page: {url}");
+    for attempt in 1..=PASTE_ATTEMPTS {
+        let input = read_input(&url).await?;
+        let response = url::Url::parse(input.trim())
+            .map_err(Into::into)
+            .and_then(|input_url| parse_redirect_url(&input_url));
+        match response {
+            Ok(RedirectResponse::Token { ref state, .. })
+                if !percent_encoding::percent_decode_str(state)
+                    .decode_utf8()
+                    .is_ok_and(|state| token.csrf_is_valid(&state)) =>
+            {
+                (url, _) = token.generate_url();
+                eprintln!(
+                    "The adress belongs to a different authorization, go to this page: {url}"
+                );
+            }
+            Ok(response) => return Ok(response),
+            Err(error) => eprintln!(
+                "Error {error}.\nPlease paste the adress again ({attempt}/{PASTE_ATTEMPTS})."
+            ),
+        }
+    }
+    Err(format!("No valid adress was pasted after {PASTE_ATTEMPTS} attempts").into())
+}
 
-    let input = rpassword::prompt_password(
-        "Paste in the resulting adress after authenticating (input hidden): ",
-    )?;
+/// Prompt for the redirect address, failing after `PASTE_TIMEOUT`.
+async fn prompt_redirect_address() -> std::io::Result<String> {
+    let (input_sender, input_reciever) = oneshot::channel();
+    // A detached thread so a prompt left waiting does not keep the program from exiting
+    std::thread::spawn(move || {
+        let _ = input_sender.send(rpassword::prompt_password(
+            "Paste in the resulting adress after authenticating (input hidden): ",
+        ));
+    });
+    match tokio::time::timeout(PASTE_TIMEOUT, input_reciever).await {
+        Ok(Ok(input)) => input,
+        Ok(Err(_)) => Err(std::io::Error::other("The prompt closed unexpectedly")),
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!(
+                "No adress was pasted within {} minutes, create the token again when ready",
+                PASTE_TIMEOUT.as_secs() / 60
+            ),
+        )),
+    }
+}
 
-    let input_url = url::Url::parse(&input)?;
+/// The result of the authorization passed to the redirect url.
+#[derive(Debug, PartialEq, Eq)]
+enum RedirectResponse {
+    Token {
+        access_token: String,
+        state: String,
+    },
+    Error {
+        error: String,
+        error_description: String,
+    },
+}
 
-    let map: std::collections::HashMap<_, _> = input_url.fragment().map_or_else(
+/// Read the authorization result from the fragment of `redirect_url`, or the query when there is
+/// no fragment. Segments without a value are skipped.
+fn parse_redirect_url(
+    redirect_url: &url::Url,
+) -> Result<RedirectResponse, Box<dyn std::error::Error + Send + Sync>> {
+    let mut map: HashMap<String, String> = redirect_url.fragment().map_or_else(
         || {
-            input_url
+            redirect_url
                 .query_pairs()
                 .map(|cow_query| (cow_query.0.to_string(), cow_query.1.to_string()))
                 .collect()
@@ -129,38 +790,435 @@ pub async fn create_oauth_token(
         |fragment| {
             fragment
                 .split('&')
-                .map(|query| {
-                    let query_tuple = query.split_once('=').expect("Malformed url");
-                    (query_tuple.0.to_owned(), query_tuple.1.to_owned())
-                })
+                .filter_map(|query| query.split_once('='))
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
                 .collect()
         },
     );
 
-    let user_token = match (map.get("access_token"), map.get("state")) {
-        (Some(access_token), Some(state)) => {
-            let state_decoded = percent_encoding::percent_decode_str(state).decode_utf8()?;
-            token
-                .get_user_token(
-                    &client,
-                    Some(&state_decoded),
-                    Some(access_token),
-                    None,
-                    None,
-                )
-                .await?
+    if let (Some(access_token), Some(state)) = (map.remove("access_token"), map.remove("state")) {
+        return Ok(RedirectResponse::Token {
+            access_token,
+            state,
+        });
+    }
+    if let (Some(error), Some(error_description)) =
+        (map.remove("error"), map.remove("error_description"))
+    {
+        return Ok(RedirectResponse::Error {
+            error,
+            error_description,
+        });
+    }
+    Err("The adress contains neither an access token and state nor an error and description".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_expiring_in(expires_in: Duration) -> UserToken {
+        UserToken::from_existing_unchecked(
+            twitch_oauth2::AccessToken::new(String::from("abcdef")),
+            None,
+            "",
+            None,
+            "kaicenat".into(),
+            "641972806".into(),
+            Some(vec![]),
+            Some(expires_in),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn warn_before_token_expires() {
+        let start = tokio::time::Instant::now();
+
+        let short = expiry_warning(
+            &token_expiring_in(Duration::from_mins(30)),
+            EXPIRY_WARNING_THRESHOLD,
+        )
+        .await;
+        let short_elapsed = start.elapsed();
+        let long = expiry_warning(
+            &token_expiring_in(Duration::from_hours(3)),
+            EXPIRY_WARNING_THRESHOLD,
+        )
+        .await;
+        let long_elapsed = start.elapsed();
+
+        assert_eq!(Duration::ZERO, short_elapsed);
+        assert!(short <= Duration::from_mins(30));
+        assert!(short > Duration::from_mins(29));
+        assert!(long <= EXPIRY_WARNING_THRESHOLD);
+        assert!(long_elapsed > Duration::from_mins(119));
+        assert!(long_elapsed <= Duration::from_hours(2));
+    }
+
+    #[tokio::test]
+    async fn validate_configured_token_path() {
+        let directory = std::env::temp_dir().join("stream-cave-token-path");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut settings = crate::Settings::new(&directory.join("schedule"));
+        settings.token_path = Some(directory.join("secrets").join("token.json"));
+        let token_file = settings.token_file();
+        let mut token = None;
+
+        let missing = validate_oauth_token(&mut token, &token_file, false)
+            .await
+            .unwrap_err();
+        UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+            in_keyring: false,
         }
-        _ => match (map.get("error"), map.get("error_description")) {
-            (Some(error), Some(error_description)) => {
-                token
-                    .get_user_token(&client, None, None, Some(error), Some(error_description))
-                    .await?
+        .save(&token_file)
+        .unwrap();
+        let saved = UserData::from_file(&token_file).unwrap();
+        std::fs::write(&token_file, "not a token").unwrap();
+        let unreadable = validate_oauth_token(&mut token, &token_file, false)
+            .await
+            .unwrap_err();
+        let schedule_token_exists = directory.join("schedule").join("user-data.json").exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("Token does not exist", missing.to_string());
+        assert_eq!("abcdef", saved.access_token);
+        assert_ne!("Token does not exist", unreadable.to_string());
+        assert!(!schedule_token_exists);
+        assert!(token.is_none());
+    }
+
+    #[tokio::test]
+    async fn refresh_only_refreshable_tokens() {
+        use crate::cave::mock_api::MockApi;
+
+        let directory = std::env::temp_dir().join("stream-cave-token-refresh");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        let refreshable = UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: Some(String::from("ghijkl")),
+            client_id: Some(String::from("AAAA")),
+            client_secret: Some(String::from("BBBB")),
+            in_keyring: false,
+        };
+        refreshable.save(&token_file).unwrap();
+        let saved = UserData::from_file(&token_file).unwrap();
+        let implicit = UserData {
+            refresh_token: None,
+            ..UserData::from_file(&token_file).unwrap()
+        };
+        let mock = MockApi::start(|_| {
+            (
+                400,
+                String::from(r#"{"status":400,"message":"Invalid refresh token"}"#),
+            )
+        })
+        .await;
+        let client = reqwest::Client::new();
+        let token_url = mock.url("/token");
+
+        let not_refreshed = refresh_user_token(&client, &token_url, &implicit).await;
+        let failed = refresh_user_token(&client, &token_url, &saved).await;
+        let kept = refresh_saved_token(&client, &token_url, &saved, &token_file).await;
+        let unchanged = std::fs::read_to_string(&token_file).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(not_refreshed.is_none());
+        assert_eq!(Some(String::from("ghijkl")), saved.refresh_token);
+        assert_eq!(Some(String::from("BBBB")), saved.client_secret);
+        assert!(failed.unwrap().is_err());
+        assert!(kept.is_none());
+        assert_eq!(serde_json::to_string(&refreshable).unwrap(), unchanged);
+    }
+
+    #[tokio::test]
+    async fn refresh_device_code_token_without_secret() {
+        use crate::cave::mock_api::MockApi;
+
+        let directory = std::env::temp_dir().join("stream-cave-token-refresh-public");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        let device_token = UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: vec![String::from("user:read:follows")],
+            refresh_token: Some(String::from("ghijkl")),
+            client_id: Some(String::from("AAAA")),
+            client_secret: None,
+            in_keyring: false,
+        };
+        device_token.save(&token_file).unwrap();
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                String::from(
+                    r#"{"access_token":"mnopqr","expires_in":14400,"refresh_token":"stuvwx","scope":["user:read:follows"],"token_type":"bearer"}"#,
+                ),
+            )
+        })
+        .await;
+
+        let token = refresh_saved_token(
+            &reqwest::Client::new(),
+            &mock.url("/token"),
+            &device_token,
+            &token_file,
+        )
+        .await
+        .unwrap();
+        let saved = UserData::from_file(&token_file).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("mnopqr", token.access_token.secret());
+        assert_eq!("kaicenat", token.login.as_str());
+        assert_eq!(Some(String::from("stuvwx")), saved.refresh_token);
+        assert_eq!(Some(String::from("AAAA")), saved.client_id);
+        assert_eq!(None, saved.client_secret);
+        assert_eq!(vec![String::from("user:read:follows")], saved.scopes);
+        assert_eq!("mnopqr", saved.access_token);
+        assert!(mock.requests()[0].starts_with("POST /token"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn poll_until_device_code_is_authorized() {
+        use crate::cave::mock_api::MockApi;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let polls = AtomicUsize::new(0);
+        let mock = MockApi::start(move |request| {
+            if request.starts_with("POST /device") {
+                return (
+                    200,
+                    String::from(
+                        r#"{"device_code":"ABCD","expires_in":1800,"interval":5,"user_code":"WXYZ","verification_uri":"https://www.twitch.tv/activate?device-code=WXYZ"}"#,
+                    ),
+                );
             }
-            _ => panic!("invalid url passed"),
-        },
-    };
+            match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => (400, String::from(r#"{"status":400,"message":"authorization_pending"}"#)),
+                1 => (400, String::from(r#"{"status":400,"message":"slow_down"}"#)),
+                _ => (
+                    200,
+                    String::from(
+                        r#"{"access_token":"abcdef","expires_in":14400,"refresh_token":"ghijkl","scope":[],"token_type":"bearer"}"#,
+                    ),
+                ),
+            }
+        })
+        .await;
+        let start = tokio::time::Instant::now();
 
-    UserData::from_token(&user_token).save(&path.join("user-data.json"))?;
+        let token = device_code_token(
+            &reqwest::Client::new(),
+            &mock.url("/device"),
+            &mock.url("/token"),
+            "AAAA",
+            &[String::from("user:read:follows")],
+        )
+        .await
+        .unwrap();
 
-    Ok(())
+        assert_eq!("abcdef", token.access_token);
+        assert_eq!(Some(String::from("ghijkl")), token.refresh_token);
+        assert_eq!(Duration::from_secs(5 + 5 + 10), start.elapsed());
+        assert_eq!(4, mock.requests().len());
+    }
+
+    #[tokio::test]
+    async fn delete_token_even_if_revoking_fails() {
+        use crate::cave::mock_api::MockApi;
+
+        let directory = std::env::temp_dir().join("stream-cave-token-revoke");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        let user_data = UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+            in_keyring: false,
+        };
+        let mock = MockApi::start(|request| {
+            if request.starts_with("POST /revoke") {
+                (200, String::new())
+            } else {
+                (
+                    400,
+                    String::from(r#"{"status":400,"message":"Invalid token"}"#),
+                )
+            }
+        })
+        .await;
+        let client = reqwest::Client::new();
+
+        user_data.save(&token_file).unwrap();
+        delete_token(&client, &mock.url("/revoke"), "AAAA", &token_file)
+            .await
+            .unwrap();
+        let revoked_removed = !token_file.exists();
+        user_data.save(&token_file).unwrap();
+        delete_token(&client, &mock.url("/unreachable"), "AAAA", &token_file)
+            .await
+            .unwrap();
+        let failed_removed = !token_file.exists();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(revoked_removed);
+        assert!(failed_removed);
+        assert_eq!(
+            vec!["POST /revoke HTTP/1.1", "POST /unreachable HTTP/1.1"],
+            mock.requests()
+        );
+    }
+
+    #[test]
+    fn skip_valueless_fragment_segments() {
+        let url = url::Url::parse(
+            "https://iridescentsun.com/#access_token=abcdef&scope=&force_verify&state=c3ab8aa6",
+        )
+        .unwrap();
+
+        assert_eq!(
+            RedirectResponse::Token {
+                access_token: String::from("abcdef"),
+                state: String::from("c3ab8aa6"),
+            },
+            parse_redirect_url(&url).unwrap()
+        );
+    }
+
+    #[test]
+    fn report_missing_access_token() {
+        let fragment = url::Url::parse("https://iridescentsun.com/#scope=&state=c3ab8aa6").unwrap();
+        let query = url::Url::parse(
+            "https://iridescentsun.com/?error=access_denied&error_description=The+user+denied",
+        )
+        .unwrap();
+
+        assert!(parse_redirect_url(&fragment)
+            .unwrap_err()
+            .to_string()
+            .contains("neither an access token"));
+        assert_eq!(
+            RedirectResponse::Error {
+                error: String::from("access_denied"),
+                error_description: String::from("The user denied"),
+            },
+            parse_redirect_url(&query).unwrap()
+        );
+    }
+
+    #[test]
+    fn request_configured_scopes() {
+        let scopes = [String::from("user:read:follows")];
+        let mut token = token_builder("AAAA", &scopes).unwrap();
+        let mut unscoped = token_builder("AAAA", &[]).unwrap();
+
+        let (url, _) = token.generate_url();
+        let (unscoped_url, _) = unscoped.generate_url();
+        let scope = |url: &url::Url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "scope")
+                .map(|(_, value)| value.to_string())
+        };
+
+        assert_eq!(Some(String::from("user:read:follows")), scope(&url));
+        assert_eq!(None, scope(&unscoped_url));
+        assert_eq!(
+            scopes[..],
+            missing_scopes(&crate::cave::mock_api::unchecked_token(), &scopes)
+        );
+    }
+
+    #[tokio::test]
+    async fn ask_again_after_malformed_paste() {
+        let mut token = ImplicitUserTokenBuilder::new(
+            twitch_oauth2::ClientId::new(String::from("AAAA")),
+            url::Url::parse("https://iridescentsun.com").unwrap(),
+        );
+        let mut pages = Vec::new();
+        let mut inputs = vec![String::from("https://iridescentsun.com/#access_toke")].into_iter();
+
+        let response = authorize(&mut token, |page| {
+            pages.push(page.clone());
+            let input = inputs.next().unwrap_or_else(|| {
+                let state = page
+                    .query_pairs()
+                    .find(|(key, _)| key == "state")
+                    .unwrap()
+                    .1
+                    .to_string();
+                format!("https://iridescentsun.com/#access_token=abcdef&scope=&state={state}")
+            });
+            std::future::ready(Ok(input))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(2, pages.len());
+        assert_eq!(pages[0], pages[1]);
+        assert!(matches!(
+            response,
+            RedirectResponse::Token { access_token, .. } if access_token == "abcdef"
+        ));
+    }
+
+    #[tokio::test]
+    async fn stop_retrying_failed_requests() {
+        let directory = std::env::temp_dir().join("stream-cave-token-retry");
+        let _ = std::fs::remove_dir_all(&directory);
+        let token_file = directory.join("user-data.json");
+        UserData {
+            access_token: String::from("abcdef"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+            in_keyring: false,
+        }
+        .save(&token_file)
+        .unwrap();
+        let mut token = None;
+        let policy = RetryPolicy {
+            max_retries: Some(2),
+            interval: Duration::from_millis(1),
+        };
+
+        let error = tokio::time::timeout(
+            Duration::from_secs(5),
+            validate_with_client(
+                &twitch_oauth2::client::DummyClient,
+                &mut token,
+                &token_file,
+                false,
+                policy,
+            ),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            Some(&TokenError::Network { attempts: 3 }),
+            error.downcast_ref::<TokenError>()
+        );
+        assert!(token.is_none());
+    }
 }
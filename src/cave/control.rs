@@ -0,0 +1,557 @@
+use super::{tasks_handler::ActivePlayers, SharedSettings};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc::Sender,
+    task,
+};
+
+/// The file name of the control socket of the daemon.
+pub const SOCKET_NAME: &str = "control.sock";
+
+/// The event types that can be simulated with the `simulate` command.
+pub const SIMULATED_EVENTS: [&str; 3] = ["live", "rerun", "offline"];
+
+/// An event sent to the event handler by the websocket, the exit handler or `EventInjector`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketEvent {
+    /// The event type, such as `live`, `offline`, `update` or `retry`
+    pub kind: String,
+    /// The login of the streamer
+    pub name: String,
+    /// The display name of the streamer
+    pub display_name: String,
+    /// The category the streamer switched to, only set for `update` events
+    pub category: Option<String>,
+    /// Why and how often the stream was restarted, only set for `retry` events of the exit handler
+    pub retry: Option<Retry>,
+}
+
+/// Why the exit handler restarts a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryReason {
+    /// The player crashed while the stream was live
+    Crash,
+    /// The player crashed and the live status of the stream could not be checked
+    StatusUnknown,
+}
+
+/// A restart of a stream by the exit handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    pub reason: RetryReason,
+    /// The restarts of the stream within the retry window, 1 for the first restart
+    pub attempt: u32,
+}
+
+impl SocketEvent {
+    /// Create an event of type `kind` for the stream `name` without a category.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::control::SocketEvent;
+    ///
+    /// let event = SocketEvent::new("live", "kaicenat", "KaiCenat");
+    ///
+    /// assert_eq!("KaiCenat", event.display_name);
+    /// assert_eq!(None, event.category);
+    /// ```
+    #[must_use]
+    pub fn new(kind: &str, name: &str, display_name: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            category: None,
+            retry: None,
+        }
+    }
+
+    /// Create a `retry` event restarting the stream `name`.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::control::{Retry, RetryReason, SocketEvent};
+    ///
+    /// let retry = Retry { reason: RetryReason::Crash, attempt: 2 };
+    /// let event = SocketEvent::retry("kaicenat", "KaiCenat", retry);
+    ///
+    /// assert_eq!("retry", event.kind);
+    /// assert_eq!(Some(retry), event.retry);
+    /// ```
+    #[must_use]
+    pub fn retry(name: &str, display_name: &str, retry: Retry) -> Self {
+        Self {
+            retry: Some(retry),
+            ..Self::new("retry", name, display_name)
+        }
+    }
+}
+
+/// Sends simulated events to the event handler of the current connection of the daemon, as if
+/// twitch had sent them.
+#[derive(Clone, Default)]
+pub struct EventInjector {
+    sender: Arc<Mutex<Option<Sender<SocketEvent>>>>,
+}
+
+impl EventInjector {
+    /// Send the simulated events to `sender`, replacing the event handler of a previous
+    /// connection.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::control::EventInjector;
+    /// use tokio::sync::mpsc;
+    ///
+    /// let (event_sender, _event_reciever) = mpsc::channel(10);
+    /// EventInjector::default().connect(event_sender);
+    /// ```
+    pub fn connect(&self, sender: Sender<SocketEvent>) {
+        *self.sender.lock().expect("Mutex lock poisoned") = Some(sender);
+    }
+
+    /// Send an `event` of one of the `SIMULATED_EVENTS` types for the stream `name`.
+    ///
+    /// # Errors
+    /// Will return an error if the event type cannot be simulated, or no event handler is
+    /// connected.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::control::{EventInjector, SocketEvent};
+    /// use tokio::sync::mpsc;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let (event_sender, mut event_reciever) = mpsc::channel(10);
+    ///     let events = EventInjector::default();
+    ///     events.connect(event_sender);
+    ///
+    ///     events.send("live", "kaicenat").await.unwrap();
+    ///
+    ///     assert_eq!(
+    ///         Some(SocketEvent::new("live", "kaicenat", "kaicenat")),
+    ///         event_reciever.recv().await
+    ///     );
+    /// }
+    /// ```
+    pub async fn send(
+        &self,
+        event: &str,
+        name: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !SIMULATED_EVENTS.contains(&event) {
+            return Err(format!("Unable to simulate {event} events").into());
+        }
+        let sender = self.sender.lock().expect("Mutex lock poisoned").clone();
+        let Some(sender) = sender else {
+            return Err("The daemon is not connected to twitch".into());
+        };
+        sender
+            .send(SocketEvent::new(event, name, name))
+            .await
+            .map_err(|_| "The event handler stopped".into())
+    }
+}
+
+/// The path of the control socket in the `directory` of the schedule.
+///
+/// # Examples
+/// ```
+/// use std::path::{Path, PathBuf};
+/// use stream_cave::control::socket_path;
+///
+/// assert_eq!(PathBuf::from("./control.sock"), socket_path(Path::new("./")));
+/// ```
+#[must_use]
+pub fn socket_path(directory: &Path) -> PathBuf {
+    directory.join(SOCKET_NAME)
+}
+
+/// Serve the commands sent with `send_command` on the socket at `path`. A stale socket left by a
+/// previous daemon is replaced.
+///
+/// Commands are single lines:
+/// - `close <name>` closes the active player of the stream `name`
+/// - `list` replies with the streams with an active player
+/// - `clear [<name>]` removes the entry of `name`, or of all streams, without closing the players
+/// - `simulate <event> <name>` sends `events` an event for `name` as if twitch had sent it
+/// - `pause` and `resume` stop and restart playing the streams going live
+/// - `profile [<name>]` replies with the default profile of `settings`, or switches it to `name`.
+///   Players that are already playing keep their quality, the switch lasts until the config file
+///   changes
+///
+/// # Errors
+/// Will return an error if the socket cannot be created or stops accepting connections.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use std::sync::{Arc, RwLock};
+/// use stream_cave::control::{control_handler, socket_path, EventInjector};
+/// use stream_cave::tasks_handler::ActivePlayers;
+/// use stream_cave::Settings;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let active_players = ActivePlayers::default();
+///     let events = EventInjector::default();
+///     let settings = Arc::new(RwLock::new(Settings::new(Path::new("./"))));
+///
+///     control_handler(&socket_path(Path::new("./")), active_players, events, settings)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn control_handler(
+    path: &Path,
+    active_players: ActivePlayers,
+    events: EventInjector,
+    settings: SharedSettings,
+) -> std::io::Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let players = active_players.clone();
+        let events = events.clone();
+        let settings = settings.clone();
+        task::spawn(async move {
+            if let Err(error) = handle_command(stream, &players, &events, &settings).await {
+                eprintln!("Error while handling control command: {error}");
+            }
+        });
+    }
+}
+
+async fn handle_command(
+    stream: UnixStream,
+    active_players: &ActivePlayers,
+    events: &EventInjector,
+    settings: &SharedSettings,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut command = String::new();
+    BufReader::new(reader).read_line(&mut command).await?;
+
+    let reply = match command.trim().split_once(' ') {
+        Some(("close", name)) => match active_players.close(name) {
+            Ok(()) => String::from("ok"),
+            Err(error) => format!("error {error}"),
+        },
+        Some(("clear", name)) => match active_players.clear(Some(name)) {
+            Ok(names) => format!("ok {}", names.join(" ")),
+            Err(error) => format!("error {error}"),
+        },
+        Some(("simulate", event)) => {
+            let (event, name) = event.split_once(' ').unwrap_or((event, ""));
+            match events.send(event, name.trim()).await {
+                Ok(()) => String::from("ok"),
+                Err(error) => format!("error {error}"),
+            }
+        }
+        Some(("profile", name)) => match set_profile(settings, name.trim()) {
+            Ok(()) => String::from("ok"),
+            Err(error) => format!("error {error}"),
+        },
+        None if command.trim() == "profile" => {
+            let (profile, _) = settings.read().expect("RwLock poisoned").profile();
+            format!("ok {profile}")
+        }
+        None if command.trim() == "clear" => match active_players.clear(None) {
+            Ok(names) => format!("ok {}", names.join(" ")),
+            Err(error) => format!("error {error}"),
+        },
+        None if command.trim() == "pause" => {
+            if active_players.pause() {
+                String::from("ok")
+            } else {
+                String::from("error Playing streams is already paused")
+            }
+        }
+        None if command.trim() == "resume" => {
+            if active_players.resume() {
+                String::from("ok")
+            } else {
+                String::from("error Playing streams is not paused")
+            }
+        }
+        None if command.trim() == "list" => format!("ok {}", active_players.names().join(" ")),
+        _ => format!("error Unknown command: {}", command.trim()),
+    };
+    writer.write_all(format!("{reply}\n").as_bytes()).await
+}
+
+/// Play the streams launched from now on with the profile `name` of `settings`.
+fn set_profile(
+    settings: &SharedSettings,
+    name: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut settings = settings.write().expect("RwLock poisoned");
+    if settings.profiles.contains_key(name) {
+        name.clone_into(&mut settings.default_profile);
+        return Ok(());
+    }
+    let profiles: Vec<&str> = settings.profiles.keys().map(String::as_str).collect();
+    let profiles = profiles.join(", ");
+    drop(settings);
+    Err(format!("Unknown profile {name}, the profiles are {profiles}").into())
+}
+
+/// Send `command` to the daemon listening on the socket at `path` and return its reply, the
+/// names of the affected streams separated by spaces for `list` and `clear`.
+///
+/// # Errors
+/// Will return an error if the daemon is not running or the daemon rejects the command.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::control::{send_command, socket_path};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     send_command(&socket_path(Path::new("./")), "close kaicenat").await.unwrap();
+///     let active = send_command(&socket_path(Path::new("./")), "list").await.unwrap();
+/// }
+/// ```
+pub async fn send_command(
+    path: &Path,
+    command: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut stream = UnixStream::connect(path).await.map_err(|error| {
+        format!(
+            "Unable to connect to the daemon at {}: {error}",
+            path.display()
+        )
+    })?;
+    stream.write_all(format!("{command}\n").as_bytes()).await?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await?;
+
+    let reply = reply.trim();
+    if let Some(names) = reply.strip_prefix("ok") {
+        return Ok(names.trim_start().to_string());
+    }
+    Err(reply.strip_prefix("error ").unwrap_or(reply).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cave::{
+        event_handler::{event_handler, LaunchPolicy},
+        quality::Quality,
+        tasks_handler::wait_for_player,
+        Settings,
+    };
+    use std::{sync::RwLock, time::Duration};
+    use tokio::{process::Command, sync::mpsc, time::timeout};
+
+    #[tokio::test]
+    async fn close_active_stream() {
+        let directory = std::env::temp_dir().join("stream-cave-control");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = socket_path(&directory);
+        let active_players = ActivePlayers::default();
+        let close_reciever = active_players.start("kaicenat").unwrap();
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let player = task::spawn(wait_for_player(child, close_reciever, None));
+        let handler_players = active_players.clone();
+        let handler_path = path.clone();
+        let settings = Arc::new(RwLock::new(Settings::new(&directory)));
+        task::spawn(async move {
+            control_handler(
+                &handler_path,
+                handler_players,
+                EventInjector::default(),
+                settings,
+            )
+            .await
+        });
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        let missing = send_command(&path, "close jynxzi").await.unwrap_err();
+        send_command(&path, "close kaicenat").await.unwrap();
+        let result = timeout(Duration::from_secs(5), player)
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("No player is active for jynxzi", missing.to_string());
+        assert!(result.is_none());
+        assert!(!active_players.is_active("kaicenat"));
+    }
+
+    #[tokio::test]
+    async fn simulated_live_event_dispatches_task() {
+        let directory = std::env::temp_dir().join("stream-cave-control-simulate");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = socket_path(&directory);
+        let events = EventInjector::default();
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, mut task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        events.connect(socket_sender);
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            Arc::new(RwLock::new(Settings::new(&directory))),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+        let handler_path = path.clone();
+        let settings = Arc::new(RwLock::new(Settings::new(&directory)));
+        task::spawn(async move {
+            control_handler(&handler_path, ActivePlayers::default(), events, settings).await
+        });
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        let unknown = send_command(&path, "simulate update kaicenat")
+            .await
+            .unwrap_err();
+        send_command(&path, "simulate live kaicenat").await.unwrap();
+        let task = timeout(Duration::from_secs(5), task_reciever.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("Unable to simulate update events", unknown.to_string());
+        assert_eq!("kaicenat", task.name);
+        assert_eq!(Quality::Height(1080), task.quality);
+    }
+
+    #[tokio::test]
+    async fn paused_live_event_spawns_nothing() {
+        let directory = std::env::temp_dir().join("stream-cave-control-pause");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = socket_path(&directory);
+        let events = EventInjector::default();
+        let active_players = ActivePlayers::default();
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, mut task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        events.connect(socket_sender);
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            Arc::new(RwLock::new(Settings::new(&directory))),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        let handler_path = path.clone();
+        let settings = Arc::new(RwLock::new(Settings::new(&directory)));
+        task::spawn(async move {
+            control_handler(&handler_path, active_players, events, settings).await
+        });
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        send_command(&path, "pause").await.unwrap();
+        let paused_again = send_command(&path, "pause").await.unwrap_err();
+        send_command(&path, "simulate live kaicenat").await.unwrap();
+        let paused = timeout(Duration::from_millis(500), task_reciever.recv()).await;
+        send_command(&path, "resume").await.unwrap();
+        send_command(&path, "simulate live jynxzi").await.unwrap();
+        let resumed = timeout(Duration::from_secs(5), task_reciever.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            "Playing streams is already paused",
+            paused_again.to_string()
+        );
+        assert!(paused.is_err());
+        assert_eq!("jynxzi", resumed.name);
+    }
+
+    #[tokio::test]
+    async fn switched_profile_applies_to_new_streams() {
+        let directory = std::env::temp_dir().join("stream-cave-control-profile");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = socket_path(&directory);
+        let events = EventInjector::default();
+        let mut settings = Settings::new(&directory);
+        settings.profiles.insert(String::from("low-data"), 360);
+        let settings = Arc::new(RwLock::new(settings));
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, mut task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        events.connect(socket_sender);
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            settings.clone(),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+        let handler_path = path.clone();
+        let handler_settings = settings.clone();
+        task::spawn(async move {
+            control_handler(
+                &handler_path,
+                ActivePlayers::default(),
+                events,
+                handler_settings,
+            )
+            .await
+        });
+        while !path.exists() {
+            tokio::task::yield_now().await;
+        }
+
+        let before = send_command(&path, "profile").await.unwrap();
+        let unknown = send_command(&path, "profile mobile").await.unwrap_err();
+        send_command(&path, "profile low-data").await.unwrap();
+        let after = send_command(&path, "profile").await.unwrap();
+        send_command(&path, "simulate live kaicenat").await.unwrap();
+        let task = timeout(Duration::from_secs(5), task_reciever.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("normal", before);
+        assert_eq!(
+            "Unknown profile mobile, the profiles are low-data, normal",
+            unknown.to_string()
+        );
+        assert_eq!("low-data", after);
+        assert_eq!(Quality::Height(360), task.quality);
+    }
+}
@@ -1,23 +1,94 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::task::yield_now;
 use tokio::{
     sync::mpsc::{Receiver, Sender},
     task,
+    time::sleep,
+};
+
+use super::{
+    control::{Retry, RetryReason, SocketEvent},
+    player::mpv_profile_args,
+    quality::{ProfileQuality, Quality, QualitySelector},
+    tasks_handler::{self, ActivePlayers, LiveDetailsLookup, StreamTask},
+    twitch_socket::api_structs::EventType,
+    SharedSettings, StreamConfig, StreamGroup, Streams,
 };
 
-use super::StreamConfig;
+/// Delay before the second retry within the retry window, doubled for every retry after it.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const RETRY_MAX_DELAY: Duration = Duration::from_mins(2);
+
+/// How `event_handler` decides which streams are launched and at what quality.
+pub struct LaunchPolicy {
+    /// Chooses the quality of every launched stream
+    pub quality_selector: Box<dyn QualitySelector>,
+    /// The streams api used to look up the category of streams with allowed or blocked
+    /// categories
+    pub category_lookup: Option<LiveDetailsLookup>,
+    /// The groups of the schedule, a stream of an exclusive group closes the other streams of
+    /// the group when it is launched
+    pub groups: Vec<StreamGroup>,
+}
+
+impl Default for LaunchPolicy {
+    /// Play streams at the quality of their profile without looking up categories.
+    fn default() -> Self {
+        Self {
+            quality_selector: Box::new(ProfileQuality),
+            category_lookup: None,
+            groups: Vec::new(),
+        }
+    }
+}
 
 /// Start the event handling tasks.
 /// Tasks will finish when the senders are closed.
 ///
+/// Streams are played at the quality chosen by the selector of `launch_policy` for the profile of
+/// `settings` and without their own latency at the latency of `settings`, both are read for every
+/// event so reloaded settings apply to the following players.
+///
+/// `offline` and `update` events close the player of the stream in `active_players` when they
+/// match one of its close rules. Streams snoozed in `active_players` are not played, including
+/// retries, until an `offline` event of the stream.
+///
+/// Retries recieved from `exit_handler` are delayed with an increasing backoff by the attempt
+/// they carry, so a stream crashing repeatedly within a short window is not restarted in a hot
+/// loop. Retries of streams whose live status is unknown are always delayed.
+///
+/// Streams with allowed or blocked categories are only played after their current category is
+/// looked up with the category lookup of `launch_policy` and allowed. Streams whose category
+/// cannot be looked up are played.
+///
+/// Launching a stream of an exclusive group of `launch_policy` closes the players of the other
+/// streams of the group in `active_players`, and the players of the streams with it in their
+/// `streams_to_close_on`.
+///
+/// When a playing streamer switches to a category of their `category_quality_overrides` the
+/// player is reopened at the quality of the category. Other category changes are ignored.
+///
+/// Launching a stream also launches the streams with it in their `streams_to_open_on`, and the
+/// streams opened by those, as `Streams::plan` describes. Streams that are already playing are
+/// not launched again.
+///
 /// # Panics
-/// If the mutex lock is poisoned the function will panic.
+/// If the mutex or `RwLock` lock is poisoned the function will panic.
 ///
 /// # Examples
 /// ```
+/// use std::collections::BTreeMap;
+/// use std::path::Path;
+/// use std::sync::{Arc, RwLock};
 /// use tokio::sync::mpsc;
-/// use stream_cave::event_handler;
-/// use stream_cave::StreamConfig;
+/// use stream_cave::control::SocketEvent;
+/// use stream_cave::event_handler::{self, LaunchPolicy};
+/// use stream_cave::tasks_handler::ActivePlayers;
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Settings, StreamConfig};
 ///
 /// #[tokio::main]
 /// async fn main() {
@@ -28,42 +99,44 @@ use super::StreamConfig;
 ///     let (file_sender, file_reciever) = mpsc::channel(10);
 ///
 ///     let streamer = StreamConfig {
-///         name: String::from("kaicenat"),
-///         id: 641_972_806,
-///         quality_overides: vec![(String::from("normal"), 480)],
-///         streams_to_close_on: Vec::new(),
-///         streams_to_open_on: Vec::new(),
+///         quality_overides: BTreeMap::from([(String::from("normal"), Quality::Height(480))]),
+///         ..StreamConfig::new("kaicenat", 641_972_806)
 ///     };
 ///
-///         event_handler::event_handler(socket_reciever, exit_reciever, file_reciever, event_sender);
+///     let settings = Arc::new(RwLock::new(Settings::new(Path::new("./"))));
+///
+///     event_handler::event_handler(socket_reciever, exit_reciever, file_reciever, event_sender,
+///     settings, ActivePlayers::default(), LaunchPolicy::default());
 ///
 ///     file_sender.send(streamer).await.unwrap();
 ///
 ///     socket_sender
-///         .send((String::from("live"), String::from("kaicenat")))
+///         .send(SocketEvent::new("live", "kaicenat", "KaiCenat"))
 ///         .await
 ///         .unwrap();
 ///
-///     assert_eq!(
-///         Some((String::from("kaicenat"), 480)),
-///         event_reciever.recv().await
-///     );
+///     let task = event_reciever.recv().await.unwrap();
+///     assert_eq!((String::from("kaicenat"), Quality::Height(480)), (task.name, task.quality));
 /// }
 /// ```
 pub fn event_handler(
-    mut event_handler_twitch_websocket_reciever: Receiver<(String, String)>,
-    mut event_handler_exit_handler_reciever: Receiver<(String, String)>,
+    mut event_handler_twitch_websocket_reciever: Receiver<SocketEvent>,
+    mut event_handler_exit_handler_reciever: Receiver<SocketEvent>,
     mut event_handler_file_watcher_reciever: Receiver<StreamConfig>,
-    event_handler_task_spawner_sender: Sender<(String, u16)>,
+    event_handler_task_spawner_sender: Sender<StreamTask>,
+    settings: SharedSettings,
+    active_players: ActivePlayers,
+    launch_policy: LaunchPolicy,
 ) {
     let streamer_configs = Arc::new(Mutex::new(Vec::new()));
     let file_configs = streamer_configs.clone();
     let socket_configs = streamer_configs.clone();
 
     let socket_sender = event_handler_task_spawner_sender.clone();
-
-    // FIXME: get global profile from settings
-    let global_profile = ("normal", 1080);
+    let socket_settings = settings.clone();
+    let launch_policy = Arc::new(launch_policy);
+    let socket_policy = launch_policy.clone();
+    let exit_players = active_players.clone();
 
     task::spawn(async move {
         while let Some(config) = event_handler_file_watcher_reciever.recv().await {
@@ -77,9 +150,38 @@ pub fn event_handler(
 
     task::spawn(async move {
         while let Some(stream) = event_handler_twitch_websocket_reciever.recv().await {
+            if stream.kind == "offline" || stream.kind == "update" {
+                close_on_event(&socket_configs, &stream, &active_players);
+                if stream.kind == "update" {
+                    reopen_on_category(
+                        socket_configs.clone(),
+                        &stream,
+                        &socket_sender,
+                        &socket_settings,
+                        &socket_policy,
+                        &active_players,
+                    )
+                    .await;
+                }
+                if stream.kind == "offline" && active_players.unsnooze(&stream.name) {
+                    eprintln!(
+                        "{} went offline, playing it again when it goes live",
+                        stream.display_name
+                    );
+                }
+                continue;
+            }
             let sender_clone = socket_sender.clone();
             let config_clone = socket_configs.clone();
-            handle_event(config_clone, stream, sender_clone, &global_profile).await;
+            handle_event(
+                config_clone,
+                stream,
+                sender_clone,
+                &socket_settings,
+                &socket_policy,
+                &active_players,
+            )
+            .await;
         }
     });
 
@@ -87,18 +189,261 @@ pub fn event_handler(
         while let Some(stream) = event_handler_exit_handler_reciever.recv().await {
             let sender_clone = event_handler_task_spawner_sender.clone();
             let config_clone = streamer_configs.clone();
-            handle_event(config_clone, stream, sender_clone, &global_profile).await;
+            let delay = stream.retry.map_or(Duration::ZERO, retry_delay);
+            let settings_clone = settings.clone();
+            let policy_clone = launch_policy.clone();
+            let players_clone = exit_players.clone();
+            task::spawn(async move {
+                if !delay.is_zero() {
+                    if stream
+                        .retry
+                        .is_some_and(|retry| retry.reason == RetryReason::StatusUnknown)
+                    {
+                        eprintln!(
+                            "Unable to check if {} is live, retrying in {} secs",
+                            stream.display_name,
+                            delay.as_secs()
+                        );
+                    } else {
+                        eprintln!(
+                            "{} closed repeatedly, retrying in {} secs",
+                            stream.display_name,
+                            delay.as_secs()
+                        );
+                    }
+                    sleep(delay).await;
+                }
+                handle_event(
+                    config_clone,
+                    stream,
+                    sender_clone,
+                    &settings_clone,
+                    &policy_clone,
+                    &players_clone,
+                )
+                .await;
+            });
         }
     });
 }
 
+/// Close the player of the stream of `event` if the stream went offline or the event matches one
+/// of its close rules. Only the players started by the daemon are closed.
+fn close_on_event(
+    configs: &Mutex<Vec<StreamConfig>>,
+    event: &SocketEvent,
+    active_players: &ActivePlayers,
+) {
+    let close = configs
+        .lock()
+        .expect("Mutex lock poisoned")
+        .iter()
+        .find(|streamer| streamer.name == event.name)
+        .is_some_and(|config| {
+            event.kind == "offline" || config.close_when.iter().any(|rule| rule.matches(event))
+        });
+    if close && active_players.close(&event.name).is_ok() {
+        eprintln!("Closed {} after {} event", event.name, event.kind);
+    }
+}
+
+/// Reopen the player of the stream of the `update` event at the quality of the new category, if
+/// the stream is playing and has a quality for the category.
+async fn reopen_on_category(
+    configs: Arc<Mutex<Vec<StreamConfig>>>,
+    event: &SocketEvent,
+    sender: &Sender<StreamTask>,
+    settings: &SharedSettings,
+    launch_policy: &LaunchPolicy,
+    active_players: &ActivePlayers,
+) {
+    let quality = configs
+        .lock()
+        .expect("Mutex lock poisoned")
+        .iter()
+        .find(|streamer| streamer.name == event.name)
+        .and_then(|config| {
+            event
+                .category
+                .as_deref()
+                .and_then(|category| config.category_quality(category))
+        });
+    let Some(quality) = quality else {
+        return;
+    };
+    if !active_players.stop(&event.name) {
+        return;
+    }
+    eprintln!(
+        "{} switched to {}, reopening at {quality}",
+        event.display_name,
+        event.category.as_deref().unwrap_or_default()
+    );
+    let stream = SocketEvent::new("category", &event.name, &event.display_name);
+    launch_stream(
+        configs,
+        stream,
+        sender,
+        settings,
+        launch_policy,
+        active_players,
+        Some(quality),
+    )
+    .await;
+}
+
+/// Close the players of the streams sharing an exclusive group of `groups` with `name`.
+fn close_exclusive_peers(groups: &[StreamGroup], name: &str, active_players: &ActivePlayers) {
+    let peers = groups
+        .iter()
+        .filter(|group| group.exclusive && group.streams.iter().any(|stream| stream == name))
+        .flat_map(|group| &group.streams)
+        .filter(|stream| *stream != name);
+    for peer in peers {
+        if active_players.close(peer).is_ok() {
+            eprintln!("Closed {peer}, {name} of the same exclusive group is playing");
+        }
+    }
+}
+
+/// Close the players of the streams with `name` in their `streams_to_close_on`.
+fn close_on_stream(configs: &Mutex<Vec<StreamConfig>>, name: &str, active_players: &ActivePlayers) {
+    let closing: Vec<String> = configs
+        .lock()
+        .expect("Mutex lock poisoned")
+        .iter()
+        .filter(|config| {
+            config
+                .streams_to_close_on
+                .iter()
+                .any(|stream| stream == name)
+        })
+        .map(|config| config.name.clone())
+        .collect();
+    for stream in closing {
+        if active_players.close(&stream).is_ok() {
+            eprintln!("Closed {stream}, {name} is playing");
+        }
+    }
+}
+
+/// How long to wait before restarting a stream for `retry`. The first retry within the window is
+/// not delayed, unless the live status of the stream is unknown.
+fn retry_delay(retry: Retry) -> Duration {
+    let attempt = match retry.reason {
+        RetryReason::Crash => retry.attempt,
+        RetryReason::StatusUnknown => retry.attempt.saturating_add(1),
+    };
+    if attempt <= 1 {
+        Duration::ZERO
+    } else {
+        RETRY_BASE_DELAY
+            .saturating_mul(2_u32.saturating_pow(attempt - 2))
+            .min(RETRY_MAX_DELAY)
+    }
+}
+
 async fn handle_event(
     configs: Arc<Mutex<Vec<StreamConfig>>>,
-    stream: (String, String),
-    sender: Sender<(String, u16)>,
-    global_profile: &(&str, u16),
+    stream: SocketEvent,
+    sender: Sender<StreamTask>,
+    settings: &SharedSettings,
+    launch_policy: &LaunchPolicy,
+    active_players: &ActivePlayers,
 ) {
-    let mut stream_quality = global_profile.1;
+    let name = stream.name.clone();
+    if !launch_stream(
+        configs.clone(),
+        stream,
+        &sender,
+        settings,
+        launch_policy,
+        active_players,
+        None,
+    )
+    .await
+    {
+        return;
+    }
+
+    let streams = Streams {
+        streams: configs.lock().expect("Mutex lock poisoned").clone(),
+        ..Streams::new()
+    };
+    for dependent in streams.plan(&name).opens {
+        if active_players.is_active(&dependent) {
+            continue;
+        }
+        eprintln!("Opening {dependent}, {name} is playing");
+        let event = SocketEvent::new("open", &dependent, &dependent);
+        launch_stream(
+            configs.clone(),
+            event,
+            &sender,
+            settings,
+            launch_policy,
+            active_players,
+            None,
+        )
+        .await;
+    }
+}
+
+/// Check if the stream of `config` is played in the category it is currently streaming, streams
+/// whose category cannot be looked up are played.
+async fn allows_current_category(
+    config: &StreamConfig,
+    stream: &SocketEvent,
+    stream_id: u32,
+    launch_policy: &LaunchPolicy,
+) -> bool {
+    let category = match &launch_policy.category_lookup {
+        Some(lookup) => tasks_handler::current_category(&stream.name, stream_id, lookup).await,
+        None => None,
+    };
+    match category {
+        Some(category) if !config.allows_category(&category) => {
+            eprintln!(
+                "Not playing {}, they are streaming {category}",
+                stream.display_name
+            );
+            false
+        }
+        Some(_) => true,
+        None => {
+            eprintln!(
+                "Unable to look up the category of {}, playing it anyway",
+                stream.display_name
+            );
+            true
+        }
+    }
+}
+
+/// Send the task of `stream` to `sender` unless the stream is not launched on the event, returns
+/// if it was sent. `open` events of dependent streams and `category` events of reopened players
+/// are launched like retries, `quality` replaces the quality of the profile.
+async fn launch_stream(
+    configs: Arc<Mutex<Vec<StreamConfig>>>,
+    stream: SocketEvent,
+    sender: &Sender<StreamTask>,
+    settings: &SharedSettings,
+    launch_policy: &LaunchPolicy,
+    active_players: &ActivePlayers,
+    quality: Option<Quality>,
+) -> bool {
+    let (global_profile, mut latency, player) = {
+        let settings = settings.read().expect("RwLock poisoned");
+        (settings.profile(), settings.latency, settings.player)
+    };
+    let mut stream_quality = None;
+    let mut stream_id = 0;
+    let mut player_args = Vec::new();
+    let mut stream_player = None;
+    let mut record = false;
+    let mut launch_on = vec![EventType::Live];
+    let mut resume = false;
+    let mut category_filter = None;
 
     yield_now().await;
     if let Some(config) = configs
@@ -106,28 +451,98 @@ async fn handle_event(
         .as_ref()
         .expect("Mutex lock poisoned")
         .iter()
-        .find(|streamer| streamer.name == stream.1)
+        .find(|streamer| streamer.name == stream.name)
     {
-        let global_quality_overrides = &config.quality_overides;
-        if let Some(current_profile_override) = global_quality_overrides
-            .iter()
-            .find(|(profile, _)| *profile == global_profile.0)
-        {
-            stream_quality = current_profile_override.1;
+        stream_id = config.id;
+        if !config.launch_on.is_empty() {
+            launch_on.clone_from(&config.launch_on);
+        }
+        stream_player = config.player;
+        record = config.record;
+        player_args = mpv_profile_args(
+            config.player.unwrap_or(player),
+            config.mpv_profile.as_deref(),
+            config.mpv_config_dir.as_deref(),
+        );
+        player_args.extend_from_slice(&config.player_args);
+        resume = config.resume;
+        if let Some(stream_latency) = config.latency {
+            latency = stream_latency;
         }
+        stream_quality = Some(launch_policy.quality_selector.select(
+            &stream.name,
+            Some(config),
+            &global_profile,
+        ));
+        category_filter = config.filters_categories().then(|| config.clone());
     }
+    let stream_quality = quality.or(stream_quality).unwrap_or_else(|| {
+        launch_policy
+            .quality_selector
+            .select(&stream.name, None, &global_profile)
+    });
 
-    let task = (stream.1.clone(), stream_quality);
+    if stream.kind != "retry"
+        && stream.kind != "open"
+        && stream.kind != "category"
+        && !launch_on.iter().any(|event| event.as_str() == stream.kind)
+    {
+        return false;
+    }
+    if active_players.is_paused() {
+        eprintln!(
+            "Not playing {}, playing streams is paused",
+            stream.display_name
+        );
+        return false;
+    }
+    if active_players.is_snoozed(&stream.name) {
+        eprintln!(
+            "Not playing {}, it was closed and has not gone offline since",
+            stream.display_name
+        );
+        return false;
+    }
+    if let Some(config) = category_filter {
+        if !allows_current_category(&config, &stream, stream_id, launch_policy).await {
+            return false;
+        }
+    }
+
+    close_exclusive_peers(&launch_policy.groups, &stream.name, active_players);
+    close_on_stream(&configs, &stream.name, active_players);
+    let task = StreamTask {
+        name: stream.name,
+        display_name: stream.display_name,
+        id: stream_id,
+        quality: stream_quality,
+        player_args,
+        player: stream_player,
+        record,
+        resume,
+        latency,
+    };
     sender
         .send(task)
         .await
         .expect("Task spawner reciever closed");
+    true
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::{sync::mpsc, task};
+    use crate::cave::{quality::Quality, Latency, Settings};
+    use std::{collections::BTreeMap, path::Path, sync::RwLock};
+    use tokio::{
+        sync::mpsc,
+        task,
+        time::{timeout, Instant},
+    };
+
+    fn default_settings() -> SharedSettings {
+        Arc::new(RwLock::new(Settings::new(Path::new("./"))))
+    }
 
     #[tokio::test]
     async fn no_config_event_handling() {
@@ -137,16 +552,34 @@ mod tests {
         let (_, file_reciever) = mpsc::channel(10);
 
         task::spawn(async {
-            event_handler(socket_reciever, exit_reciever, file_reciever, event_sender);
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
         });
 
         socket_sender
-            .send((String::from("live"), String::from("kaicenat")))
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
             .await
             .unwrap();
 
         assert_eq!(
-            Some((String::from("kaicenat"), 1080)),
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 0,
+                quality: Quality::Height(1080),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
             event_reciever.recv().await
         );
     }
@@ -159,26 +592,41 @@ mod tests {
         let (file_sender, file_reciever) = mpsc::channel(10);
 
         let streamer = StreamConfig {
-            name: String::from("kaicenat"),
-            id: 641_972_806,
-            quality_overides: vec![(String::from("normal"), 480)],
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([(String::from("normal"), Quality::Height(480))]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
         };
 
         task::spawn(async {
-            event_handler(socket_reciever, exit_reciever, file_reciever, event_sender);
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
         });
 
         file_sender.send(streamer).await.unwrap();
 
         socket_sender
-            .send((String::from("live"), String::from("kaicenat")))
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
             .await
             .unwrap();
 
         assert_eq!(
-            Some((String::from("kaicenat"), 480)),
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 641_972_806,
+                quality: Quality::Height(480),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
             event_reciever.recv().await
         );
     }
@@ -191,25 +639,40 @@ mod tests {
         let (file_sender, file_reciever) = mpsc::channel(10);
 
         let streamer = StreamConfig {
-            name: String::from("kaicenat"),
-            id: 641_972_806,
-            quality_overides: vec![(String::from("normal"), 480)],
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([(String::from("normal"), Quality::Height(480))]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
         };
 
         task::spawn(async {
-            event_handler(socket_reciever, exit_reciever, file_reciever, event_sender);
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
         });
 
         file_sender.send(streamer).await.unwrap();
         exit_sender
-            .send((String::from("live"), String::from("kaicenat")))
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
             .await
             .unwrap();
 
         assert_eq!(
-            Some((String::from("kaicenat"), 480)),
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 641_972_806,
+                quality: Quality::Height(480),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
             event_reciever.recv().await
         );
     }
@@ -221,28 +684,717 @@ mod tests {
         let (_, exit_reciever) = mpsc::channel(10);
         let (file_sender, file_reciever) = mpsc::channel(10);
 
+        let streamer = StreamConfig::new("kaicenat", 641_972_806);
+
+        task::spawn(async {
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
+        });
+
+        file_sender.send(streamer).await.unwrap();
+
+        socket_sender
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 641_972_806,
+                quality: Quality::Height(1080),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
+            event_reciever.recv().await
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_override_lookup() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+
         let streamer = StreamConfig {
-            name: String::from("kaicenat"),
-            id: 641_972_806,
-            quality_overides: Vec::new(),
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([
+                (String::from("low-data"), Quality::Height(0)),
+                (String::from("normal"), Quality::Height(720)),
+            ]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
         };
 
         task::spawn(async {
-            event_handler(socket_reciever, exit_reciever, file_reciever, event_sender);
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
         });
 
         file_sender.send(streamer).await.unwrap();
 
         socket_sender
-            .send((String::from("live"), String::from("kaicenat")))
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 641_972_806,
+                quality: Quality::Height(720),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
+            event_reciever.recv().await
+        );
+    }
+
+    #[tokio::test]
+    async fn reloaded_profile_sets_quality() {
+        let directory = std::env::temp_dir().join("stream-cave-reload");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let config_file = directory.join("config.json");
+        let mut config = Settings::new(&directory);
+        std::fs::write(&config_file, serde_json::to_string(&config).unwrap()).unwrap();
+        let settings = Arc::new(RwLock::new(Settings::new(&directory)));
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let streamer = StreamConfig {
+            quality_overides: BTreeMap::from([
+                (String::from("normal"), Quality::Height(720)),
+                (String::from("mobile"), Quality::Height(360)),
+            ]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
+        };
+        let live = SocketEvent::new("live", "kaicenat", "kaicenat");
+        task::spawn(crate::cave::file_watcher::config_watcher(
+            config_file.clone(),
+            settings.clone(),
+            Duration::from_millis(10),
+        ));
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            settings.clone(),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+        file_sender.send(streamer).await.unwrap();
+
+        socket_sender.send(live.clone()).await.unwrap();
+        let before = event_reciever.recv().await.unwrap();
+        config.profiles.insert(String::from("mobile"), 480);
+        config.default_profile = String::from("mobile");
+        std::fs::write(&config_file, serde_json::to_string(&config).unwrap()).unwrap();
+        while settings.read().unwrap().default_profile != "mobile" {
+            sleep(Duration::from_millis(10)).await;
+        }
+        socket_sender.send(live).await.unwrap();
+        let after = event_reciever.recv().await.unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(Quality::Height(720), before.quality);
+        assert_eq!(Quality::Height(360), after.quality);
+    }
+
+    struct AudioOnly;
+
+    impl QualitySelector for AudioOnly {
+        fn select(&self, _: &str, _: Option<&StreamConfig>, _: &(String, u16)) -> Quality {
+            Quality::Height(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_quality_selector() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let streamer = StreamConfig {
+            quality_overides: BTreeMap::from([(String::from("normal"), Quality::Height(720))]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
+        };
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            ActivePlayers::default(),
+            LaunchPolicy {
+                quality_selector: Box::new(AudioOnly),
+                ..LaunchPolicy::default()
+            },
+        );
+        file_sender.send(streamer).await.unwrap();
+        while file_sender.capacity() < file_sender.max_capacity() {
+            yield_now().await;
+        }
+        for name in ["kaicenat", "jynxzi"] {
+            socket_sender
+                .send(SocketEvent::new("live", name, name))
+                .await
+                .unwrap();
+        }
+        let configured = event_reciever.recv().await.unwrap();
+        let unconfigured = event_reciever.recv().await.unwrap();
+
+        assert_eq!(
+            ("kaicenat", Quality::Height(0)),
+            (configured.name.as_str(), configured.quality)
+        );
+        assert_eq!(
+            ("jynxzi", Quality::Height(0)),
+            (unconfigured.name.as_str(), unconfigured.quality)
+        );
+    }
+
+    #[tokio::test]
+    async fn launch_only_in_allowed_categories() {
+        use crate::cave::mock_api::{helix_response, stream_data, unchecked_token, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let login = if request.contains("user_id=641972806") {
+                "kaicenat"
+            } else {
+                "jynxzi"
+            };
+            (200, helix_response(&[stream_data(login, 1)]))
+        })
+        .await;
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let mut allowed = StreamConfig::new("kaicenat", 641_972_806);
+        allowed.categories_allow.push(String::from("just chatting"));
+        let mut blocked = StreamConfig::new("jynxzi", 411_377_640);
+        blocked.categories_block.push(String::from("Just Chatting"));
+        let live = |name: &str| SocketEvent::new("live", name, name);
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            ActivePlayers::default(),
+            LaunchPolicy {
+                category_lookup: Some(LiveDetailsLookup {
+                    streams_api_url: mock.url("/streams"),
+                    user_access_token: Arc::new(Some(unchecked_token())),
+                    client_id: String::from("AAAA"),
+                }),
+                ..LaunchPolicy::default()
+            },
+        );
+        file_sender.send(allowed).await.unwrap();
+        file_sender.send(blocked).await.unwrap();
+        while file_sender.capacity() < file_sender.max_capacity() {
+            yield_now().await;
+        }
+
+        socket_sender.send(live("jynxzi")).await.unwrap();
+        socket_sender.send(live("kaicenat")).await.unwrap();
+        let task = event_reciever.recv().await.unwrap();
+        drop(socket_sender);
+
+        assert_eq!("kaicenat", task.name);
+        assert!(event_reciever.recv().await.is_none());
+        assert_eq!(2, mock.requests().len());
+    }
+
+    #[tokio::test]
+    async fn exclusive_group_closes_other_streams() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let first = active_players.start("kaicenat").unwrap();
+        let _other = active_players.start("caseoh_").unwrap();
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy {
+                groups: vec![
+                    StreamGroup {
+                        name: String::from("shooters"),
+                        streams: vec![String::from("kaicenat"), String::from("jynxzi")],
+                        exclusive: true,
+                    },
+                    StreamGroup {
+                        name: String::from("variety"),
+                        streams: vec![String::from("jynxzi"), String::from("caseoh_")],
+                        exclusive: false,
+                    },
+                ],
+                ..LaunchPolicy::default()
+            },
+        );
+        socket_sender
+            .send(SocketEvent::new("live", "jynxzi", "Jynxzi"))
+            .await
+            .unwrap();
+        let task = event_reciever.recv().await.unwrap();
+
+        assert_eq!("jynxzi", task.name);
+        assert_eq!(Ok(()), first.await);
+        assert!(!active_players.is_active("kaicenat"));
+        assert!(active_players.is_active("caseoh_"));
+    }
+
+    #[tokio::test]
+    async fn snoozed_stream_waits_for_offline() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (exit_sender, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let _close_reciever = active_players.start("kaicenat").unwrap();
+        active_players.close("kaicenat").unwrap();
+        let event = |kind: &str| SocketEvent::new(kind, "kaicenat", "KaiCenat");
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        exit_sender.send(event("retry")).await.unwrap();
+        socket_sender.send(event("live")).await.unwrap();
+        let snoozed = timeout(Duration::from_millis(200), event_reciever.recv()).await;
+        socket_sender.send(event("offline")).await.unwrap();
+        socket_sender.send(event("live")).await.unwrap();
+        let task = event_reciever.recv().await.unwrap();
+
+        assert!(snoozed.is_err());
+        assert_eq!("kaicenat", task.name);
+        assert!(!active_players.is_snoozed("kaicenat"));
+    }
+
+    #[tokio::test]
+    async fn offline_event_closes_player() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, _event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let kaicenat_close = active_players.start("kaicenat").unwrap();
+        let _jynxzi_close = active_players.start("jynxzi").unwrap();
+        let streams = crate::Streams::read_streams(Path::new("./tests/resources"));
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        for config in streams.streams {
+            file_sender.send(config).await.unwrap();
+        }
+        while file_sender.capacity() < file_sender.max_capacity() {
+            task::yield_now().await;
+        }
+        socket_sender
+            .send(SocketEvent::new("offline", "kaicenat", "KaiCenat"))
+            .await
+            .unwrap();
+        timeout(Duration::from_secs(5), kaicenat_close)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(!active_players.is_active("kaicenat"));
+        assert!(active_players.is_active("jynxzi"));
+    }
+
+    #[tokio::test]
+    async fn reopen_at_category_quality() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let kaicenat_close = active_players.start("kaicenat").unwrap();
+        let _jynxzi_close = active_players.start("jynxzi").unwrap();
+        let mut streams = crate::Streams::read_streams(Path::new("./tests/resources"));
+        for config in &mut streams.streams {
+            config
+                .category_quality_overrides
+                .insert(String::from("Just Chatting"), Quality::Height(360));
+        }
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        for config in streams.streams {
+            file_sender.send(config).await.unwrap();
+        }
+        while file_sender.capacity() < file_sender.max_capacity() {
+            task::yield_now().await;
+        }
+        for (name, category) in [
+            ("jynxzi", "Rainbow Six Siege"),
+            ("kaicenat", "just chatting"),
+        ] {
+            socket_sender
+                .send(SocketEvent {
+                    category: Some(category.to_string()),
+                    ..SocketEvent::new("update", name, name)
+                })
+                .await
+                .unwrap();
+        }
+        timeout(Duration::from_secs(5), kaicenat_close)
+            .await
+            .unwrap()
+            .unwrap();
+        let task = timeout(Duration::from_secs(5), event_reciever.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            ("kaicenat", Quality::Height(360)),
+            (&*task.name, task.quality)
+        );
+        assert!(!active_players.is_snoozed("kaicenat"));
+        assert!(active_players.is_active("jynxzi"));
+        assert!(event_reciever.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn open_dependent_streams_once() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let _hasanabi_close = active_players.start("hasanabi").unwrap();
+        let mut streams = crate::Streams::read_streams(Path::new("./tests/resources"));
+        // Opening each other does not open them again
+        for (name, trigger) in [
+            ("jynxzi", "kaicenat"),
+            ("kaicenat", "jynxzi"),
+            ("hasanabi", "kaicenat"),
+        ] {
+            streams
+                .get_mut(name)
+                .unwrap()
+                .streams_to_open_on
+                .push(trigger.to_string());
+        }
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        for config in streams.streams {
+            file_sender.send(config).await.unwrap();
+        }
+        while file_sender.capacity() < file_sender.max_capacity() {
+            task::yield_now().await;
+        }
+        socket_sender
+            .send(SocketEvent::new("live", "kaicenat", "KaiCenat"))
+            .await
+            .unwrap();
+        let live = event_reciever.recv().await.unwrap();
+        let opened = event_reciever.recv().await.unwrap();
+        let more = timeout(Duration::from_millis(200), event_reciever.recv()).await;
+
+        assert_eq!("kaicenat", live.name);
+        assert_eq!("jynxzi", opened.name);
+        assert!(more.is_err());
+    }
+
+    #[tokio::test]
+    async fn live_stream_closes_background_streams() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        let jynxzi_close = active_players.start("jynxzi").unwrap();
+        let mut streams = crate::Streams::read_streams(Path::new("./tests/resources"));
+        for name in ["jynxzi", "hasanabi"] {
+            streams
+                .get_mut(name)
+                .unwrap()
+                .streams_to_close_on
+                .push(String::from("kaicenat"));
+        }
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            active_players.clone(),
+            LaunchPolicy::default(),
+        );
+        for config in streams.streams {
+            file_sender.send(config).await.unwrap();
+        }
+        while file_sender.capacity() < file_sender.max_capacity() {
+            task::yield_now().await;
+        }
+        socket_sender
+            .send(SocketEvent::new("live", "kaicenat", "KaiCenat"))
+            .await
+            .unwrap();
+        let task = event_reciever.recv().await.unwrap();
+        timeout(Duration::from_secs(5), jynxzi_close)
             .await
+            .unwrap()
             .unwrap();
 
+        assert_eq!("kaicenat", task.name);
+        assert!(!active_players.is_active("jynxzi"));
+        assert!(active_players.is_snoozed("jynxzi"));
+        // Not playing, so it was not closed
+        assert!(!active_players.is_snoozed("hasanabi"));
+    }
+
+    #[tokio::test]
+    async fn rerun_launches_only_when_enabled() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+
+        let default_streamer = StreamConfig::new("jynxzi", 411_377_640);
+        let rerun_streamer = StreamConfig {
+            launch_on: vec![EventType::Live, EventType::Rerun],
+            ..StreamConfig::new("kaicenat", 641_972_806)
+        };
+
+        task::spawn(async {
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
+        });
+
+        file_sender.send(default_streamer).await.unwrap();
+        file_sender.send(rerun_streamer).await.unwrap();
+
+        for name in ["jynxzi", "kaicenat"] {
+            socket_sender
+                .send(SocketEvent::new("rerun", name, name))
+                .await
+                .unwrap();
+        }
+
         assert_eq!(
-            Some((String::from("kaicenat"), 1080)),
+            Some(StreamTask {
+                name: String::from("kaicenat"),
+                display_name: String::from("kaicenat"),
+                id: 641_972_806,
+                quality: Quality::Height(1080),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            }),
             event_reciever.recv().await
         );
+        assert!(event_reciever.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn mpv_profile_added_to_player_args() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+
+        let streamer = StreamConfig {
+            player_args: vec![String::from("--volume=50")],
+            mpv_profile: Some(String::from("music")),
+            ..StreamConfig::new("lofigirl", 0)
+        };
+
+        task::spawn(async {
+            event_handler(
+                socket_reciever,
+                exit_reciever,
+                file_reciever,
+                event_sender,
+                default_settings(),
+                ActivePlayers::default(),
+                LaunchPolicy::default(),
+            );
+        });
+
+        file_sender.send(streamer).await.unwrap();
+        socket_sender
+            .send(SocketEvent::new("live", "lofigirl", "Lofi Girl"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            vec![String::from("--profile=music"), String::from("--volume=50")],
+            event_reciever.recv().await.unwrap().player_args
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_player_replaces_settings_player() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let mut streams = crate::Streams::read_streams(Path::new("./tests/resources"));
+        let mut kaicenat = streams.streams.remove(0);
+        kaicenat.player = Some(crate::Player::Streamlink);
+        kaicenat.mpv_profile = Some(String::from("music"));
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+        file_sender.send(kaicenat).await.unwrap();
+        file_sender.send(streams.streams.remove(0)).await.unwrap();
+        for name in ["kaicenat", "hasanabi"] {
+            socket_sender
+                .send(SocketEvent::new("live", name, name))
+                .await
+                .unwrap();
+        }
+        let kaicenat = event_reciever.recv().await.unwrap();
+        let hasanabi = event_reciever.recv().await.unwrap();
+
+        assert_eq!(Some(crate::Player::Streamlink), kaicenat.player);
+        assert!(kaicenat.player_args.is_empty());
+        assert_eq!(None, hasanabi.player);
+    }
+
+    #[test]
+    fn retry_delay_increases_with_attempts() {
+        let crash = |attempt| Retry {
+            reason: RetryReason::Crash,
+            attempt,
+        };
+
+        let delays: Vec<Duration> = (1..=4).map(|attempt| retry_delay(crash(attempt))).collect();
+
+        assert_eq!(
+            vec![
+                Duration::ZERO,
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(8)
+            ],
+            delays
+        );
+        assert_eq!(RETRY_MAX_DELAY, retry_delay(crash(20)));
+        assert_eq!(
+            Duration::from_secs(2),
+            retry_delay(Retry {
+                reason: RetryReason::StatusUnknown,
+                attempt: 1,
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn repeated_retries_are_delayed() {
+        let (_, socket_reciever) = mpsc::channel(10);
+        let (event_sender, mut event_reciever) = mpsc::channel(10);
+        let (exit_sender, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            event_sender,
+            default_settings(),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+
+        let mut delays = Vec::new();
+        for attempt in 1..=3 {
+            let start = Instant::now();
+            let retry = Retry {
+                reason: RetryReason::Crash,
+                attempt,
+            };
+            exit_sender
+                .send(SocketEvent::retry("kaicenat", "kaicenat", retry))
+                .await
+                .unwrap();
+            event_reciever.recv().await.unwrap();
+            delays.push(start.elapsed());
+        }
+
+        assert!(delays[0] < delays[1]);
+        assert!(delays[1] < delays[2]);
     }
 }
@@ -0,0 +1,224 @@
+use super::{events::StreamEvent, notifier::Notifier};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The number of records kept by the daemon.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// What kind of event was recorded
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Live,
+    Offline,
+    Retry,
+    Update,
+    Closed,
+    /// The websocket connection was restarted
+    Reconnect,
+    /// The user token was rejected or is missing
+    AuthError,
+    /// The user token expires soon and has to be created again
+    Reauthenticate,
+    /// The websocket could not be reconnected within the configured attempts
+    Unreachable,
+    /// A test notification was sent
+    Test,
+}
+
+/// An event that happened in the daemon.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub kind: RecordKind,
+    pub message: String,
+}
+
+impl EventRecord {
+    /// Create a record of an event happening now.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::event_log::{EventRecord, RecordKind};
+    ///
+    /// let record = EventRecord::new(RecordKind::Reconnect, String::from("Websocket timed out"));
+    /// assert_eq!(RecordKind::Reconnect, record.kind);
+    /// ```
+    #[must_use]
+    pub fn new(kind: RecordKind, message: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        Self {
+            timestamp,
+            kind,
+            message,
+        }
+    }
+}
+
+impl From<&StreamEvent> for EventRecord {
+    fn from(event: &StreamEvent) -> Self {
+        let kind = match event {
+            StreamEvent::Live { .. } => RecordKind::Live,
+            StreamEvent::Offline { .. } => RecordKind::Offline,
+            StreamEvent::Retry { .. } => RecordKind::Retry,
+            StreamEvent::Update { .. } => RecordKind::Update,
+            StreamEvent::Closed { .. } => RecordKind::Closed,
+            StreamEvent::Test => RecordKind::Test,
+        };
+        Self::new(kind, event.to_string())
+    }
+}
+
+impl std::fmt::Display for EventRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {:?}: {}", self.timestamp, self.kind, self.message)
+    }
+}
+
+/// The most recent events of the daemon, stored one json record per line in a file.
+pub struct EventLog {
+    path: PathBuf,
+    limit: usize,
+    lock: Mutex<()>,
+}
+
+impl EventLog {
+    /// Create a log stored in `path` keeping the last `limit` records.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::event_log::{EventLog, DEFAULT_LIMIT};
+    ///
+    /// let log = EventLog::new(Path::new("./events.log"), DEFAULT_LIMIT);
+    /// ```
+    #[must_use]
+    pub fn new(path: &Path, limit: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            limit,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Add `record` to the log, dropping the oldest records over the limit.
+    ///
+    /// # Errors
+    /// Will return an error if the record cannot be serialized or the log cannot be written.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::event_log::{EventLog, EventRecord, RecordKind, DEFAULT_LIMIT};
+    ///
+    /// let log = EventLog::new(Path::new("./events.log"), DEFAULT_LIMIT);
+    /// log.record(&EventRecord::new(RecordKind::AuthError, String::from("Token expired")))
+    ///     .unwrap();
+    /// ```
+    pub fn record(
+        &self,
+        record: &EventRecord,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _guard = self.lock.lock().expect("Mutex lock poisoned");
+        let existing = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let mut lines: Vec<String> = existing.lines().map(str::to_string).collect();
+        lines.push(serde_json::to_string(record)?);
+        let start = lines.len().saturating_sub(self.limit);
+
+        let mut data = lines[start..].join("\n");
+        data.push('\n');
+        std::fs::write(&self.path, data)?;
+        Ok(())
+    }
+
+    /// Read the last `count` records of the log at `path`, newest first. Lines that cannot be
+    /// read are skipped.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::event_log::EventLog;
+    ///
+    /// for record in EventLog::tail(Path::new("./events.log"), 20) {
+    ///     println!("{record}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn tail(path: &Path, count: usize) -> Vec<EventRecord> {
+        std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .rev()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .take(count)
+            .collect()
+    }
+}
+
+/// A notifier recording every stream event to an `EventLog`.
+pub struct EventLogNotifier {
+    pub log: Arc<EventLog>,
+}
+
+#[async_trait]
+impl Notifier for EventLogNotifier {
+    async fn notify(
+        &self,
+        event: &StreamEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.log
+            .record(&EventRecord::from(event))
+            .map_err(|error| format!("Unable to record event: {error}").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cave::quality::Quality;
+
+    #[tokio::test]
+    async fn tail_respects_limit() {
+        let path = std::env::temp_dir().join("stream-cave-events.log");
+        let _ = std::fs::remove_file(&path);
+        let log = Arc::new(EventLog::new(&path, 3));
+        let notifier = EventLogNotifier { log: log.clone() };
+
+        for name in ["kaicenat", "jynxzi", "caseoh_"] {
+            notifier
+                .notify(&StreamEvent::Live {
+                    name: name.to_string(),
+                    login: name.to_string(),
+                    quality: Quality::Height(1080),
+                    details: None,
+                })
+                .await
+                .unwrap();
+        }
+        log.record(&EventRecord::new(
+            RecordKind::Reconnect,
+            String::from("Signal timeout"),
+        ))
+        .unwrap();
+        let all = EventLog::tail(&path, 10);
+        let newest = EventLog::tail(&path, 2);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(3, all.len());
+        assert_eq!(RecordKind::Reconnect, all[0].kind);
+        assert_eq!("caseoh_ is live, playing at 1080p", all[1].message);
+        assert_eq!("jynxzi is live, playing at 1080p", all[2].message);
+        assert_eq!(all[..2], newest[..]);
+    }
+}
@@ -0,0 +1,506 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    sync::mpsc::{self, Receiver, Sender},
+    task,
+};
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
+use twitch_oauth2::UserToken;
+
+use super::{
+    control::SocketEvent,
+    event_handler, file_watcher,
+    quality::Quality,
+    tasks_handler::{LiveDetailsLookup, StreamTask},
+    twitch_socket, SharedSettings, StreamConfig, Streams,
+};
+
+/// An event in the life of a monitored stream, `name` is the display name of the streamer and
+/// `login` its login name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A stream went live and should be played at `quality`
+    Live {
+        name: String,
+        login: String,
+        quality: Quality,
+        /// The viewer count and start time, only looked up when enabled
+        details: Option<LiveDetails>,
+    },
+    /// A stream went offline
+    Offline { name: String, login: String },
+    /// A stream is being restarted after its player closed while it was still live
+    Retry { name: String, login: String },
+    /// The information of a live stream changed
+    Update { name: String, login: String },
+    /// The player of a stream was closed
+    Closed {
+        name: String,
+        login: String,
+        success: bool,
+    },
+    /// A sample event to check that the notifiers work
+    Test,
+}
+
+/// Information about a stream that went live from the streams api.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveDetails {
+    pub viewer_count: u32,
+    /// When the stream started as an RFC 3339 timestamp
+    pub started_at: String,
+}
+
+impl StreamEvent {
+    /// The display name of the stream of the event, `None` for test events.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::events::StreamEvent;
+    ///
+    /// let event = StreamEvent::Offline {
+    ///     name: String::from("Jynxzi"),
+    ///     login: String::from("jynxzi"),
+    /// };
+    ///
+    /// assert_eq!(Some("Jynxzi"), event.name());
+    /// assert_eq!(None, StreamEvent::Test.name());
+    /// ```
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Live { name, .. }
+            | Self::Offline { name, .. }
+            | Self::Retry { name, .. }
+            | Self::Update { name, .. }
+            | Self::Closed { name, .. } => Some(name),
+            Self::Test => None,
+        }
+    }
+
+    /// The login name of the stream of the event, `None` for test events.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::events::StreamEvent;
+    ///
+    /// let event = StreamEvent::Offline {
+    ///     name: String::from("Jynxzi"),
+    ///     login: String::from("jynxzi"),
+    /// };
+    ///
+    /// assert_eq!(Some("jynxzi"), event.login());
+    /// assert_eq!(None, StreamEvent::Test.login());
+    /// ```
+    #[must_use]
+    pub fn login(&self) -> Option<&str> {
+        match self {
+            Self::Live { login, .. }
+            | Self::Offline { login, .. }
+            | Self::Retry { login, .. }
+            | Self::Update { login, .. }
+            | Self::Closed { login, .. } => Some(login),
+            Self::Test => None,
+        }
+    }
+}
+
+impl std::fmt::Display for StreamEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Live {
+                name,
+                quality,
+                details,
+                ..
+            } => {
+                if *quality == Quality::Height(0) {
+                    write!(f, "{name} is live, playing audio only")?;
+                } else {
+                    write!(f, "{name} is live, playing at {quality}")?;
+                }
+                if let Some(details) = details {
+                    write!(
+                        f,
+                        " ({} viewers, live since {})",
+                        details.viewer_count, details.started_at
+                    )?;
+                }
+                Ok(())
+            }
+            Self::Offline { name, .. } => write!(f, "{name} went offline"),
+            Self::Retry { name, .. } => write!(f, "{name} is still live, restarting player"),
+            Self::Update { name, .. } => write!(f, "{name} updated their stream"),
+            Self::Closed {
+                name,
+                success: true,
+                ..
+            } => write!(f, "{name}'s player was closed"),
+            Self::Closed {
+                name,
+                success: false,
+                ..
+            } => write!(f, "{name}'s player exited with an error"),
+            Self::Test => write!(f, "Test notification from stream-cave"),
+        }
+    }
+}
+
+/// Turn the streams to play sent by `event_handler` into a stream of live events.
+///
+/// # Examples
+/// ```
+/// use stream_cave::events::{task_events, StreamEvent};
+/// use stream_cave::tasks_handler::StreamTask;
+/// use stream_cave::Latency;
+/// use stream_cave::quality::Quality;
+/// use tokio::sync::mpsc;
+/// use tokio_stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (task_sender, task_reciever) = mpsc::channel(5);
+///     let mut events = task_events(task_reciever);
+///
+///     task_sender
+///         .send(StreamTask {
+///             name: String::from("jynxzi"),
+///             display_name: String::from("jynxzi"),
+///             id: 411_377_640,
+///             quality: Quality::Height(720),
+///             player_args: Vec::new(),
+///             player: None,
+///             record: false,
+///             resume: false,
+///             latency: Latency::Normal,
+///         })
+///         .await
+///         .unwrap();
+///
+///     assert_eq!(
+///         Some(StreamEvent::Live {
+///             name: String::from("jynxzi"),
+///             login: String::from("jynxzi"),
+///             quality: Quality::Height(720),
+///             details: None,
+///         }),
+///         events.next().await
+///     );
+/// }
+/// ```
+pub fn task_events(
+    task_events_event_handler_reciever: Receiver<StreamTask>,
+) -> impl Stream<Item = StreamEvent> {
+    ReceiverStream::new(task_events_event_handler_reciever).map(|stream_task| StreamEvent::Live {
+        name: stream_task.display_name,
+        login: stream_task.name,
+        quality: stream_task.quality,
+        details: None,
+    })
+}
+
+/// The event of a message sent to `event_handler` by the websocket or the exit handler, `None`
+/// for the messages turned into events by `task_events`.
+///
+/// # Examples
+/// ```
+/// use stream_cave::control::SocketEvent;
+/// use stream_cave::events::{message_event, StreamEvent};
+///
+/// let message = SocketEvent::new("offline", "jynxzi", "Jynxzi");
+///
+/// assert_eq!(Some(StreamEvent::Offline { name: String::from("Jynxzi"), login: String::from("jynxzi") }), message_event(&message));
+/// ```
+#[must_use]
+pub fn message_event(message: &SocketEvent) -> Option<StreamEvent> {
+    match message.kind.as_str() {
+        "offline" => Some(StreamEvent::Offline {
+            name: message.display_name.clone(),
+            login: message.name.clone(),
+        }),
+        "retry" => Some(StreamEvent::Retry {
+            name: message.display_name.clone(),
+            login: message.name.clone(),
+        }),
+        "update" => Some(StreamEvent::Update {
+            name: message.display_name.clone(),
+            login: message.name.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// Forward the messages of `reciever` to the returned reciever, sending the events of the
+/// messages to `event_sender`. Stops when either reciever is dropped.
+fn tap_messages(
+    mut reciever: Receiver<SocketEvent>,
+    event_sender: Sender<StreamEvent>,
+) -> Receiver<SocketEvent> {
+    let (message_sender, message_reciever) = mpsc::channel(10);
+    task::spawn(async move {
+        while let Some(message) = reciever.recv().await {
+            if let Some(event) = message_event(&message) {
+                if event_sender.send(event).await.is_err() {
+                    return;
+                }
+            }
+            if message_sender.send(message).await.is_err() {
+                return;
+            }
+        }
+    });
+    message_reciever
+}
+
+/// Run `event_handler` on the given channels without starting any players and return a stream
+/// of the events: the streams it plays as live events, and the offline, update and retry
+/// messages sent to it.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use std::sync::{Arc, RwLock};
+/// use stream_cave::control::SocketEvent;
+/// use stream_cave::events::{event_stream, StreamEvent};
+/// use stream_cave::event_handler::LaunchPolicy;
+/// use stream_cave::Settings;
+/// use tokio::sync::mpsc;
+/// use tokio_stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (socket_sender, socket_reciever) = mpsc::channel(5);
+///     let (_exit_sender, exit_reciever) = mpsc::channel(5);
+///     let (_file_sender, file_reciever) = mpsc::channel(5);
+///     let settings = Arc::new(RwLock::new(Settings::new(Path::new("./"))));
+///     let events = event_stream(socket_reciever, exit_reciever, file_reciever, settings, LaunchPolicy::default());
+///     tokio::pin!(events);
+///
+///     socket_sender
+///         .send(SocketEvent::new("offline", "jynxzi", "Jynxzi"))
+///         .await
+///         .unwrap();
+///
+///     assert_eq!(Some(StreamEvent::Offline { name: String::from("Jynxzi"), login: String::from("jynxzi") }), events.next().await);
+/// }
+/// ```
+pub fn event_stream(
+    event_handler_twitch_websocket_reciever: Receiver<SocketEvent>,
+    event_handler_exit_handler_reciever: Receiver<SocketEvent>,
+    event_handler_file_watcher_reciever: Receiver<StreamConfig>,
+    settings: SharedSettings,
+    launch_policy: event_handler::LaunchPolicy,
+) -> impl Stream<Item = StreamEvent> {
+    let (event_sender, event_reciever) = mpsc::channel(10);
+    let (event_handler_task_events_sender, task_events_event_handler_reciever) = mpsc::channel(10);
+
+    let websocket_messages = tap_messages(
+        event_handler_twitch_websocket_reciever,
+        event_sender.clone(),
+    );
+    let exit_messages = tap_messages(event_handler_exit_handler_reciever, event_sender);
+    event_handler::event_handler(
+        websocket_messages,
+        exit_messages,
+        event_handler_file_watcher_reciever,
+        event_handler_task_events_sender,
+        settings,
+        super::tasks_handler::ActivePlayers::default(),
+        launch_policy,
+    );
+
+    task_events(task_events_event_handler_reciever).merge(ReceiverStream::new(event_reciever))
+}
+
+/// Monitor the streams in `streams` and return a stream of their events without starting any
+/// players, along with the reciever of the restart signals sent on websocket and token errors.
+///
+/// The schedule directory and api endpoints are read from `settings`. Retries are only sent by
+/// an exit handler, callers running their own players pass the reciever of its messages as
+/// `exit_handler_reciever`.
+///
+/// # Panics
+/// If the `RwLock` is poisoned the function will panic.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use std::sync::{Arc, Mutex, RwLock};
+/// use stream_cave::{authentication, events, Settings, Streams};
+/// use tokio::sync::mpsc;
+/// use tokio_stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     const TWITCH_WEBSOCKET_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+///     const TWITCH_API_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+///     const CLIENT_ID: &str = "AAAAAAAAA";
+///     let path = Path::new("./");
+///     let mut token = None;
+///     authentication::validate_oauth_token(&mut token, &path.join("user-data.json"), false).await.unwrap();
+///     let streams = Arc::new(Mutex::new(Streams::read_streams(&path)));
+///     let settings = Arc::new(RwLock::new(Settings::read_config(&[path.to_path_buf()]).unwrap()));
+///     let (_exit_sender, exit_reciever) = mpsc::channel(5);
+///
+///     let (events, _restart_signal_reciever) = events::subscribe(
+///         settings,
+///         streams,
+///         TWITCH_WEBSOCKET_URL,
+///         TWITCH_API_URL,
+///         Arc::new(token),
+///         CLIENT_ID,
+///         exit_reciever,
+///     );
+///     tokio::pin!(events);
+///     while let Some(event) = events.next().await {
+///         println!("{event}");
+///     }
+/// }
+/// ```
+pub fn subscribe(
+    settings: SharedSettings,
+    streams: Arc<Mutex<Streams>>,
+    twitch_websocket_url: &'static str,
+    twitch_api_url: &'static str,
+    user_access_token: Arc<Option<UserToken>>,
+    client_id: &'static str,
+    exit_handler_reciever: Receiver<SocketEvent>,
+) -> (impl Stream<Item = StreamEvent>, Receiver<u8>) {
+    let (file_watcher_twitch_websocket_sender, twitch_socket_file_watcher_reciever) =
+        mpsc::channel(10);
+    let (file_watcher_event_handler_sender, event_handler_file_watcher_reciever) =
+        mpsc::channel(10);
+    let (twitch_websocket_event_handler_sender, event_handler_twitch_websocket_reciever) =
+        mpsc::channel(10);
+    let (restart_signal_sender, restart_signal_reciever) = mpsc::channel(1);
+    let (schedule_path, streams_api_url) = {
+        let settings = settings.read().expect("RwLock poisoned");
+        (settings.schedule.clone(), settings.endpoints.streams())
+    };
+    let category_lookup = LiveDetailsLookup {
+        streams_api_url,
+        user_access_token: user_access_token.clone(),
+        client_id: client_id.to_string(),
+    };
+
+    task::spawn(async move {
+        file_watcher::file_watcher(
+            file_watcher_twitch_websocket_sender,
+            file_watcher_event_handler_sender,
+            &file_watcher::ScheduleSource::Directory(schedule_path),
+            &streams,
+        )
+        .await;
+    });
+    twitch_socket::twitch_websocket(
+        twitch_socket_file_watcher_reciever,
+        twitch_websocket_event_handler_sender,
+        restart_signal_sender,
+        twitch_websocket_url.to_string(),
+        twitch_api_url.to_string(),
+        user_access_token,
+        client_id,
+        None,
+        twitch_socket::SubscriptionCost::default(),
+        tokio::sync::broadcast::channel(1).1,
+    );
+    let events = event_stream(
+        event_handler_twitch_websocket_reciever,
+        exit_handler_reciever,
+        event_handler_file_watcher_reciever,
+        settings,
+        event_handler::LaunchPolicy {
+            category_lookup: Some(category_lookup),
+            ..event_handler::LaunchPolicy::default()
+        },
+    );
+
+    (events, restart_signal_reciever)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::RwLock;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn live_event_on_stream() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+
+        event_handler::event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            Arc::new(RwLock::new(crate::Settings::new(std::path::Path::new(
+                "./",
+            )))),
+            crate::cave::tasks_handler::ActivePlayers::default(),
+            crate::event_handler::LaunchPolicy::default(),
+        );
+        let mut events = task_events(task_reciever);
+
+        socket_sender
+            .send(SocketEvent::new("live", "kaicenat", "kaicenat"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Some(StreamEvent::Live {
+                name: String::from("kaicenat"),
+                login: String::from("kaicenat"),
+                quality: Quality::Height(1080),
+                details: None,
+            }),
+            events.next().await
+        );
+    }
+
+    #[tokio::test]
+    async fn offline_and_retry_events_on_stream() {
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (exit_sender, exit_reciever) = mpsc::channel(10);
+        let (_file_sender, file_reciever) = mpsc::channel(10);
+        let settings = crate::Settings::new(std::path::Path::new("./"));
+        let events = event_stream(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            Arc::new(RwLock::new(settings)),
+            crate::event_handler::LaunchPolicy::default(),
+        );
+        tokio::pin!(events);
+
+        socket_sender
+            .send(SocketEvent::new("offline", "kaicenat", "KaiCenat"))
+            .await
+            .unwrap();
+        let offline = events.next().await;
+        exit_sender
+            .send(SocketEvent::new("retry", "jynxzi", "Jynxzi"))
+            .await
+            .unwrap();
+        let retry = events.next().await;
+        let restarted = events.next().await;
+
+        assert_eq!(
+            Some(StreamEvent::Offline {
+                name: String::from("KaiCenat"),
+                login: String::from("kaicenat"),
+            }),
+            offline
+        );
+        assert_eq!(
+            Some(StreamEvent::Retry {
+                name: String::from("Jynxzi"),
+                login: String::from("jynxzi"),
+            }),
+            retry
+        );
+        assert!(matches!(restarted, Some(StreamEvent::Live { name, .. }) if name == "Jynxzi"));
+    }
+}
@@ -1,24 +1,95 @@
-use crate::cave::Streams;
+use crate::cave::{Settings, SharedSettings, Streams};
+use notify::{EventKind, RecursiveMode, Watcher};
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use tokio::sync::mpsc::Sender;
+use tokio::{
+    sync::mpsc::{self, Sender},
+    time::{sleep, timeout},
+};
 
 use super::StreamConfig;
 
+/// How often the daemon checks the config file for changes.
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long the schedule file has to stay unchanged before a change is read.
+pub const SCHEDULE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Where the schedule of the daemon is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleSource {
+    /// The schedule.json of the directory, with the files it includes
+    Directory(PathBuf),
+    /// A single schedule file, its includes are not read
+    File(PathBuf),
+    /// The schedule piped to stdin, it is only read once
+    Stdin,
+}
+
+impl ScheduleSource {
+    /// The file checked for changes, `None` for stdin.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::PathBuf;
+    /// use stream_cave::file_watcher::ScheduleSource;
+    ///
+    /// let directory = ScheduleSource::Directory(PathBuf::from("./"));
+    ///
+    /// assert_eq!(Some(PathBuf::from("./schedule.json")), directory.watched_file());
+    /// assert_eq!(None, ScheduleSource::Stdin.watched_file());
+    /// ```
+    #[must_use]
+    pub fn watched_file(&self) -> Option<PathBuf> {
+        match self {
+            Self::Directory(directory) => Some(directory.join("schedule.json")),
+            Self::File(file) => Some(file.clone()),
+            Self::Stdin => None,
+        }
+    }
+
+    /// Read the schedule again. The schedule of a directory is read while holding its
+    /// `Streams::lock`, so it is not read while the cli writes it.
+    fn reload(&self) -> Result<Streams, Box<dyn std::error::Error + Send + Sync>> {
+        match self {
+            Self::Directory(directory) => {
+                let lock = Streams::lock(directory)
+                    .inspect_err(|error| eprintln!("Error while locking schedule: {error}"))
+                    .ok();
+                let streams = Streams::read_streams_with_includes(directory);
+                drop(lock);
+                streams
+            }
+            Self::File(file) => {
+                let file = std::fs::File::open(file)?;
+                Ok(Streams::from_reader(std::io::BufReader::new(file))?)
+            }
+            Self::Stdin => Err("The schedule read from stdin cannot be read again".into()),
+        }
+    }
+}
+
 /// Watch the stream configurations file for changes, read stream configurations from `streams` and
 /// send both the existing and new configurations to `event_handler` and `twitch_websocket`
 ///
+/// The directory of the watched file of `schedule` is watched with the `notify` crate. A changed
+/// file is only read once it stays the same for `SCHEDULE_DEBOUNCE`, and only the streams not yet
+/// in `streams` are sent and added to it. The `notify` flag of existing streams is updated in
+/// `streams`, other changes to existing streams need a restart. A schedule read from stdin is not
+/// watched. Finishes when the `event_handler` stops.
+///
 /// # Panics
 /// If the Mutex lock is poison the function will panic.
 ///
 /// # Examples
-/// ```
-/// use stream_cave::{Settings, Streams, file_watcher};
+/// ```no_run
+/// use stream_cave::{Settings, Streams, file_watcher::{self, ScheduleSource}};
 /// use tokio::sync::mpsc;
-/// use std::path::Path;
+/// use std::path::PathBuf;
 /// use std::sync::{Arc, Mutex};
 ///
 /// #[tokio::main]
@@ -26,22 +97,39 @@ use super::StreamConfig;
 ///     let streams = Arc::new(Mutex::new(Streams::new()));
 ///     let (tx1, rx1) = mpsc::channel(5);
 ///     let (tx2, rx2) = mpsc::channel(5);
-///     let streams_path = Path::new("./");
-///     
-///     file_watcher::file_watcher(tx1, tx2, &streams_path, &streams).await;
+///     let schedule = ScheduleSource::Directory(PathBuf::from("./"));
+///
+///     file_watcher::file_watcher(tx1, tx2, &schedule, &streams).await;
 /// }
 /// ```
 pub async fn file_watcher(
-    file_watcher_twitch_websocket_sender: Sender<u32>,
+    file_watcher_twitch_websocket_sender: Sender<(u32, Vec<&'static str>)>,
     file_watcher_event_handler_sender: Sender<StreamConfig>,
-    _streams_path: &Path,
+    schedule: &ScheduleSource,
     streams: &Arc<Mutex<Streams>>,
 ) {
+    let schedule_file = schedule.watched_file();
+    // Watch and read the schedule before sending the streams so no later change is missed
+    let (change_sender, mut change_reciever) = mpsc::channel(10);
+    let watcher = schedule_file.as_deref().and_then(|file| {
+        watch_file(file, change_sender)
+            .inspect_err(|error| {
+                eprintln!(
+                    "Error watching {}, changes need a restart: {error}",
+                    file.display()
+                );
+            })
+            .ok()
+    });
+    let mut current = schedule_file
+        .as_ref()
+        .and_then(|file| std::fs::read_to_string(file).ok());
+
     let streams_clone = streams.lock().expect("Mutex lock poisoned").clone();
     let streamers = streams_clone.streams.into_iter();
     for streamer in streamers {
         file_watcher_twitch_websocket_sender
-            .send(streamer.id)
+            .send((streamer.id, streamer.subscription_types()))
             .await
             .expect("Twitch websocket reciever is closed");
         file_watcher_event_handler_sender
@@ -49,12 +137,159 @@ pub async fn file_watcher(
             .await
             .expect("Event handler reciever is closed");
     }
-    // TODO: Add functionality to watch schedule file and send changes
+
+    let (Some(schedule_file), Some(_watcher)) = (schedule_file, watcher) else {
+        file_watcher_event_handler_sender.closed().await;
+        return;
+    };
+    loop {
+        let change = tokio::select! {
+            change = change_reciever.recv() => change,
+            () = file_watcher_event_handler_sender.closed() => None,
+        };
+        if change.is_none() {
+            return;
+        }
+        // Editors can write the file in several steps, wait until it stops changing
+        while timeout(SCHEDULE_DEBOUNCE, change_reciever.recv())
+            .await
+            .is_ok_and(|change| change.is_some())
+        {}
+        let Ok(contents) = std::fs::read_to_string(&schedule_file) else {
+            continue;
+        };
+        if current.as_ref() == Some(&contents) {
+            continue;
+        }
+        current = Some(contents);
+
+        let reloaded = match schedule.reload() {
+            Ok(reloaded) => reloaded,
+            Err(error) => {
+                eprintln!("Error reading changed schedule, keeping the current streams: {error}");
+                continue;
+            }
+        };
+        let added: Vec<StreamConfig> = {
+            let mut streams = streams.lock().expect("Mutex lock poisoned");
+            for existing in &mut streams.streams {
+                if let Some(reloaded) = reloaded.get(&existing.name) {
+                    existing.notify = reloaded.notify;
+                }
+            }
+            let added: Vec<StreamConfig> = reloaded
+                .streams
+                .into_iter()
+                .filter(|streamer| streams.get(&streamer.name).is_none())
+                .collect();
+            streams.streams.extend(added.iter().cloned());
+            added
+        };
+        for streamer in added {
+            eprintln!("Adding {} from the changed schedule", streamer.name);
+            if file_watcher_twitch_websocket_sender
+                .send((streamer.id, streamer.subscription_types()))
+                .await
+                .is_err()
+                || file_watcher_event_handler_sender
+                    .send(streamer)
+                    .await
+                    .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Watch the directory of `file` for changes to it, sending them to `change_sender`. The changes
+/// are sent while the returned watcher is kept.
+fn watch_file(
+    file: &Path,
+    change_sender: Sender<()>,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let file_name = file.file_name().map(std::ffi::OsStr::to_os_string);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        // Reading the file is an access and not a change
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == file_name.as_deref())
+        {
+            // A full channel already has a change waiting to be read
+            let _ = change_sender.try_send(());
+        }
+    })?;
+    let directory = match file.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+/// Check `config_file` for changes every `interval` and apply the changed settings to
+/// `settings` with `Settings::reload`. Files that cannot be read keep the current settings.
+///
+/// # Panics
+/// If the `RwLock` is poisoned the function will panic.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::{Path, PathBuf};
+/// use std::sync::{Arc, RwLock};
+/// use stream_cave::file_watcher::{config_watcher, CONFIG_POLL_INTERVAL};
+/// use stream_cave::Settings;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let settings = Arc::new(RwLock::new(Settings::new(Path::new("./"))));
+///
+///     config_watcher(PathBuf::from("./config.json"), settings, CONFIG_POLL_INTERVAL).await;
+/// }
+/// ```
+pub async fn config_watcher(config_file: PathBuf, settings: SharedSettings, interval: Duration) {
+    let mut current = std::fs::read_to_string(&config_file).ok();
+    loop {
+        sleep(interval).await;
+        let Ok(config) = std::fs::read_to_string(&config_file) else {
+            continue;
+        };
+        if current.as_ref() == Some(&config) {
+            continue;
+        }
+        let reloaded = Settings::from_reader(config.as_bytes());
+        current = Some(config);
+        let reloaded = match reloaded {
+            Ok(reloaded) => reloaded,
+            Err(error) => {
+                eprintln!("Error deserializing changed config file, keeping the current settings: {error}");
+                continue;
+            }
+        };
+        let restart_needed = settings.write().expect("RwLock poisoned").reload(reloaded);
+        if restart_needed.is_empty() {
+            eprintln!("Reloaded {}", config_file.display());
+        } else {
+            eprintln!(
+                "Reloaded {}, restart the daemon to apply the changed {}",
+                config_file.display(),
+                restart_needed.join(", ")
+            );
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::cave::StreamConfig;
+    use crate::cave::{quality::Quality, StreamConfig};
+    use std::collections::BTreeMap;
 
     use super::*;
 
@@ -65,41 +300,150 @@ mod test {
         let json_streams = std::fs::read_to_string("./tests/resources/schedule.json").unwrap();
         let streams: Streams = serde_json::from_str(&json_streams).unwrap();
         let streams = Arc::new(Mutex::new(streams));
-        let path = std::path::Path::new("./tests/resources");
+        let schedule = ScheduleSource::Directory(PathBuf::from("./tests/resources"));
 
         let kai = StreamConfig {
-            name: String::from("kaicenat"),
-            id: 641_972_806,
-            quality_overides: vec![(String::from("normal"), 480), (String::from("low-data"), 0)],
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([
+                (String::from("normal"), Quality::Height(480)),
+                (String::from("low-data"), Quality::Height(0)),
+            ]),
+            ..StreamConfig::new("kaicenat", 641_972_806)
         };
         let hasan = StreamConfig {
-            name: String::from("hasanabi"),
-            id: 207_813_352,
-            quality_overides: vec![(String::from("normal"), 480), (String::from("low-data"), 0)],
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([
+                (String::from("normal"), Quality::Height(480)),
+                (String::from("low-data"), Quality::Height(0)),
+            ]),
+            ..StreamConfig::new("hasanabi", 207_813_352)
         };
         let jynxzi = StreamConfig {
-            name: String::from("jynxzi"),
-            id: 411_377_640,
-            quality_overides: vec![(String::from("normal"), 480), (String::from("low-data"), 0)],
-            streams_to_close_on: Vec::new(),
-            streams_to_open_on: Vec::new(),
+            quality_overides: BTreeMap::from([
+                (String::from("normal"), Quality::Height(480)),
+                (String::from("low-data"), Quality::Height(0)),
+            ]),
+            ..StreamConfig::new("jynxzi", 411_377_640)
         };
 
         let (id_sender, mut id_reciever) = mpsc::channel(5);
         let (config_sender, mut config_reciever) = mpsc::channel(5);
 
-        file_watcher(id_sender, config_sender, path, &streams).await;
-        assert_eq!(id_reciever.recv().await, Some(641_972_806));
-        assert_eq!(id_reciever.recv().await, Some(207_813_352));
-        assert_eq!(id_reciever.recv().await, Some(411_377_640));
-        assert_eq!(id_reciever.recv().await, None);
+        tokio::task::spawn(async move {
+            file_watcher(id_sender, config_sender, &schedule, &streams).await;
+        });
+        assert_eq!(
+            id_reciever.recv().await,
+            Some((641_972_806, vec!["stream.online", "stream.offline"]))
+        );
+        assert_eq!(
+            id_reciever.recv().await,
+            Some((207_813_352, vec!["stream.online", "stream.offline"]))
+        );
+        assert_eq!(
+            id_reciever.recv().await,
+            Some((411_377_640, vec!["stream.online", "stream.offline"]))
+        );
+        assert!(id_reciever.try_recv().is_err());
         assert_eq!(config_reciever.recv().await, Some(kai));
         assert_eq!(config_reciever.recv().await, Some(hasan));
         assert_eq!(config_reciever.recv().await, Some(jynxzi));
-        assert_eq!(config_reciever.recv().await, None);
+        assert!(config_reciever.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn send_only_added_streams() {
+        use tokio::{sync::mpsc, time::Instant};
+
+        let directory = std::env::temp_dir().join("stream-cave-schedule-watcher");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut schedule = Streams::read_streams(std::path::Path::new("./tests/resources"));
+        let jynxzi = schedule.streams.pop().unwrap();
+        schedule.streams.truncate(1);
+        std::fs::write(
+            directory.join("schedule.json"),
+            serde_json::to_string(&schedule).unwrap(),
+        )
+        .unwrap();
+        let streams = Arc::new(Mutex::new(Streams::read_streams(&directory)));
+        let (id_sender, mut id_reciever) = mpsc::channel(5);
+        let (config_sender, mut config_reciever) = mpsc::channel(5);
+
+        let watched_streams = streams.clone();
+        let watched_directory = ScheduleSource::Directory(directory.clone());
+        tokio::task::spawn(async move {
+            file_watcher(
+                id_sender,
+                config_sender,
+                &watched_directory,
+                &watched_streams,
+            )
+            .await;
+        });
+        assert_eq!("kaicenat", config_reciever.recv().await.unwrap().name);
+        id_reciever.recv().await.unwrap();
+
+        let start = Instant::now();
+        schedule.streams[0].notify = false;
+        schedule.streams.push(jynxzi);
+        std::fs::write(
+            directory.join("schedule.json"),
+            serde_json::to_string(&schedule).unwrap(),
+        )
+        .unwrap();
+        let added = config_reciever.recv().await.unwrap();
+        let elapsed = start.elapsed();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("jynxzi", added.name);
+        assert!(elapsed >= SCHEDULE_DEBOUNCE);
+        assert_eq!(
+            Some((411_377_640, vec!["stream.online", "stream.offline"])),
+            id_reciever.recv().await
+        );
+        assert!(config_reciever.try_recv().is_err());
+        assert_eq!(2, streams.lock().unwrap().streams.len());
+        assert!(!streams.lock().unwrap().get("kaicenat").unwrap().notify);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch_only_the_given_schedule_file() {
+        use tokio::sync::mpsc;
+
+        let directory = std::env::temp_dir().join("stream-cave-schedule-file-watcher");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut schedule = Streams::read_streams(std::path::Path::new("./tests/resources"));
+        let jynxzi = schedule.streams.pop().unwrap();
+        let hasan = schedule.streams.pop().unwrap();
+        let schedule_file = directory.join("custom.json");
+        std::fs::write(&schedule_file, serde_json::to_string(&schedule).unwrap()).unwrap();
+        let streams = Arc::new(Mutex::new(schedule.clone()));
+        let (id_sender, mut id_reciever) = mpsc::channel(5);
+        let (config_sender, mut config_reciever) = mpsc::channel(5);
+
+        let watched_streams = streams.clone();
+        let source = ScheduleSource::File(schedule_file.clone());
+        tokio::task::spawn(async move {
+            file_watcher(id_sender, config_sender, &source, &watched_streams).await;
+        });
+        assert_eq!("kaicenat", config_reciever.recv().await.unwrap().name);
+        id_reciever.recv().await.unwrap();
+
+        let mut ignored = schedule.clone();
+        ignored.streams.push(hasan);
+        std::fs::write(
+            directory.join("schedule.json"),
+            serde_json::to_string(&ignored).unwrap(),
+        )
+        .unwrap();
+        schedule.streams.push(jynxzi);
+        std::fs::write(&schedule_file, serde_json::to_string(&schedule).unwrap()).unwrap();
+        let added = config_reciever.recv().await.unwrap();
+        sleep(SCHEDULE_DEBOUNCE * 3).await;
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("jynxzi", added.name);
+        assert!(config_reciever.try_recv().is_err());
+        assert_eq!(2, streams.lock().unwrap().streams.len());
     }
 }
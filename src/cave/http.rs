@@ -0,0 +1,238 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{sync::OnceLock, time::Duration};
+
+/// The user agent sent with every request unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str = concat!("stream-cave/", env!("CARGO_PKG_VERSION"));
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+static USER_AGENT: OnceLock<String> = OnceLock::new();
+
+/// Timeouts applied to every request made to the Twitch API.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    /// Seconds to wait for a connection to be established
+    pub connect_secs: u64,
+    /// Seconds to wait for the whole request to finish
+    pub total_secs: u64,
+}
+
+impl Default for RequestTimeouts {
+    fn default() -> Self {
+        Self {
+            connect_secs: 15,
+            total_secs: 30,
+        }
+    }
+}
+
+/// The urls of the Twitch services, overridable to use a mock such as `twitch-cli` or a mirror.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct TwitchEndpoints {
+    /// The base url of the Helix api, without a trailing slash
+    pub api_base: String,
+    /// The url of the `EventSub` websocket
+    pub websocket: String,
+    /// The website streams are played from, with a trailing slash
+    pub website: String,
+}
+
+impl Default for TwitchEndpoints {
+    fn default() -> Self {
+        Self {
+            api_base: String::from("https://api.twitch.tv/helix"),
+            websocket: String::from("wss://eventsub.wss.twitch.tv/ws"),
+            website: String::from("https://www.twitch.tv/"),
+        }
+    }
+}
+
+impl TwitchEndpoints {
+    /// The url of the search channels endpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::http::TwitchEndpoints;
+    ///
+    /// let endpoints = TwitchEndpoints {
+    ///     api_base: String::from("http://localhost:8080/mock"),
+    ///     ..TwitchEndpoints::default()
+    /// };
+    ///
+    /// assert_eq!("http://localhost:8080/mock/search/channels", endpoints.search_channels());
+    /// ```
+    #[must_use]
+    pub fn search_channels(&self) -> String {
+        format!("{}/search/channels", self.api_base)
+    }
+
+    /// The url of the streams endpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::http::TwitchEndpoints;
+    ///
+    /// assert_eq!("https://api.twitch.tv/helix/streams", TwitchEndpoints::default().streams());
+    /// ```
+    #[must_use]
+    pub fn streams(&self) -> String {
+        format!("{}/streams", self.api_base)
+    }
+
+    /// The url of the `EventSub` subscriptions endpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::http::TwitchEndpoints;
+    ///
+    /// assert_eq!(
+    ///     "https://api.twitch.tv/helix/eventsub/subscriptions",
+    ///     TwitchEndpoints::default().subscriptions()
+    /// );
+    /// ```
+    #[must_use]
+    pub fn subscriptions(&self) -> String {
+        format!("{}/eventsub/subscriptions", self.api_base)
+    }
+
+    /// The url of the schedule endpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::http::TwitchEndpoints;
+    ///
+    /// assert_eq!("https://api.twitch.tv/helix/schedule", TwitchEndpoints::default().schedule());
+    /// ```
+    #[must_use]
+    pub fn schedule(&self) -> String {
+        format!("{}/schedule", self.api_base)
+    }
+}
+
+/// Build a client using the given timeouts sending `user_agent` with every request. Redirects are
+/// not followed as required by the twitch oauth2 token requests.
+///
+/// # Errors
+/// Will return an error if the TLS backend cannot be initialized.
+///
+/// # Examples
+/// ```
+/// use stream_cave::http::{build_client, RequestTimeouts, DEFAULT_USER_AGENT};
+///
+/// let client = build_client(RequestTimeouts::default(), DEFAULT_USER_AGENT).unwrap();
+/// ```
+pub fn build_client(
+    timeouts: RequestTimeouts,
+    user_agent: &str,
+) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(Duration::from_secs(timeouts.connect_secs))
+        .timeout(Duration::from_secs(timeouts.total_secs))
+        .build()
+}
+
+/// Set the timeouts and the user agent of the shared client. Returns false if the shared client
+/// was already created, in which case nothing is changed.
+///
+/// `DEFAULT_USER_AGENT` is used when `user_agent` is `None`.
+///
+/// # Examples
+/// ```
+/// use stream_cave::http::{init, user_agent, RequestTimeouts};
+///
+/// assert!(init(RequestTimeouts::default(), Some(String::from("my-cave/1.0"))));
+/// assert!(!init(RequestTimeouts::default(), None));
+/// assert_eq!("my-cave/1.0", user_agent());
+/// ```
+#[must_use]
+pub fn init(timeouts: RequestTimeouts, user_agent: Option<String>) -> bool {
+    let mut initialized = false;
+    CLIENT.get_or_init(|| {
+        initialized = true;
+        if let Some(user_agent) = user_agent {
+            let _ = USER_AGENT.set(user_agent);
+        }
+        create_client(timeouts)
+    });
+    initialized
+}
+
+/// Get the client shared by all requests, created with the default timeouts if `init` was not
+/// called.
+pub fn client() -> reqwest::Client {
+    CLIENT
+        .get_or_init(|| create_client(RequestTimeouts::default()))
+        .clone()
+}
+
+/// The user agent sent by the shared client and the websocket connection.
+pub fn user_agent() -> &'static str {
+    USER_AGENT.get_or_init(|| DEFAULT_USER_AGENT.to_string())
+}
+
+fn create_client(timeouts: RequestTimeouts) -> reqwest::Client {
+    build_client(timeouts, user_agent()).unwrap_or_else(|error| {
+        eprintln!("Error creating http client: {error}, using client without timeouts");
+        reqwest::Client::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{io::AsyncReadExt, net::TcpListener, time::Instant};
+
+    #[tokio::test]
+    async fn slow_response_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_socket, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_mins(1)).await;
+        });
+
+        let client = build_client(
+            RequestTimeouts {
+                connect_secs: 1,
+                total_secs: 1,
+            },
+            DEFAULT_USER_AGENT,
+        )
+        .unwrap();
+        let start = Instant::now();
+        let error = client
+            .get(format!("http://{address}/"))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert!(error.is_timeout());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn send_user_agent() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 8192];
+            let read = socket.read(&mut buffer).await.unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_lowercase()
+        });
+
+        let client = build_client(RequestTimeouts::default(), DEFAULT_USER_AGENT).unwrap();
+        let _ = client
+            .get(format!("http://{address}/"))
+            .timeout(Duration::from_secs(1))
+            .send()
+            .await;
+        let request = server.await.unwrap();
+
+        assert!(DEFAULT_USER_AGENT.starts_with("stream-cave/"));
+        assert!(request.contains(&format!("user-agent: {DEFAULT_USER_AGENT}")));
+    }
+}
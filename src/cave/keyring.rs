@@ -0,0 +1,87 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// The service the user tokens are stored under in the keyring.
+const KEYRING_SERVICE: &str = "stream-cave";
+
+/// A keyring storing the user tokens under their login.
+pub trait Keyring: Send + Sync {
+    /// The token stored for `login`, `None` if there is none.
+    ///
+    /// # Errors
+    /// Will return an error if the keyring cannot be read.
+    fn lookup(
+        &self,
+        login: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Store `token` for `login`, replacing the stored token.
+    ///
+    /// # Errors
+    /// Will return an error if the keyring cannot store the token.
+    fn store(
+        &self,
+        login: &str,
+        token: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+    /// Remove the token of `login`.
+    ///
+    /// # Errors
+    /// Will return an error if the keyring cannot remove the token.
+    fn clear(&self, login: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// The Secret Service keyring of the desktop, such as GNOME Keyring or `KWallet`, through the
+/// `secret-tool` program of libsecret.
+///
+/// Other platforms have no `secret-tool`, so storing a token fails and it is written to the token
+/// file instead.
+pub struct SecretTool;
+
+impl Keyring for SecretTool {
+    fn lookup(
+        &self,
+        login: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "service", KEYRING_SERVICE, "login", login])
+            .output()?;
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8(output.stdout)?))
+    }
+
+    fn store(
+        &self,
+        login: &str,
+        token: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut store = Command::new("secret-tool")
+            .args(["store", "--label", "stream-cave twitch token"])
+            .args(["service", KEYRING_SERVICE, "login", login])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = store.stdin.take() {
+            stdin.write_all(token.as_bytes())?;
+        }
+        let status = store.wait()?;
+        if !status.success() {
+            return Err(format!("secret-tool failed to store the token ({status})").into());
+        }
+        Ok(())
+    }
+
+    fn clear(&self, login: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let status = Command::new("secret-tool")
+            .args(["clear", "service", KEYRING_SERVICE, "login", login])
+            .status()?;
+        if !status.success() {
+            return Err(format!("secret-tool failed to remove the token ({status})").into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task,
+    time::sleep,
+};
+
+/// A minimal http server standing in for the Twitch API in tests.
+pub struct MockApi {
+    pub address: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    max_in_flight: Arc<AtomicUsize>,
+}
+
+impl MockApi {
+    /// Start serving, `respond` maps the request line of every request to a status code and
+    /// json body.
+    pub async fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&str) -> (u16, String) + Send + Sync + 'static,
+    {
+        Self::start_with_delay(Duration::ZERO, respond).await
+    }
+
+    /// Start serving like `start`, holding every request for `delay` before responding.
+    pub async fn start_with_delay<F>(delay: Duration, respond: F) -> Self
+    where
+        F: Fn(&str) -> (u16, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let requests_clone = requests.clone();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight_clone = max_in_flight.clone();
+        let respond = Arc::new(respond);
+
+        task::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let requests = requests_clone.clone();
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight_clone.clone();
+                let respond = respond.clone();
+                task::spawn(async move {
+                    let mut buffer = vec![0; 8192];
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    sleep(delay).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                    let request_line = request.lines().next().unwrap_or_default().to_string();
+                    let (status, body) = respond(&request_line);
+                    requests.lock().unwrap().push(request_line);
+                    let response = format!(
+                        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        Self {
+            address,
+            requests,
+            max_in_flight,
+        }
+    }
+
+    /// The url of `path` on the server.
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.address)
+    }
+
+    /// The request lines recieved so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The most requests that were being handled at the same time.
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// A search result for `login` as returned by the search channels endpoint.
+pub fn search_data(login: &str, id: u32, is_live: bool) -> String {
+    format!(
+        r#"{{"broadcaster_language":"en","broadcaster_login":"{login}","display_name":"{login}","game_id":"509658","game_name":"Just Chatting","id":"{id}","tag_ids":[],"tags":[],"is_live":{is_live},"thumbnail_url":"","title":"test","started_at":"2024-01-01T00:00:00Z"}}"#
+    )
+}
+
+/// A stream as returned by the streams endpoint.
+pub fn stream_data(login: &str, id: u32) -> String {
+    stream_data_with_viewers(login, id, 10)
+}
+
+/// A stream with `viewers` viewers as returned by the streams endpoint.
+pub fn stream_data_with_viewers(login: &str, id: u32, viewers: u32) -> String {
+    format!(
+        r#"{{"id":"1","user_id":"{id}","user_login":"{login}","user_name":"{login}","game_id":"509658","game_name":"Just Chatting","type":"live","title":"test","viewer_count":{viewers},"started_at":"2024-01-01T00:00:00Z","language":"en","thumbnail_url":"","tags":[],"is_mature":false}}"#
+    )
+}
+
+/// Wrap `data` in the response envelope of the Helix api.
+pub fn helix_response(data: &[String]) -> String {
+    format!(r#"{{"data":[{}],"pagination":{{}}}}"#, data.join(","))
+}
+
+/// The schedule of `login` with a segment starting at each of `start_times`, as returned by the
+/// schedule endpoint.
+pub fn schedule_response(login: &str, start_times: &[&str]) -> String {
+    let segments: Vec<String> = start_times
+        .iter()
+        .map(|start_time| {
+            format!(
+                r#"{{"id":"{login}-{start_time}","start_time":"{start_time}","end_time":null,"title":"test","canceled_until":null,"category":{{"id":"509658","name":"Just Chatting"}},"is_recurring":true}}"#
+            )
+        })
+        .collect();
+    format!(
+        r#"{{"data":{{"segments":[{}],"broadcaster_id":"1","broadcaster_name":"{login}","broadcaster_login":"{login}","vacation":null}},"pagination":{{}}}}"#,
+        segments.join(",")
+    )
+}
+
+/// An unchecked token accepted by the mock.
+pub fn unchecked_token() -> twitch_oauth2::UserToken {
+    use std::str::FromStr;
+
+    twitch_oauth2::UserToken::from_existing_unchecked(
+        twitch_oauth2::AccessToken::from_str("").unwrap(),
+        None,
+        "",
+        None,
+        "".into(),
+        "".into(),
+        Some(vec![]),
+        Some(std::time::Duration::from_secs(10000)),
+    )
+}
@@ -0,0 +1,488 @@
+use super::{events::StreamEvent, http, SharedSettings, Streams};
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::{process::Command, sync::mpsc::Receiver};
+
+/// A backend that notifies the user of stream events.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Notify the user of `event`.
+    ///
+    /// # Errors
+    /// Will return an error if the notification could not be delivered.
+    async fn notify(
+        &self,
+        event: &StreamEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Which notification backend to use
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub enum NotifierConfig {
+    /// Desktop notifications via `notify-send`
+    Desktop,
+    /// Messages posted to a Discord webhook
+    Discord { webhook_url: String },
+    /// Discard all notifications
+    None,
+}
+
+impl NotifierConfig {
+    /// The name of the backend, without its settings.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::notifier::NotifierConfig;
+    ///
+    /// assert_eq!("desktop", NotifierConfig::Desktop.name());
+    /// ```
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Desktop => "desktop",
+            Self::Discord { .. } => "discord",
+            Self::None => "none",
+        }
+    }
+}
+
+/// Desktop notifications using `notify-send`.
+pub struct DesktopNotifier;
+
+#[async_trait]
+impl Notifier for DesktopNotifier {
+    async fn notify(
+        &self,
+        event: &StreamEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let status = Command::new("notify-send")
+            .arg("Stream Cave")
+            .arg(event.to_string())
+            .status()
+            .await
+            .map_err(|error| format!("Unable to run notify-send: {error}"))?;
+        if !status.success() {
+            return Err(format!("notify-send exited with {status}").into());
+        }
+        Ok(())
+    }
+}
+
+/// Discord notifications using a channel webhook.
+pub struct DiscordNotifier {
+    pub webhook_url: String,
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    async fn notify(
+        &self,
+        event: &StreamEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let response = http::client()
+            .post(&self.webhook_url)
+            .json(&serde_json::json!({ "content": event.to_string() }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(format!("Error {} from the Discord webhook", response.status()).into());
+        }
+        Ok(())
+    }
+}
+
+/// A notifier that does nothing.
+pub struct NoopNotifier;
+
+#[async_trait]
+impl Notifier for NoopNotifier {
+    async fn notify(
+        &self,
+        _event: &StreamEvent,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        Ok(())
+    }
+}
+
+/// Create the notifiers described by the configuration.
+///
+/// # Examples
+/// ```
+/// use stream_cave::notifier::{from_config, NotifierConfig};
+///
+/// let notifiers = from_config(&[NotifierConfig::Desktop, NotifierConfig::None]);
+/// assert_eq!(2, notifiers.len());
+/// ```
+#[must_use]
+pub fn from_config(configs: &[NotifierConfig]) -> Vec<Box<dyn Notifier>> {
+    configs
+        .iter()
+        .map(|config| -> Box<dyn Notifier> {
+            match config {
+                NotifierConfig::Desktop => Box::new(DesktopNotifier),
+                NotifierConfig::Discord { webhook_url } => Box::new(DiscordNotifier {
+                    webhook_url: webhook_url.clone(),
+                }),
+                NotifierConfig::None => Box::new(NoopNotifier),
+            }
+        })
+        .collect()
+}
+
+/// Send `event` to all notifiers concurrently, logging the failed notifications.
+pub async fn notify_all(notifiers: &[Box<dyn Notifier>], event: &StreamEvent) {
+    for result in notify_each(notifiers, event).await {
+        if let Err(error) = result {
+            eprintln!("Error while sending notification: {error}");
+        }
+    }
+}
+
+/// Send `event` to all notifiers concurrently and return the result of each notifier, in the
+/// order of `notifiers`. A failing notifier does not keep the others from being notified.
+///
+/// # Examples
+/// ```
+/// use stream_cave::events::StreamEvent;
+/// use stream_cave::notifier::{notify_each, NoopNotifier};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let results = notify_each(&[Box::new(NoopNotifier)], &StreamEvent::Test).await;
+///
+///     assert!(results[0].is_ok());
+/// }
+/// ```
+pub async fn notify_each(
+    notifiers: &[Box<dyn Notifier>],
+    event: &StreamEvent,
+) -> Vec<Result<(), Box<dyn std::error::Error + Send + Sync>>> {
+    join_all(notifiers.iter().map(|notifier| notifier.notify(event))).await
+}
+
+/// Fan out the events recieved from `task_spawner` to all notifiers.
+/// Finishes when the sender is closed.
+///
+/// # Examples
+/// ```
+/// use stream_cave::events::StreamEvent;
+/// use stream_cave::notifier::{notification_handler, NoopNotifier};
+/// use stream_cave::quality::Quality;
+/// use tokio::sync::mpsc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (event_sender, event_reciever) = mpsc::channel(5);
+///
+///     event_sender
+///         .send(StreamEvent::Live {
+///             name: String::from("jynxzi"),
+///             login: String::from("jynxzi"),
+///             quality: Quality::Height(720),
+///             details: None,
+///         })
+///         .await
+///         .unwrap();
+///     drop(event_sender);
+///
+///     notification_handler(event_reciever, vec![Box::new(NoopNotifier)]).await;
+/// }
+/// ```
+pub async fn notification_handler(
+    mut notification_handler_task_spawner_reciever: Receiver<StreamEvent>,
+    notifiers: Vec<Box<dyn Notifier>>,
+) {
+    while let Some(event) = notification_handler_task_spawner_reciever.recv().await {
+        notify_all(&notifiers, &event).await;
+    }
+}
+
+/// Fan out the events recieved from `task_spawner` to the notifiers of `settings`.
+///
+/// The notifiers are recreated when the notifiers of `settings` change, `extra_notifiers` are
+/// always notified. The events of the streams of `streams` with `notify` disabled only reach
+/// `extra_notifiers`, the flag is read for every event so schedule changes apply to the following
+/// events. Finishes when the sender is closed.
+///
+/// # Panics
+/// If the mutex or `RwLock` lock is poisoned the function will panic.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use std::sync::{Arc, Mutex, RwLock};
+/// use stream_cave::notifier::{notification_handler_with_settings, NotifierConfig};
+/// use stream_cave::{Settings, Streams};
+/// use tokio::sync::mpsc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (event_sender, event_reciever) = mpsc::channel(5);
+///     let mut settings = Settings::new(Path::new("./"));
+///     settings.notifiers.push(NotifierConfig::None);
+///
+///     drop(event_sender);
+///     let settings = Arc::new(RwLock::new(settings));
+///     let streams = Arc::new(Mutex::new(Streams::new()));
+///     notification_handler_with_settings(event_reciever, settings, Vec::new(), streams).await;
+/// }
+/// ```
+pub async fn notification_handler_with_settings(
+    mut notification_handler_task_spawner_reciever: Receiver<StreamEvent>,
+    settings: SharedSettings,
+    extra_notifiers: Vec<Box<dyn Notifier>>,
+    streams: Arc<Mutex<Streams>>,
+) {
+    let mut configs = settings.read().expect("RwLock poisoned").notifiers.clone();
+    let mut notifiers = from_config(&configs);
+    while let Some(event) = notification_handler_task_spawner_reciever.recv().await {
+        let current = settings.read().expect("RwLock poisoned").notifiers.clone();
+        if current != configs {
+            notifiers = from_config(&current);
+            configs = current;
+        }
+        if !is_muted(&streams, &event) {
+            notify_all(&notifiers, &event).await;
+        }
+        notify_all(&extra_notifiers, &event).await;
+    }
+}
+
+/// Check if the stream of `event` has notifications disabled in `streams`.
+fn is_muted(streams: &Mutex<Streams>, event: &StreamEvent) -> bool {
+    event.login().is_some_and(|login| {
+        streams
+            .lock()
+            .expect("Mutex lock poisoned")
+            .get(login)
+            .is_some_and(|stream| !stream.notify)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cave::quality::Quality;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc;
+
+    struct RecordingNotifier {
+        events: Arc<Mutex<Vec<StreamEvent>>>,
+    }
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        async fn notify(
+            &self,
+            event: &StreamEvent,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    struct FailingNotifier;
+
+    #[async_trait]
+    impl Notifier for FailingNotifier {
+        async fn notify(
+            &self,
+            _event: &StreamEvent,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err("Webhook deleted".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn recieve_live_event() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(NoopNotifier),
+            Box::new(RecordingNotifier {
+                events: events.clone(),
+            }),
+        ];
+        let (event_sender, event_reciever) = mpsc::channel(5);
+
+        event_sender
+            .send(StreamEvent::Live {
+                name: String::from("kaicenat"),
+                login: String::from("kaicenat"),
+                quality: Quality::Height(480),
+                details: None,
+            })
+            .await
+            .unwrap();
+        drop(event_sender);
+        notification_handler(event_reciever, notifiers).await;
+
+        assert_eq!(
+            vec![StreamEvent::Live {
+                name: String::from("kaicenat"),
+                login: String::from("kaicenat"),
+                quality: Quality::Height(480),
+                details: None,
+            }],
+            *events.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notification_reaches_every_backend() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let notifiers: Vec<Box<dyn Notifier>> = vec![
+            Box::new(FailingNotifier),
+            Box::new(RecordingNotifier {
+                events: events.clone(),
+            }),
+        ];
+
+        let results = notify_each(&notifiers, &StreamEvent::Test).await;
+
+        assert_eq!(2, results.len());
+        assert_eq!(
+            "Webhook deleted",
+            results[0].as_ref().unwrap_err().to_string()
+        );
+        assert!(results[1].is_ok());
+        assert_eq!(vec![StreamEvent::Test], *events.lock().unwrap());
+    }
+
+    fn discord_settings(mock: &crate::cave::mock_api::MockApi) -> SharedSettings {
+        let mut settings = crate::Settings::new(std::path::Path::new("./"));
+        settings.notifiers.push(NotifierConfig::Discord {
+            webhook_url: mock.url("/webhook"),
+        });
+        Arc::new(std::sync::RwLock::new(settings))
+    }
+
+    #[tokio::test]
+    async fn muted_stream_plays_without_notification() {
+        use crate::cave::{
+            control::SocketEvent,
+            event_handler::{event_handler, LaunchPolicy},
+            events::task_events,
+            mock_api::MockApi,
+            tasks_handler::ActivePlayers,
+            StreamConfig,
+        };
+        use tokio_stream::StreamExt;
+
+        let mock = MockApi::start(|_| (204, String::new())).await;
+        let settings = discord_settings(&mock);
+        let configs = vec![
+            StreamConfig::new("kaicenat", 641_972_806),
+            StreamConfig {
+                notify: false,
+                ..StreamConfig::new("jynxzi", 411_377_640)
+            },
+        ];
+        let streams = Arc::new(Mutex::new(Streams {
+            streams: configs.clone(),
+            ..Streams::new()
+        }));
+        let (socket_sender, socket_reciever) = mpsc::channel(5);
+        let (_exit_sender, exit_reciever) = mpsc::channel(5);
+        let (file_sender, file_reciever) = mpsc::channel(5);
+        let (task_sender, task_reciever) = mpsc::channel(5);
+        let (event_sender, event_reciever) = mpsc::channel(5);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            settings.clone(),
+            ActivePlayers::default(),
+            LaunchPolicy::default(),
+        );
+        for config in configs {
+            file_sender.send(config).await.unwrap();
+        }
+        while file_sender.capacity() < file_sender.max_capacity() {
+            tokio::task::yield_now().await;
+        }
+        socket_sender
+            .send(SocketEvent::new("live", "kaicenat", "KaiCenat"))
+            .await
+            .unwrap();
+        socket_sender
+            .send(SocketEvent::new("live", "jynxzi", "Jynxzi"))
+            .await
+            .unwrap();
+        let mut launched = task_events(task_reciever);
+        let mut players = Vec::new();
+        for _ in 0..2 {
+            let event = launched.next().await.unwrap();
+            players.push(event.login().unwrap().to_string());
+            event_sender.send(event).await.unwrap();
+        }
+        drop(event_sender);
+        notification_handler_with_settings(
+            event_reciever,
+            settings,
+            vec![Box::new(RecordingNotifier {
+                events: events.clone(),
+            })],
+            streams,
+        )
+        .await;
+        players.sort();
+
+        assert_eq!(vec!["jynxzi", "kaicenat"], players);
+        assert_eq!(1, mock.requests().len());
+        assert_eq!(2, events.lock().unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn unmuting_applies_to_following_events() {
+        use crate::cave::{mock_api::MockApi, StreamConfig};
+
+        let mock = MockApi::start(|_| (204, String::new())).await;
+        let streams = Arc::new(Mutex::new(Streams {
+            streams: vec![StreamConfig {
+                notify: false,
+                ..StreamConfig::new("jynxzi", 411_377_640)
+            }],
+            ..Streams::new()
+        }));
+        let offline = StreamEvent::Offline {
+            name: String::from("Jynxzi"),
+            login: String::from("jynxzi"),
+        };
+        let (event_sender, event_reciever) = mpsc::channel(5);
+        let handler = tokio::task::spawn(notification_handler_with_settings(
+            event_reciever,
+            discord_settings(&mock),
+            Vec::new(),
+            streams.clone(),
+        ));
+
+        event_sender.send(offline.clone()).await.unwrap();
+        while event_sender.capacity() < event_sender.max_capacity() {
+            tokio::task::yield_now().await;
+        }
+        streams.lock().unwrap().streams[0].notify = true;
+        event_sender.send(offline).await.unwrap();
+        drop(event_sender);
+        handler.await.unwrap();
+
+        assert_eq!(1, mock.requests().len());
+    }
+
+    #[test]
+    fn create_from_config() {
+        let notifiers = from_config(&[
+            NotifierConfig::Desktop,
+            NotifierConfig::Discord {
+                webhook_url: String::from("https://discord.com/api/webhooks/0/token"),
+            },
+            NotifierConfig::None,
+        ]);
+
+        assert_eq!(3, notifiers.len());
+    }
+}
@@ -1,65 +1,1384 @@
-use crate::Player;
-use std::{future::Future, process::ExitStatus};
+use crate::{quality::Quality, Latency, Player};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::OpenOptions,
+    future::Future,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::{SystemTime, UNIX_EPOCH},
+};
 use tokio::process::Command;
 
-/// Play the given stream and return a future with the exit status.
+/// Extra options used when playing streams with streamlink.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamlinkOptions {
+    /// Add `--twitch-disable-ads`
+    pub disable_ads: bool,
+    /// The config file sending the user access token to Twitch, see `write_auth_config`
+    pub auth_config: Option<PathBuf>,
+}
+
+/// The file name of the streamlink config file written by `write_auth_config`.
+pub const AUTH_CONFIG_FILE: &str = "streamlink-auth.conf";
+
+/// Write a streamlink config file to `directory` sending `token` to Twitch with every request.
+///
+/// The token is passed in a file only the user can read, because the arguments of streamlink
+/// can be read by every user of the system.
+///
+/// # Errors
+/// Will return an error if the file cannot be written.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::{Path, PathBuf};
+/// use stream_cave::player::write_auth_config;
+///
+/// let config = write_auth_config(Path::new("/srv/stream-cave"), "abcdef").unwrap();
+///
+/// assert_eq!(PathBuf::from("/srv/stream-cave/streamlink-auth.conf"), config);
+/// ```
+pub fn write_auth_config(directory: &Path, token: &str) -> std::io::Result<PathBuf> {
+    use std::io::Write;
+
+    std::fs::create_dir_all(directory)?;
+    let config = directory.join(AUTH_CONFIG_FILE);
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(0o600);
+        // The mode is only used for new files
+        if config.exists() {
+            std::fs::set_permissions(&config, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(&config)?;
+    writeln!(file, "twitch-api-header=Authorization=OAuth {token}")?;
+    Ok(config)
+}
+
+/// The default config file of streamlink, which is no longer read once a config file is passed
+/// with `--config`.
+fn default_streamlink_config() -> Option<PathBuf> {
+    directories::BaseDirs::new()
+        .map(|base| base.config_dir().join("streamlink").join("config"))
+        .filter(|config| config.is_file())
+}
+
+/// A player launched with a user-defined command, used by `Player::Custom`.
+///
+/// The template is split into words on whitespace, there is no quoting. In each word these
+/// placeholders are replaced:
+/// - `{url}` by the url of the stream, required
+/// - `{quality}` by the quality as named by streamlink, such as `720p` or `audio_only`
+/// - `{audio_flag}` by `audio_flag` when playing audio only, and by nothing otherwise
+///
+/// Words left empty are dropped, the first word is the program of the player.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CustomPlayer {
+    /// The command playing a stream, such as `iptvplayer {url} --quality {quality}`
+    pub template: String,
+    /// The argument replacing `{audio_flag}` when playing audio only
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub audio_flag: String,
+}
+
+impl CustomPlayer {
+    const PLACEHOLDERS: [&'static str; 3] = ["{url}", "{quality}", "{audio_flag}"];
+
+    /// Check that the template has a program, the `{url}` placeholder and no unknown
+    /// placeholders.
+    ///
+    /// # Errors
+    /// Will return an error describing the first problem of the template.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::player::CustomPlayer;
+    ///
+    /// let custom = CustomPlayer {
+    ///     template: String::from("iptvplayer --quality {quality}"),
+    ///     audio_flag: String::new(),
+    /// };
+    ///
+    /// assert!(custom.check().is_err());
+    /// ```
+    pub fn check(&self) -> Result<(), String> {
+        let Some(program) = self.template.split_whitespace().next() else {
+            return Err(String::from("The custom player template is empty"));
+        };
+        if program.contains('{') {
+            return Err(format!(
+                "The custom player template \"{}\" has to start with the program",
+                self.template
+            ));
+        }
+        if !self.template.contains("{url}") {
+            return Err(format!(
+                "The custom player template \"{}\" is missing the {{url}} placeholder",
+                self.template
+            ));
+        }
+        let mut unknown = self.template.clone();
+        for placeholder in Self::PLACEHOLDERS {
+            unknown = unknown.replace(placeholder, "");
+        }
+        if let Some(start) = unknown.find('{') {
+            let placeholder = unknown[start..]
+                .split_whitespace()
+                .next()
+                .unwrap_or_default();
+            return Err(format!(
+                "Unknown placeholder {placeholder} in the custom player template, the placeholders are {}",
+                Self::PLACEHOLDERS.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// The program of the template, its first word.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::player::CustomPlayer;
+    ///
+    /// let custom = CustomPlayer {
+    ///     template: String::from("iptvplayer {url}"),
+    ///     audio_flag: String::new(),
+    /// };
+    ///
+    /// assert_eq!("iptvplayer", custom.program());
+    /// ```
+    #[must_use]
+    pub fn program(&self) -> &str {
+        self.template.split_whitespace().next().unwrap_or_default()
+    }
+
+    /// The words of the template after the program with the placeholders replaced for `stream`
+    /// played at `quality`.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::player::CustomPlayer;
+    /// use stream_cave::quality::Quality;
+    ///
+    /// let custom = CustomPlayer {
+    ///     template: String::from("iptvplayer {url} --quality={quality} {audio_flag}"),
+    ///     audio_flag: String::from("--audio"),
+    /// };
+    ///
+    /// assert_eq!(
+    ///     vec!["https://twitch.tv/jynxzi", "--quality=720p"],
+    ///     custom.args("https://twitch.tv/jynxzi", &Quality::Height(720))
+    /// );
+    /// assert_eq!(
+    ///     vec!["https://twitch.tv/jynxzi", "--quality=audio_only", "--audio"],
+    ///     custom.args("https://twitch.tv/jynxzi", &Quality::Height(0))
+    /// );
+    /// ```
+    #[must_use]
+    pub fn args(&self, stream: &str, quality: &Quality) -> Vec<String> {
+        let audio_flag = if quality == &Quality::Height(0) {
+            self.audio_flag.as_str()
+        } else {
+            ""
+        };
+        let quality = quality.streamlink_quality();
+        let [url_placeholder, quality_placeholder, audio_placeholder] = Self::PLACEHOLDERS;
+        self.template
+            .split_whitespace()
+            .skip(1)
+            .map(|word| {
+                word.replace(url_placeholder, stream)
+                    .replace(quality_placeholder, &quality)
+                    .replace(audio_placeholder, audio_flag)
+            })
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+}
+
+/// Options of the player processes of every stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerOptions {
+    /// The program of the player, the program of the `Player` is looked up in `PATH` when unset
+    pub path: Option<PathBuf>,
+    /// Arguments added after the arguments selecting the quality, before the arguments of the
+    /// stream
+    pub extra_args: Vec<String>,
+    pub streamlink: StreamlinkOptions,
+    /// The command of `Player::Custom`
+    pub custom: Option<CustomPlayer>,
+}
+
+impl PlayerOptions {
+    /// The program run for `player`: the `path` when set, otherwise the program of the custom
+    /// player template or of `player`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::PathBuf;
+    /// use stream_cave::player::{CustomPlayer, PlayerOptions};
+    /// use stream_cave::Player;
+    ///
+    /// let options = PlayerOptions {
+    ///     custom: Some(CustomPlayer {
+    ///         template: String::from("iptvplayer {url}"),
+    ///         audio_flag: String::new(),
+    ///     }),
+    ///     ..PlayerOptions::default()
+    /// };
+    ///
+    /// assert_eq!(PathBuf::from("mpv"), options.program(Player::Mpv));
+    /// assert_eq!(PathBuf::from("iptvplayer"), options.program(Player::Custom));
+    /// ```
+    #[must_use]
+    pub fn program(&self, player: Player) -> PathBuf {
+        if let Some(path) = &self.path {
+            return path.clone();
+        }
+        match (player, &self.custom) {
+            (Player::Custom, Some(custom)) => PathBuf::from(custom.program()),
+            _ => PathBuf::from(program(player)),
+        }
+    }
+}
+
+/// Play the given stream and return a future with the exit status. Twitch streams played with
+/// VLC are resolved with `playable_stream` first.
 ///
 /// # Examples
 /// ```no_run
 /// use stream_cave::get_stream;
-/// use stream_cave::Player;
+/// use stream_cave::player::PlayerOptions;
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
 ///
 /// #[tokio::main]
 /// async fn main(){
 ///     let stream = String::from("https://twitch.tv/jynxzi");
-///     let quality = 720;
+///     let quality = Quality::Height(720);
 ///
-///     let play = get_stream(Player::Mpv, stream, quality).await;
+///     let play = get_stream(Player::Mpv, stream, &quality, false, Latency::Normal, &[], &PlayerOptions::default()).await;
 /// }
 /// ```
 ///
 ///
-pub async fn get_stream<'a, 'b>(
+pub async fn get_stream<'a>(
     player: Player,
     stream: String,
-    quality: u16,
+    quality: &Quality,
+    resume: bool,
+    latency: Latency,
+    args: &[String],
+    options: &PlayerOptions,
 ) -> impl Future<Output = Result<ExitStatus, std::io::Error>> + 'a {
+    let stream = playable_stream(player, stream, quality, latency, options).await;
+    player_command(player, stream, quality, resume, latency, args, options).status()
+}
+
+/// Create the player command for the given stream, the extra arguments of `options` and then
+/// `args` are appended after the arguments selecting the quality.
+///
+/// When `resume` is set mpv may resume from the last playback position, streamlink does not keep
+/// playback positions so it is not affected. `Latency::Low` selects the low-latency profile of
+/// mpv and the low-latency streams of streamlink. The streamlink options of `options` are ignored
+/// by other players.
+///
+/// VLC plays audio only with `--no-video` and prefers the resolution of the height for other
+/// qualities, named qualities are left to VLC. VLC cannot open Twitch pages, pass it the url
+/// returned by `playable_stream`.
+///
+/// `Player::Custom` runs the `custom` template of `options`, see `CustomPlayer`. Without a
+/// template the program of `options` is run with only the extra arguments.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::{player_command, PlayerOptions};
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
+///
+/// let stream = String::from("https://twitch.tv/jynxzi");
+/// let options = PlayerOptions::default();
+/// let command = player_command(Player::Streamlink, stream, &Quality::Height(720), false, Latency::Normal, &[], &options);
+///
+/// assert_eq!("streamlink", command.as_std().get_program());
+/// ```
+#[must_use]
+pub fn player_command(
+    player: Player,
+    stream: String,
+    quality: &Quality,
+    resume: bool,
+    latency: Latency,
+    args: &[String],
+    options: &PlayerOptions,
+) -> Command {
+    let program = options.program(player);
     match player {
         Player::Mpv => {
-            let mut mpv: Command = Command::new("mpv");
-            if quality == 0 {
-                mpv.arg(stream)
-                    .arg("--no-resume-playback")
-                    .arg("--ytdl-format=bestaudio");
-            } else {
-                mpv.arg(stream)
-                    .arg("--no-resume-playback")
-                    .arg(format!("--ytdl-format=best[height<=?{quality}]"));
+            let mut mpv: Command = Command::new(program);
+            mpv.arg(stream);
+            if !resume {
+                mpv.arg("--no-resume-playback");
             }
-            mpv.status()
+            if latency == Latency::Low {
+                mpv.arg("--profile=low-latency");
+            }
+            mpv.arg(format!("--ytdl-format={}", quality.yt_dlp_format()));
+            mpv.args(&options.extra_args);
+            mpv.args(args);
+            mpv
         }
         Player::Streamlink => {
-            let mut streamlink: Command = Command::new("streamlink");
-            if quality == 0 {
-                streamlink.arg(stream).arg("audio_only");
-            } else {
-                streamlink.arg(stream).arg(format!("{quality}p"));
+            let mut streamlink: Command = Command::new(program);
+            streamlink.arg(stream).arg(quality.streamlink_quality());
+            if latency == Latency::Low {
+                streamlink.arg("--twitch-low-latency");
+            }
+            if options.streamlink.disable_ads {
+                streamlink.arg("--twitch-disable-ads");
+            }
+            if let Some(auth_config) = &options.streamlink.auth_config {
+                if let Some(default_config) = default_streamlink_config() {
+                    streamlink.arg(format!("--config={}", default_config.display()));
+                }
+                streamlink.arg(format!("--config={}", auth_config.display()));
+            }
+            streamlink.args(&options.extra_args);
+            streamlink.args(args);
+            streamlink
+        }
+        Player::Vlc => {
+            let mut vlc: Command = Command::new(program);
+            vlc.arg(stream);
+            if !resume {
+                vlc.arg("--qt-continue=0");
             }
-            streamlink.status()
+            match quality {
+                Quality::Height(0) => {
+                    vlc.arg("--no-video");
+                }
+                Quality::Height(height) => {
+                    vlc.arg(format!("--preferred-resolution={height}"));
+                }
+                Quality::Named(_) => {}
+            }
+            vlc.args(&options.extra_args);
+            vlc.args(args);
+            vlc
+        }
+        Player::Custom => {
+            let mut custom: Command = Command::new(program);
+            if let Some(template) = &options.custom {
+                custom.args(template.args(&stream, quality));
+            }
+            custom.args(&options.extra_args);
+            custom.args(args);
+            custom
+        }
+    }
+}
+
+/// The url `player` is launched with for `stream`.
+///
+/// VLC cannot read Twitch pages, so Twitch streams are resolved with yt-dlp for it, other players
+/// get `stream` unchanged. Streams that cannot be resolved are passed on as is, after printing
+/// the error.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::{playable_stream, PlayerOptions};
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let stream = String::from("https://twitch.tv/jynxzi");
+///     let options = PlayerOptions::default();
+///     let url = playable_stream(Player::Mpv, stream.clone(), &Quality::Height(720), Latency::Normal, &options).await;
+///
+///     assert_eq!(stream, url);
+/// }
+/// ```
+pub async fn playable_stream(
+    player: Player,
+    stream: String,
+    quality: &Quality,
+    latency: Latency,
+    options: &PlayerOptions,
+) -> String {
+    if player != Player::Vlc || !stream.contains("twitch.tv/") {
+        return stream;
+    }
+    let resolver = resolver_command(player, stream.clone(), quality, latency, options);
+    match resolve_stream_url(resolver).await {
+        Ok(url) => url,
+        Err(error) => {
+            eprintln!("Error resolving {stream} for VLC: {error}");
+            stream
+        }
+    }
+}
+
+/// Create the command resolving the direct url of the given stream without playing it.
+///
+/// Mpv resolves streams with yt-dlp, so `yt-dlp -g` is used for it, VLC and custom players, and
+/// `streamlink --stream-url` for streamlink. The quality and latency are selected as in `player_command`.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::{resolver_command, PlayerOptions};
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
+///
+/// let stream = String::from("https://twitch.tv/jynxzi");
+/// let options = PlayerOptions::default();
+/// let command = resolver_command(Player::Mpv, stream, &Quality::Height(720), Latency::Normal, &options);
+///
+/// assert_eq!("yt-dlp", command.as_std().get_program());
+/// ```
+#[must_use]
+pub fn resolver_command(
+    player: Player,
+    stream: String,
+    quality: &Quality,
+    latency: Latency,
+    options: &PlayerOptions,
+) -> Command {
+    match player {
+        Player::Mpv | Player::Vlc | Player::Custom => {
+            let mut yt_dlp = Command::new("yt-dlp");
+            yt_dlp.arg("-g").arg("-f");
+            yt_dlp.arg(quality.yt_dlp_format());
+            yt_dlp.arg(stream);
+            yt_dlp
+        }
+        Player::Streamlink => {
+            let mut streamlink =
+                player_command(player, stream, quality, false, latency, &[], options);
+            streamlink.arg("--stream-url");
+            streamlink
+        }
+    }
+}
+
+/// Run the resolver `command` and return the url it printed.
+///
+/// # Errors
+/// Will return an error if the resolver cannot be run, fails or does not print a url.
+///
+/// # Examples
+/// ```no_run
+/// use stream_cave::player::{resolve_stream_url, resolver_command, PlayerOptions};
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let stream = String::from("https://twitch.tv/jynxzi");
+///     let options = PlayerOptions::default();
+///     let command = resolver_command(Player::Streamlink, stream, &Quality::Height(720), Latency::Normal, &options);
+///
+///     println!("{}", resolve_stream_url(command).await.unwrap());
+/// }
+/// ```
+pub async fn resolve_stream_url(
+    mut command: Command,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let program = command.as_std().get_program().to_string_lossy().to_string();
+    let output = command.output().await?;
+    if !output.status.success() {
+        return Err(format!(
+            "`{program}` failed to resolve the stream: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| format!("`{program}` did not print a stream url").into())
+}
+
+/// The arguments starting `player` minimized so it does not take the focus.
+///
+/// Streamlink passes them on to its player, which is assumed to be mpv. Window managers ignoring
+/// the request, such as most Wayland compositors, open the player as usual.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::minimized_args;
+/// use stream_cave::Player;
+///
+/// assert_eq!(vec![String::from("--window-minimized=yes")], minimized_args(Player::Mpv));
+/// ```
+#[must_use]
+pub fn minimized_args(player: Player) -> Vec<String> {
+    match player {
+        Player::Mpv => vec![String::from("--window-minimized=yes")],
+        Player::Streamlink => vec![String::from("--player-args=--window-minimized=yes")],
+        Player::Vlc => vec![String::from("--qt-start-minimized")],
+        Player::Custom => Vec::new(),
+    }
+}
+
+/// The arguments selecting the mpv `profile` and the mpv configuration directory `config_dir`.
+/// Other players do not read mpv profiles, they are ignored with a warning.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use stream_cave::player::mpv_profile_args;
+/// use stream_cave::Player;
+///
+/// assert_eq!(
+///     vec![String::from("--profile=music"), String::from("--config-dir=./mpv")],
+///     mpv_profile_args(Player::Mpv, Some("music"), Some(Path::new("./mpv")))
+/// );
+/// assert!(mpv_profile_args(Player::Streamlink, Some("music"), None).is_empty());
+/// ```
+#[must_use]
+pub fn mpv_profile_args(
+    player: Player,
+    profile: Option<&str>,
+    config_dir: Option<&Path>,
+) -> Vec<String> {
+    if profile.is_none() && config_dir.is_none() {
+        return Vec::new();
+    }
+    match player {
+        Player::Mpv => profile
+            .map(|profile| format!("--profile={profile}"))
+            .into_iter()
+            .chain(config_dir.map(|directory| format!("--config-dir={}", directory.display())))
+            .collect(),
+        Player::Streamlink | Player::Vlc | Player::Custom => {
+            eprintln!("Ignoring the mpv profile settings, the player is not mpv");
+            Vec::new()
+        }
+    }
+}
+
+/// The arguments making `player` record the stream to `file`.
+///
+/// Mpv records the stream while playing it, streamlink records it instead of starting its player.
+/// VLC and custom players cannot record, they get no arguments.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use stream_cave::player::record_args;
+/// use stream_cave::Player;
+///
+/// let file = Path::new("/srv/archive/jynxzi-1709402400.ts");
+///
+/// assert_eq!(
+///     vec![String::from("--stream-record=/srv/archive/jynxzi-1709402400.ts")],
+///     record_args(Player::Mpv, file)
+/// );
+/// assert!(record_args(Player::Vlc, file).is_empty());
+/// ```
+#[must_use]
+pub fn record_args(player: Player, file: &Path) -> Vec<String> {
+    match player {
+        Player::Mpv => vec![format!("--stream-record={}", file.display())],
+        Player::Streamlink => vec![format!("--output={}", file.display())],
+        Player::Vlc | Player::Custom => Vec::new(),
+    }
+}
+
+/// The file a recording of `name` started at `now` is written to in `directory`, such as
+/// `jynxzi-1709402400.ts` with the seconds since the unix epoch.
+///
+/// Characters of `name` other than letters, digits, `-` and `_` are replaced with `_`, and a
+/// number is appended when the file already exists so restarted recordings do not overwrite each
+/// other. The directory is created when missing.
+///
+/// # Errors
+/// Will return an error if `directory` cannot be created.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::{Path, PathBuf};
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use stream_cave::player::recording_file;
+///
+/// let now = UNIX_EPOCH + Duration::from_secs(1_709_402_400);
+/// let file = recording_file(Path::new("/srv/archive"), "jynxzi", now).unwrap();
+///
+/// assert_eq!(PathBuf::from("/srv/archive/jynxzi-1709402400.ts"), file);
+/// ```
+pub fn recording_file(
+    directory: &Path,
+    name: &str,
+    now: SystemTime,
+) -> Result<PathBuf, std::io::Error> {
+    let stem = recording_stem(directory, name, now, ".ts")?;
+    Ok(directory.join(format!("{stem}.ts")))
+}
+
+/// The file name template of the numbered segments of a recording of `name` started at `now` in
+/// `directory`, such as `jynxzi-1709402400-0001.ts`.
+///
+/// The name is sanitised and numbered as in `recording_file`, so a restarted recording does not
+/// overwrite the first segments.
+///
+/// # Errors
+/// Will return an error if `directory` cannot be created.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::{Path, PathBuf};
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use stream_cave::player::segment_template;
+///
+/// let now = UNIX_EPOCH + Duration::from_secs(1_709_402_400);
+///
+/// assert_eq!(
+///     PathBuf::from("/srv/archive/jynxzi-1709402400-%04d.ts"),
+///     segment_template(Path::new("/srv/archive"), "jynxzi", now).unwrap()
+/// );
+/// ```
+pub fn segment_template(
+    directory: &Path,
+    name: &str,
+    now: SystemTime,
+) -> Result<PathBuf, std::io::Error> {
+    let stem = recording_stem(directory, name, now, "-0001.ts")?;
+    Ok(directory.join(format!("{stem}-%04d.ts")))
+}
+
+/// The sanitised `name-timestamp` stem of a recording whose first file, the stem followed by
+/// `first_file`, does not exist yet in `directory`. Creates the directory when missing.
+fn recording_stem(
+    directory: &Path,
+    name: &str,
+    now: SystemTime,
+    first_file: &str,
+) -> Result<String, std::io::Error> {
+    std::fs::create_dir_all(directory)?;
+    let name: String = name
+        .chars()
+        .map(|character| match character {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => character,
+            _ => '_',
+        })
+        .collect();
+    let timestamp = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+
+    let mut stem = format!("{name}-{timestamp}");
+    let mut count = 1;
+    while directory.join(format!("{stem}{first_file}")).exists() {
+        stem = format!("{name}-{timestamp}-{count}");
+        count += 1;
+    }
+    Ok(stem)
+}
+
+/// Create the ffmpeg command splitting a recording read from its stdin into segments of
+/// `max_minutes` each, written to the files of `template` numbered from 1, see
+/// `segment_template`.
+///
+/// The stream is copied without re-encoding and split on the next keyframe, so no part of the
+/// stream is lost between segments.
+///
+/// # Examples
+/// ```
+/// use std::path::Path;
+/// use stream_cave::player::segment_command;
+///
+/// let command = segment_command(Path::new("./jynxzi-1709402400-%04d.ts"), 60);
+///
+/// assert_eq!("ffmpeg", command.as_std().get_program());
+/// ```
+#[must_use]
+pub fn segment_command(template: &Path, max_minutes: u32) -> Command {
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg
+        .args([
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-i",
+            "-",
+            "-c",
+            "copy",
+        ])
+        .args(["-f", "segment", "-segment_time"])
+        .arg((u64::from(max_minutes) * 60).to_string())
+        .args(["-segment_start_number", "1", "-reset_timestamps", "1"])
+        .arg(template);
+    ffmpeg
+}
+
+/// The message printed by mpv when playback starts, see `playback_args`.
+pub const PLAYING_MESSAGE: &str = "stream-cave: playing";
+
+/// The arguments making `player` report the start of playback on stdout, checked with
+/// `started_playing`. Streamlink reports starting its player without extra arguments.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::playback_args;
+/// use stream_cave::Player;
+///
+/// assert!(playback_args(Player::Streamlink).is_empty());
+/// ```
+#[must_use]
+pub fn playback_args(player: Player) -> Vec<String> {
+    match player {
+        Player::Mpv => vec![format!("--term-playing-msg={PLAYING_MESSAGE}")],
+        Player::Streamlink | Player::Vlc | Player::Custom => Vec::new(),
+    }
+}
+
+/// Check if `line` of the stdout of `player` reports the start of playback. Streamlink recording
+/// a stream reports writing the output instead of starting its player.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::{started_playing, PLAYING_MESSAGE};
+/// use stream_cave::Player;
+///
+/// assert!(started_playing(Player::Mpv, PLAYING_MESSAGE));
+/// assert!(started_playing(Player::Streamlink, "[cli][info] Starting player: mpv"));
+/// assert!(started_playing(Player::Streamlink, "[cli][info] Writing output to"));
+/// assert!(!started_playing(Player::Mpv, "Playing: https://www.twitch.tv/jynxzi"));
+/// ```
+#[must_use]
+pub fn started_playing(player: Player, line: &str) -> bool {
+    match player {
+        Player::Mpv => line == PLAYING_MESSAGE,
+        Player::Streamlink => {
+            line.contains("Starting player") || line.contains("Writing output to")
         }
+        Player::Vlc | Player::Custom => false,
+    }
+}
+
+/// Check if `player` reports the start of playback, see `started_playing`. VLC and custom
+/// players do not, so they are not watched for a startup timeout.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::reports_playback;
+/// use stream_cave::Player;
+///
+/// assert!(reports_playback(Player::Mpv));
+/// assert!(!reports_playback(Player::Vlc));
+/// ```
+#[must_use]
+pub const fn reports_playback(player: Player) -> bool {
+    !matches!(player, Player::Vlc | Player::Custom)
+}
+
+/// Redirect the stdout and stderr of `command` to `log_file`. The log of the previous launch is
+/// kept with the extension `.log.1`.
+///
+/// # Errors
+/// Will return an error if the log file or its directory cannot be created.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::player::{log_output, player_command, PlayerOptions};
+/// use stream_cave::quality::Quality;
+/// use stream_cave::{Latency, Player};
+///
+/// let stream = String::from("https://twitch.tv/jynxzi");
+/// let mut command = player_command(Player::Mpv, stream, &Quality::Height(720), false, Latency::Normal, &[], &PlayerOptions::default());
+///
+/// log_output(&mut command, Path::new("./logs/jynxzi.log")).unwrap();
+/// ```
+pub fn log_output(command: &mut Command, log_file: &Path) -> std::io::Result<()> {
+    if let Some(directory) = log_file.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    if log_file.exists() {
+        std::fs::rename(log_file, log_file.with_extension("log.1"))?;
+    }
+    // Opened for appending so the output forwarded by the daemon can be added to the same file
+    let stdout = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)?;
+    let stderr = stdout.try_clone()?;
+    command.stdout(stdout).stderr(stderr);
+    Ok(())
+}
+
+/// How a player exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitKind {
+    /// The player finished playing
+    Normal,
+    /// The user closed the player, by quitting mpv or interrupting either player
+    UserQuit,
+    /// The player failed while playing or was killed, the stream may be restarted
+    Crash,
+    /// The player could not be started, restarting it would fail again
+    LaunchFailure,
+}
+
+/// Classify the exit `status` of `player`.
+///
+/// Mpv exits with 4 when quit by the user and 1 when it fails to initialize, streamlink exits
+/// with 130 when interrupted. Shells report a missing or non executable program as 127 and 126.
+/// On unix players ended by `SIGHUP`, `SIGINT` or `SIGTERM` were quit, other signals are crashes.
+///
+/// # Examples
+/// ```no_run
+/// use std::process::Command;
+/// use stream_cave::player::{classify_exit, ExitKind};
+/// use stream_cave::Player;
+///
+/// let status = Command::new("mpv").arg("https://twitch.tv/jynxzi").status().unwrap();
+///
+/// if classify_exit(Player::Mpv, status) == ExitKind::Crash {
+///     println!("mpv crashed");
+/// }
+/// ```
+#[must_use]
+pub fn classify_exit(player: Player, status: ExitStatus) -> ExitKind {
+    if let Some(code) = status.code() {
+        return match (player, code) {
+            (_, 0) => ExitKind::Normal,
+            (Player::Mpv, 4) | (Player::Streamlink, 130) => ExitKind::UserQuit,
+            (Player::Mpv, 1) | (_, 126 | 127) => ExitKind::LaunchFailure,
+            _ => ExitKind::Crash,
+        };
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        const SIGHUP: i32 = 1;
+        const SIGINT: i32 = 2;
+        const SIGTERM: i32 = 15;
+        if let Some(SIGHUP | SIGINT | SIGTERM) = status.signal() {
+            return ExitKind::UserQuit;
+        }
+    }
+    ExitKind::Crash
+}
+
+/// The program run by the given player, empty for `Player::Custom` whose program is set by its
+/// template.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::program;
+/// use stream_cave::Player;
+///
+/// assert_eq!("mpv", program(Player::Mpv));
+/// ```
+#[must_use]
+pub const fn program(player: Player) -> &'static str {
+    match player {
+        Player::Mpv => "mpv",
+        Player::Streamlink => "streamlink",
+        Player::Vlc => "vlc",
+        Player::Custom => "",
     }
 }
 
+/// Find `program` in the directories of `PATH`, a program containing a path separator is only
+/// checked to exist.
+///
+/// # Examples
+/// ```
+/// use stream_cave::player::find_program;
+///
+/// assert!(find_program("stream-cave-nonexistent-player").is_none());
+/// ```
+#[must_use]
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    let program_path = Path::new(program);
+    if program_path.components().count() > 1 {
+        return program_path.is_file().then(|| program_path.to_path_buf());
+    }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Check that the program run by `player` with `options` can be found, see
+/// `PlayerOptions::program`.
+///
+/// # Errors
+/// Will return an error naming the program if it is not found in `PATH`.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::PathBuf;
+/// use stream_cave::player::{check_player, PlayerOptions};
+/// use stream_cave::Player;
+///
+/// let mpv = check_player(Player::Mpv, &PlayerOptions::default()).unwrap();
+/// let options = PlayerOptions {
+///     path: Some(PathBuf::from("/opt/mpv/bin/mpv")),
+///     ..PlayerOptions::default()
+/// };
+/// let custom = check_player(Player::Mpv, &options).unwrap();
+/// ```
+pub fn check_player(
+    player: Player,
+    options: &PlayerOptions,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    check_program(&options.program(player).to_string_lossy())
+}
+
+fn check_program(program: &str) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    find_program(program)
+        .ok_or_else(|| format!("player program `{program}` was not found in PATH").into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     #[tokio::test]
     async fn play_mpv() {
         let video = String::from("file://./tests/resources/video.mkv");
-        let mpv = get_stream(Player::Mpv, video, 1080).await;
+        let mpv = get_stream(
+            Player::Mpv,
+            video,
+            &Quality::Height(1080),
+            false,
+            Latency::Normal,
+            &[],
+            &PlayerOptions::default(),
+        )
+        .await;
 
         let exit_code = mpv.await.unwrap();
 
         assert!(exit_code.success());
     }
+
+    #[test]
+    fn recordings_do_not_collide() {
+        let directory = std::env::temp_dir().join("stream-cave-recordings");
+        let _ = std::fs::remove_dir_all(&directory);
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(1_709_402_405);
+
+        let first = recording_file(&directory, "../jynxzi", now).unwrap();
+        std::fs::write(&first, "").unwrap();
+        let second = recording_file(&directory, "../jynxzi", now).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(directory.join("___jynxzi-1709402405.ts"), first);
+        assert_eq!(directory.join("___jynxzi-1709402405-1.ts"), second);
+    }
+
+    #[test]
+    fn rotate_recording_segments() {
+        let directory = std::env::temp_dir().join("stream-cave-segments");
+        let _ = std::fs::remove_dir_all(&directory);
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(1_709_402_405);
+        let template = segment_template(&directory, "kaicenat", now).unwrap();
+        std::fs::write(directory.join("kaicenat-1709402405-0001.ts"), "").unwrap();
+        let restarted = segment_template(&directory, "kaicenat", now).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let command = segment_command(&template, 30);
+        let args: Vec<_> = command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+
+        let segment_time = args.iter().position(|arg| arg == "-segment_time").unwrap();
+        assert_eq!("1800", args[segment_time + 1]);
+        assert!(args.windows(2).any(|pair| pair == ["-c", "copy"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["-segment_start_number", "1"]));
+        assert_eq!(Some(&template.display().to_string()), args.last());
+        assert_eq!(directory.join("kaicenat-1709402405-%04d.ts"), template);
+        assert_eq!(directory.join("kaicenat-1709402405-1-%04d.ts"), restarted);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn classify_exit_statuses() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let code = |code: i32| ExitStatus::from_raw(code << 8);
+        let signal = ExitStatus::from_raw;
+
+        assert_eq!(ExitKind::Normal, classify_exit(Player::Mpv, code(0)));
+        assert_eq!(ExitKind::UserQuit, classify_exit(Player::Mpv, code(4)));
+        assert_eq!(ExitKind::Crash, classify_exit(Player::Streamlink, code(4)));
+        assert_eq!(
+            ExitKind::UserQuit,
+            classify_exit(Player::Streamlink, code(130))
+        );
+        assert_eq!(ExitKind::LaunchFailure, classify_exit(Player::Mpv, code(1)));
+        assert_eq!(ExitKind::Crash, classify_exit(Player::Streamlink, code(1)));
+        assert_eq!(
+            ExitKind::LaunchFailure,
+            classify_exit(Player::Streamlink, code(127))
+        );
+        assert_eq!(ExitKind::Crash, classify_exit(Player::Mpv, code(2)));
+        assert_eq!(ExitKind::UserQuit, classify_exit(Player::Mpv, signal(15)));
+        assert_eq!(ExitKind::Crash, classify_exit(Player::Mpv, signal(9)));
+        assert_eq!(
+            ExitKind::Crash,
+            classify_exit(Player::Streamlink, signal(11))
+        );
+    }
+
+    #[test]
+    fn custom_player_args() {
+        let args = vec![String::from("--video-rotate=90")];
+        let stream = String::from("https://twitch.tv/jynxzi");
+
+        let with_args = player_command(
+            Player::Mpv,
+            stream.clone(),
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &args,
+            &PlayerOptions::default(),
+        );
+        let without_args = player_command(
+            Player::Mpv,
+            stream,
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &[],
+            &PlayerOptions::default(),
+        );
+
+        assert!(with_args
+            .as_std()
+            .get_args()
+            .any(|arg| arg == "--video-rotate=90"));
+        assert!(!without_args
+            .as_std()
+            .get_args()
+            .any(|arg| arg == "--video-rotate=90"));
+    }
+
+    #[test]
+    fn custom_player_path_and_extra_args() {
+        let options = PlayerOptions {
+            path: Some(PathBuf::from("/opt/mpv/bin/mpv")),
+            extra_args: vec![String::from("--hwdec=auto")],
+            ..PlayerOptions::default()
+        };
+
+        let command = player_command(
+            Player::Mpv,
+            String::from("https://twitch.tv/jynxzi"),
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &[String::from("--volume=50")],
+            &options,
+        );
+        let args: Vec<_> = command.as_std().get_args().collect();
+
+        assert_eq!("/opt/mpv/bin/mpv", command.as_std().get_program());
+        assert_eq!(
+            [
+                "--ytdl-format=best[height<=?720]",
+                "--hwdec=auto",
+                "--volume=50"
+            ],
+            args[args.len() - 3..]
+        );
+    }
+
+    #[test]
+    fn streamlink_ad_options() {
+        let stream = String::from("https://twitch.tv/jynxzi");
+        let options = PlayerOptions {
+            streamlink: StreamlinkOptions {
+                disable_ads: true,
+                auth_config: Some(PathBuf::from("/srv/stream-cave/streamlink-auth.conf")),
+            },
+            ..PlayerOptions::default()
+        };
+
+        let enabled = player_command(
+            Player::Streamlink,
+            stream.clone(),
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &[],
+            &options,
+        );
+        let disabled = player_command(
+            Player::Streamlink,
+            stream,
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &[],
+            &PlayerOptions::default(),
+        );
+
+        let enabled_args: Vec<_> = enabled.as_std().get_args().collect();
+        assert!(enabled_args.contains(&"--twitch-disable-ads".as_ref()));
+        assert_eq!(
+            Some(&"--config=/srv/stream-cave/streamlink-auth.conf".as_ref()),
+            enabled_args.last()
+        );
+        assert!(!enabled_args
+            .iter()
+            .any(|arg| arg.to_string_lossy().contains("OAuth")));
+        assert!(!disabled.as_std().get_args().any(|arg| {
+            let arg = arg.to_string_lossy();
+            arg.starts_with("--twitch-") || arg.starts_with("--config")
+        }));
+    }
+
+    #[test]
+    fn write_token_to_private_config() {
+        let directory = std::env::temp_dir().join("stream-cave-auth-config");
+        let _ = std::fs::remove_dir_all(&directory);
+
+        write_auth_config(&directory, "abcdef").unwrap();
+        let config = write_auth_config(&directory, "ghijkl").unwrap();
+        let contents = std::fs::read_to_string(&config).unwrap();
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::metadata(&config).unwrap().permissions().mode() & 0o777
+        };
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(directory.join(AUTH_CONFIG_FILE), config);
+        assert_eq!("twitch-api-header=Authorization=OAuth ghijkl\n", contents);
+        #[cfg(unix)]
+        assert_eq!(0o600, mode);
+    }
+
+    #[test]
+    fn resume_playback_option() {
+        let stream = String::from("https://twitch.tv/jynxzi");
+        let options = PlayerOptions::default();
+
+        let live = player_command(
+            Player::Mpv,
+            stream.clone(),
+            &Quality::Height(720),
+            false,
+            Latency::Normal,
+            &[],
+            &options,
+        );
+        let resumed = player_command(
+            Player::Mpv,
+            stream,
+            &Quality::Height(720),
+            true,
+            Latency::Normal,
+            &[],
+            &options,
+        );
+
+        assert!(live
+            .as_std()
+            .get_args()
+            .any(|arg| arg == "--no-resume-playback"));
+        assert!(!resumed
+            .as_std()
+            .get_args()
+            .any(|arg| arg == "--no-resume-playback"));
+    }
+
+    #[test]
+    fn vlc_audio_only_command() {
+        let stream = String::from("https://video.example/jynxzi/index.m3u8");
+        let options = PlayerOptions {
+            extra_args: vec![String::from("--fullscreen")],
+            ..PlayerOptions::default()
+        };
+
+        let [audio, video] = [0, 720].map(|height| {
+            player_command(
+                Player::Vlc,
+                stream.clone(),
+                &Quality::Height(height),
+                true,
+                Latency::Normal,
+                &minimized_args(Player::Vlc),
+                &options,
+            )
+        });
+        let args = |command: &Command| -> Vec<String> {
+            command
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect()
+        };
+
+        assert_eq!("vlc", audio.as_std().get_program());
+        assert_eq!(
+            [
+                stream.as_str(),
+                "--no-video",
+                "--fullscreen",
+                "--qt-start-minimized"
+            ],
+            args(&audio)[..]
+        );
+        assert_eq!(
+            [
+                stream.as_str(),
+                "--preferred-resolution=720",
+                "--fullscreen",
+                "--qt-start-minimized"
+            ],
+            args(&video)[..]
+        );
+        assert_eq!("\"Vlc\"", serde_json::to_string(&Player::Vlc).unwrap());
+        assert_eq!(Player::Vlc, serde_json::from_str("\"Vlc\"").unwrap());
+        assert_eq!(
+            "yt-dlp",
+            resolver_command(
+                Player::Vlc,
+                String::from("https://twitch.tv/jynxzi"),
+                &Quality::Height(0),
+                Latency::Normal,
+                &options
+            )
+            .as_std()
+            .get_program()
+        );
+    }
+
+    #[test]
+    fn low_latency_options() {
+        let stream = String::from("https://twitch.tv/jynxzi");
+        let options = PlayerOptions::default();
+
+        let commands = [Player::Mpv, Player::Streamlink].map(|player| {
+            [Latency::Normal, Latency::Low].map(|latency| {
+                player_command(
+                    player,
+                    stream.clone(),
+                    &Quality::Height(720),
+                    false,
+                    latency,
+                    &[],
+                    &options,
+                )
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+            })
+        });
+        let [[mpv_normal, mpv_low], [streamlink_normal, streamlink_low]] = commands;
+
+        assert!(mpv_low.contains(&String::from("--profile=low-latency")));
+        assert!(!mpv_normal.contains(&String::from("--profile=low-latency")));
+        assert!(streamlink_low.contains(&String::from("--twitch-low-latency")));
+        assert!(!streamlink_normal.contains(&String::from("--twitch-low-latency")));
+    }
+
+    #[test]
+    fn named_streamlink_quality() {
+        let stream = String::from("https://twitch.tv/jynxzi");
+        let options = PlayerOptions::default();
+
+        let [source, high_fps] = ["source", "1080p60"].map(|name| {
+            let quality: Quality = name.parse().unwrap();
+            [Player::Streamlink, Player::Mpv].map(|player| {
+                player_command(
+                    player,
+                    stream.clone(),
+                    &quality,
+                    false,
+                    Latency::Normal,
+                    &[],
+                    &options,
+                )
+                .as_std()
+                .get_args()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect::<Vec<_>>()
+            })
+        });
+
+        assert_eq!([stream.as_str(), "source"], source[0][..2]);
+        assert_eq!([stream.as_str(), "1080p60"], high_fps[0][..2]);
+        assert!(source[1].contains(&String::from("--ytdl-format=best")));
+        assert!(high_fps[1].contains(&String::from("--ytdl-format=best[height<=?1080][fps<=?60]")));
+    }
+
+    #[test]
+    fn launch_minimized_options() {
+        let stream = String::from("https://twitch.tv/jynxzi");
+        let options = PlayerOptions::default();
+
+        let [mpv, streamlink] = [Player::Mpv, Player::Streamlink].map(|player| {
+            player_command(
+                player,
+                stream.clone(),
+                &Quality::Height(720),
+                false,
+                Latency::Normal,
+                &minimized_args(player),
+                &options,
+            )
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+        });
+
+        assert!(mpv.contains(&String::from("--window-minimized=yes")));
+        assert!(streamlink.contains(&String::from("--player-args=--window-minimized=yes")));
+    }
+
+    #[tokio::test]
+    async fn write_player_output_to_log() {
+        let directory = std::env::temp_dir().join("stream-cave-player-logs");
+        let _ = std::fs::remove_dir_all(&directory);
+        let log_file = directory.join("kaicenat.log");
+        let mut first = Command::new("sh");
+        first.args(["-c", "echo first"]);
+        let mut second = Command::new("sh");
+        second.args(["-c", "echo out; echo err >&2"]);
+
+        log_output(&mut first, &log_file).unwrap();
+        first.status().await.unwrap();
+        log_output(&mut second, &log_file).unwrap();
+        second.status().await.unwrap();
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        let previous = std::fs::read_to_string(directory.join("kaicenat.log.1")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!("out\nerr\n", log);
+        assert_eq!("first\n", previous);
+    }
+
+    #[test]
+    fn report_missing_player_program() {
+        let error = check_program("stream-cave-nonexistent-player").unwrap_err();
+
+        assert_eq!(
+            "player program `stream-cave-nonexistent-player` was not found in PATH",
+            error.to_string()
+        );
+        assert!(check_program("sh").is_ok());
+    }
 }
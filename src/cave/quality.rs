@@ -0,0 +1,152 @@
+use super::StreamConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The quality a stream is played at.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Quality {
+    /// The highest quality up to the video height, 0 for audio only
+    Height(u16),
+    /// A quality name of streamlink such as `source`, `worst` or `1080p60`, passed to streamlink
+    /// as is
+    Named(String),
+}
+
+impl Quality {
+    /// The format selector of yt-dlp closest to the quality. Named qualities with a height such as
+    /// `720p60` select the best stream up to the height and frame rate, unknown names select the
+    /// best stream.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::quality::Quality;
+    ///
+    /// assert_eq!("best[height<=?720]", Quality::Height(720).yt_dlp_format());
+    /// assert_eq!("bestaudio", Quality::Height(0).yt_dlp_format());
+    /// assert_eq!("best", Quality::Named(String::from("source")).yt_dlp_format());
+    /// assert_eq!(
+    ///     "best[height<=?1080][fps<=?60]",
+    ///     Quality::Named(String::from("1080p60")).yt_dlp_format()
+    /// );
+    /// ```
+    #[must_use]
+    pub fn yt_dlp_format(&self) -> String {
+        match self {
+            Self::Height(0) => String::from("bestaudio"),
+            Self::Height(height) => format!("best[height<=?{height}]"),
+            Self::Named(name) => match name.as_str() {
+                "worst" => String::from("worst"),
+                "audio_only" => String::from("bestaudio"),
+                name => {
+                    let Some((height, fps)) = name.split_once('p') else {
+                        return String::from("best");
+                    };
+                    match (height.parse::<u16>(), fps.parse::<u16>()) {
+                        (Ok(height), Ok(fps)) => format!("best[height<=?{height}][fps<=?{fps}]"),
+                        (Ok(height), Err(_)) if fps.is_empty() => {
+                            format!("best[height<=?{height}]")
+                        }
+                        _ => String::from("best"),
+                    }
+                }
+            },
+        }
+    }
+
+    /// The quality argument of streamlink.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::quality::Quality;
+    ///
+    /// assert_eq!("720p", Quality::Height(720).streamlink_quality());
+    /// assert_eq!("audio_only", Quality::Height(0).streamlink_quality());
+    /// ```
+    #[must_use]
+    pub fn streamlink_quality(&self) -> String {
+        match self {
+            Self::Height(0) => String::from("audio_only"),
+            Self::Height(height) => format!("{height}p"),
+            Self::Named(name) => name.clone(),
+        }
+    }
+}
+
+impl From<u16> for Quality {
+    fn from(height: u16) -> Self {
+        Self::Height(height)
+    }
+}
+
+impl std::str::FromStr for Quality {
+    type Err = String;
+
+    /// Read a video height, or otherwise a quality name.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::quality::Quality;
+    ///
+    /// assert_eq!(Ok(Quality::Height(480)), "480".parse());
+    /// assert_eq!(Ok(Quality::Named(String::from("source"))), "source".parse());
+    /// assert!("".parse::<Quality>().is_err());
+    /// ```
+    fn from_str(quality: &str) -> Result<Self, Self::Err> {
+        let quality = quality.trim();
+        if quality.is_empty() {
+            return Err(String::from("The quality is empty"));
+        }
+        Ok(quality
+            .parse()
+            .map_or_else(|_| Self::Named(quality.to_string()), Self::Height))
+    }
+}
+
+impl std::fmt::Display for Quality {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.streamlink_quality())
+    }
+}
+
+/// Chooses the quality streams are played at, see `event_handler::LaunchPolicy`.
+pub trait QualitySelector: Send + Sync {
+    /// The quality to play the stream `name` at. `config` is the configuration of the stream when
+    /// it is in the schedule and `profile` the profile of the settings.
+    fn select(&self, name: &str, config: Option<&StreamConfig>, profile: &(String, u16))
+        -> Quality;
+}
+
+/// Play streams at their quality override for the profile, or at the quality of the profile.
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeMap;
+/// use stream_cave::quality::{ProfileQuality, Quality, QualitySelector};
+/// use stream_cave::StreamConfig;
+///
+/// let mut config: StreamConfig = serde_json::from_str(
+///     r#"{"name":"kaicenat","id":0,"quality_overides":{},"streams_to_close_on":[],"streams_to_open_on":[]}"#,
+/// )
+/// .unwrap();
+/// config.quality_overides = BTreeMap::from([(String::from("mobile"), Quality::Height(360))]);
+///
+/// let profile = (String::from("mobile"), 480);
+/// assert_eq!(Quality::Height(360), ProfileQuality.select("kaicenat", Some(&config), &profile));
+/// assert_eq!(Quality::Height(480), ProfileQuality.select("jynxzi", None, &profile));
+/// ```
+pub struct ProfileQuality;
+
+impl QualitySelector for ProfileQuality {
+    fn select(
+        &self,
+        _name: &str,
+        config: Option<&StreamConfig>,
+        profile: &(String, u16),
+    ) -> Quality {
+        config
+            .and_then(|config| config.quality_overides.get(&profile.0))
+            .cloned()
+            .unwrap_or(Quality::Height(profile.1))
+    }
+}
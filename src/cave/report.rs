@@ -0,0 +1,139 @@
+use super::authentication::{token_status, TokenStatus};
+use super::notifier::NotifierConfig;
+use crate::{Settings, Streams};
+use serde::Serialize;
+use std::path::Path;
+
+/// The placeholder reported in place of credentials.
+const REDACTED: &str = "<redacted>";
+
+/// A snapshot of the configuration to attach to bug reports. The user token is only reported
+/// as its `TokenStatus` and the webhook urls of the notifiers are redacted.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub settings: Settings,
+    pub schedule: Streams,
+    pub token: TokenStatus,
+}
+
+impl Report {
+    /// Create a report of the running build with the given configuration, redacting the
+    /// credentials in `settings`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use stream_cave::authentication::TokenStatus;
+    /// use stream_cave::report::Report;
+    /// use stream_cave::{Settings, Streams};
+    ///
+    /// let report = Report::new(Settings::new(Path::new("./")), Streams::new(), TokenStatus::Absent);
+    ///
+    /// assert_eq!(env!("CARGO_PKG_VERSION"), report.version);
+    /// ```
+    #[must_use]
+    pub fn new(mut settings: Settings, schedule: Streams, token: TokenStatus) -> Self {
+        for notifier in &mut settings.notifiers {
+            if let NotifierConfig::Discord { webhook_url } = notifier {
+                *webhook_url = String::from(REDACTED);
+            }
+        }
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            settings,
+            schedule,
+            token,
+        }
+    }
+
+    /// Gather the report of the configuration in the `config` directory. The defaults are
+    /// reported when there is no config.json, and the token is checked once with twitch.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::path::Path;
+    /// use stream_cave::report::Report;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let report = Report::gather(Path::new("./")).await;
+    ///
+    ///     println!("{}", report.to_json().unwrap());
+    /// }
+    /// ```
+    pub async fn gather(config: &Path) -> Self {
+        let settings = if config.join("config.json").exists() {
+            Settings::read_config(&[config.to_path_buf()]).unwrap_or_else(|_| Settings::new(config))
+        } else {
+            Settings::new(config)
+        };
+        let schedule =
+            Streams::read_streams_with_includes(&settings.schedule).unwrap_or_else(|error| {
+                eprintln!("Error reading included schedules: {error}");
+                Streams::read_streams(&settings.schedule)
+            });
+        let token = token_status(&settings.token_file()).await;
+        Self::new(settings, schedule, token)
+    }
+
+    /// The report as a pretty printed json document.
+    ///
+    /// # Errors
+    /// Will return an error if the report cannot be serialized.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cave::authentication::token_status_with_client;
+    use crate::UserData;
+
+    #[tokio::test]
+    async fn redact_access_token() {
+        let directory = std::env::temp_dir().join("stream-cave-report");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut settings = Settings::new(&directory);
+        settings.notifiers = vec![NotifierConfig::Discord {
+            webhook_url: String::from("https://discord.com/api/webhooks/1/stream-cave-secret-hook"),
+        }];
+        UserData {
+            access_token: String::from("stream-cave-secret-token"),
+            login: String::from("kaicenat"),
+            user_id: String::from("641972806"),
+            scopes: Vec::new(),
+            refresh_token: None,
+            client_id: None,
+            client_secret: None,
+            in_keyring: false,
+        }
+        .save(&settings.token_file())
+        .unwrap();
+
+        let token =
+            token_status_with_client(&twitch_oauth2::client::DummyClient, &settings.token_file())
+                .await;
+        let missing = token_status_with_client(
+            &twitch_oauth2::client::DummyClient,
+            &directory.join("missing.json"),
+        )
+        .await;
+        let schedule = Streams::read_streams(Path::new("./tests/resources"));
+        let report = Report::new(settings, schedule, token).to_json().unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(TokenStatus::Absent, missing);
+        assert!(!report.contains("stream-cave-secret-token"));
+        assert!(!report.contains("stream-cave-secret-hook"));
+        assert!(report.contains(REDACTED));
+        assert!(report.contains(r#""token": "unverified""#));
+        assert!(report.contains("kaicenat"));
+    }
+}
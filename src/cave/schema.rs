@@ -0,0 +1,105 @@
+use schemars::{schema_for, Schema};
+
+use super::{Settings, StreamConfig, Streams};
+
+/// Generate the JSON schema of the `config.json` settings file.
+///
+/// # Examples
+/// ```
+/// use stream_cave::schema::config_schema;
+///
+/// let schema = config_schema();
+/// assert_eq!(Some("Settings"), schema.get("title").and_then(|title| title.as_str()));
+/// ```
+#[must_use]
+pub fn config_schema() -> Schema {
+    schema_for!(Settings)
+}
+
+/// Generate the JSON schema of the `schedule.json` streams file.
+///
+/// # Examples
+/// ```
+/// use stream_cave::schema::schedule_schema;
+///
+/// let schema = schedule_schema();
+/// assert_eq!(Some("Streams"), schema.get("title").and_then(|title| title.as_str()));
+/// ```
+#[must_use]
+pub fn schedule_schema() -> Schema {
+    schema_for!(Streams)
+}
+
+/// Generate the JSON schema of a single stream entry of the schedule.
+///
+/// # Examples
+/// ```
+/// use stream_cave::schema::stream_config_schema;
+///
+/// let schema = stream_config_schema();
+/// assert_eq!(Some("StreamConfig"), schema.get("title").and_then(|title| title.as_str()));
+/// ```
+#[must_use]
+pub fn stream_config_schema() -> Schema {
+    schema_for!(StreamConfig)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_fixture_matches_schema() {
+        let schema = serde_json::to_value(schedule_schema()).unwrap();
+        let schedule: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("./tests/resources/schedule.json").unwrap(),
+        )
+        .unwrap();
+
+        assert!(jsonschema::is_valid(&schema, &schedule));
+    }
+
+    #[test]
+    fn legacy_schedule_fixture_matches_schema() {
+        let schema = serde_json::to_value(schedule_schema()).unwrap();
+        let schedule: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("./tests/resources/legacy/schedule.json").unwrap(),
+        )
+        .unwrap();
+        let pairs = serde_json::json!({
+            "streams": [{
+                "name": "kaicenat",
+                "id": 641_972_806,
+                "quality_overides": [["normal"]],
+                "streams_to_close_on": [],
+                "streams_to_open_on": []
+            }]
+        });
+
+        assert!(jsonschema::is_valid(&schema, &schedule));
+        assert!(!jsonschema::is_valid(&schema, &pairs));
+        assert_eq!(
+            Streams::read_streams(std::path::Path::new("./tests/resources")).streams,
+            Streams::read_streams(std::path::Path::new("./tests/resources/legacy")).streams
+        );
+    }
+
+    #[test]
+    fn config_fixture_matches_schema() {
+        let schema = serde_json::to_value(config_schema()).unwrap();
+        let config: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("./tests/resources/config.json").unwrap(),
+        )
+        .unwrap();
+
+        assert!(jsonschema::is_valid(&schema, &config));
+    }
+
+    #[test]
+    fn invalid_schedule_fails_schema() {
+        let schema = serde_json::to_value(schedule_schema()).unwrap();
+        let schedule = serde_json::json!({ "streams": [{ "name": "kaicenat" }] });
+
+        assert!(!jsonschema::is_valid(&schema, &schedule));
+    }
+}
@@ -1,69 +1,768 @@
-use crate::cave::twitch_socket::api_structs;
-use std::{process::ExitStatus, sync::Arc, time::Duration};
+use crate::cave::{http, twitch_socket::api_structs};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, SystemTime},
+};
 use twitch_oauth2::UserToken;
 
-use crate::{cave::player, Player};
+use crate::{
+    cave::{
+        control::{Retry, RetryReason, SocketEvent},
+        events::{LiveDetails, StreamEvent},
+        player::{self, ExitKind, PlayerOptions},
+        quality::Quality,
+    },
+    Latency, Player,
+};
 use tokio::{
-    sync::mpsc::{Receiver, Sender},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::{
+        broadcast,
+        mpsc::{Receiver, Sender},
+        oneshot,
+    },
     task,
-    time::sleep,
+    time::{sleep, Instant},
 };
 
+/// Restarts of the same stream within this window count as repeated crashes.
+pub const RETRY_WINDOW: Duration = Duration::from_mins(5);
+/// Failed live status requests after which a crashed stream is restarted without its status.
+const MAX_STATUS_ATTEMPTS: u32 = 5;
+
+/// A stream to be played by `task_spawner`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamTask {
+    /// The login name of the streamer
+    pub name: String,
+    /// The display name of the streamer, may differ from the login in capitalization
+    pub display_name: String,
+    /// The broadcaster id of the streamer, 0 if unknown
+    pub id: u32,
+    /// The quality to play
+    pub quality: Quality,
+    /// Extra arguments passed to the player
+    pub player_args: Vec<String>,
+    /// The player to play with, the player of the `PlayerLaunch` when unset
+    pub player: Option<Player>,
+    /// Record the stream to the `recording_dir` of the `PlayerLaunch`
+    pub record: bool,
+    /// Allow the player to resume from the last playback position
+    pub resume: bool,
+    /// The latency requested from the player
+    pub latency: Latency,
+}
+
+/// The streams api used to look up the viewer count, start time and category of streams going
+/// live.
+#[derive(Debug, Clone)]
+pub struct LiveDetailsLookup {
+    pub streams_api_url: String,
+    pub user_access_token: Arc<Option<UserToken>>,
+    pub client_id: String,
+}
+
+/// How `task_spawner` launches the players.
+#[derive(Debug, Clone)]
+pub struct PlayerLaunch {
+    pub player: Player,
+    pub options: PlayerOptions,
+    /// The website the name of the stream is appended to
+    pub website: String,
+    /// The directory the output of each player is written to as `<name>.log`
+    pub log_dir: Option<PathBuf>,
+    /// The directory the recordings of tasks with `record` are written to
+    pub recording_dir: PathBuf,
+    /// Split the recordings of streamlink into segments of this many minutes with ffmpeg
+    pub record_max_minutes: Option<u32>,
+    /// Start the players minimized
+    pub minimized: bool,
+    /// Kill players that have not started playing within this time, they are handled as players
+    /// that exited with an error. Ignored for players not reporting playback, such as VLC
+    pub startup_timeout: Option<Duration>,
+}
+
+impl PlayerLaunch {
+    /// Launch `player` with the default options, playing streams from `website`.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::PlayerLaunch;
+    /// use stream_cave::Player;
+    ///
+    /// let launch = PlayerLaunch::new(Player::Mpv, String::from("https://www.twitch.tv/"));
+    ///
+    /// assert!(launch.startup_timeout.is_none());
+    /// ```
+    #[must_use]
+    pub fn new(player: Player, website: String) -> Self {
+        Self {
+            player,
+            options: PlayerOptions::default(),
+            website,
+            log_dir: None,
+            recording_dir: PathBuf::from("."),
+            record_max_minutes: None,
+            minimized: false,
+            startup_timeout: None,
+        }
+    }
+
+    /// Launch `player` instead of the player of the launch. The `path` and `extra_args` of the
+    /// options are only kept for the same player, they are set for the player of the settings.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::PathBuf;
+    /// use stream_cave::tasks_handler::PlayerLaunch;
+    /// use stream_cave::Player;
+    ///
+    /// let mut launch = PlayerLaunch::new(Player::Mpv, String::from("https://www.twitch.tv/"));
+    /// launch.options.path = Some(PathBuf::from("/opt/mpv/bin/mpv"));
+    ///
+    /// let launch = launch.with_player(Player::Streamlink);
+    /// assert_eq!(Player::Streamlink, launch.player);
+    /// assert!(launch.options.path.is_none());
+    /// ```
+    #[must_use]
+    pub fn with_player(mut self, player: Player) -> Self {
+        if player != self.player {
+            self.player = player;
+            self.options.path = None;
+            self.options.extra_args.clear();
+        }
+        self
+    }
+
+    fn log_file(&self, name: &str) -> Option<PathBuf> {
+        self.log_dir
+            .as_ref()
+            .map(|directory| directory.join(format!("{name}.log")))
+    }
+
+    /// The length of the segments of the recording of `stream_task`, if it is split.
+    fn segment_minutes(&self, stream_task: &StreamTask) -> Option<u32> {
+        self.record_max_minutes
+            .filter(|_| stream_task.record && self.player == Player::Streamlink)
+    }
+
+    /// The startup timeout of the player of `stream_task`, if the player reports the start of
+    /// playback. Streamlink writing a split recording to its stdout does not report it.
+    fn playback_timeout(&self, stream_task: &StreamTask) -> Option<Duration> {
+        self.startup_timeout
+            .filter(|_| player::reports_playback(self.player))
+            .filter(|_| self.segment_minutes(stream_task).is_none())
+    }
+
+    /// The arguments recording `stream_task` to new files of the recording directory, and the
+    /// ffmpeg command splitting a recording written to the stdout of the player into segments.
+    /// No arguments if the player cannot record or the directory cannot be created.
+    fn recording(&self, stream_task: &StreamTask) -> (Vec<String>, Option<Command>) {
+        let now = SystemTime::now();
+        let segment_minutes = self.segment_minutes(stream_task);
+        let file = match segment_minutes {
+            Some(_) => player::segment_template(&self.recording_dir, &stream_task.name, now),
+            None => player::recording_file(&self.recording_dir, &stream_task.name, now),
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(error) => {
+                eprintln!(
+                    "Error while creating recording directory {}, not recording {}: {error}",
+                    self.recording_dir.display(),
+                    stream_task.display_name
+                );
+                return (Vec::new(), None);
+            }
+        };
+        eprintln!(
+            "Recording {} to {}",
+            stream_task.display_name,
+            file.display()
+        );
+        if let Some(max_minutes) = segment_minutes {
+            let segmenter = player::segment_command(&file, max_minutes);
+            return (vec![String::from("--stdout")], Some(segmenter));
+        }
+        let args = player::record_args(self.player, &file);
+        if args.is_empty() {
+            eprintln!(
+                "{:?} cannot record streams, not recording {}",
+                self.player, stream_task.display_name
+            );
+        }
+        (args, None)
+    }
+
+    /// The player command playing `stream` for `stream_task`, with the output redirected as
+    /// configured, and the command splitting its recording into segments which reads the stdout
+    /// of the player.
+    fn command(&self, stream_task: &StreamTask, stream: String) -> (Command, Option<Command>) {
+        let mut player_args = stream_task.player_args.clone();
+        if self.minimized {
+            player_args.extend(player::minimized_args(self.player));
+        }
+        if self.playback_timeout(stream_task).is_some() {
+            player_args.extend(player::playback_args(self.player));
+        }
+        let segmenter = if stream_task.record {
+            let (record_args, segmenter) = self.recording(stream_task);
+            player_args.extend(record_args);
+            segmenter
+        } else {
+            None
+        };
+        let mut command = player::player_command(
+            self.player,
+            stream,
+            &stream_task.quality,
+            stream_task.resume,
+            stream_task.latency,
+            &player_args,
+            &self.options,
+        );
+        if let Some(log_file) = self.log_file(&stream_task.name) {
+            if let Err(error) = player::log_output(&mut command, &log_file) {
+                eprintln!(
+                    "Error while creating player log {}: {error}",
+                    log_file.display()
+                );
+            }
+        }
+        if self.playback_timeout(stream_task).is_some() || segmenter.is_some() {
+            command.stdout(std::process::Stdio::piped());
+        }
+        command.kill_on_drop(true);
+        (command, segmenter)
+    }
+}
+
+/// The entry of a player in `ActivePlayers`. The player is closed when the guard is dropped, so
+/// dropping the registry terminates the players it tracks.
+#[derive(Debug)]
+struct PlayerGuard {
+    close_sender: Option<oneshot::Sender<()>>,
+}
+
+impl PlayerGuard {
+    /// Drop the guard leaving the player running.
+    fn release(mut self) {
+        self.close_sender = None;
+    }
+
+    fn is_closed(&self) -> bool {
+        self.close_sender
+            .as_ref()
+            .is_none_or(oneshot::Sender::is_closed)
+    }
+}
+
+impl Drop for PlayerGuard {
+    fn drop(&mut self) {
+        if let Some(close_sender) = self.close_sender.take() {
+            let _ = close_sender.send(());
+        }
+    }
+}
+
+/// The players started by `task_spawner` that are still running, by the login name of the
+/// streamer. The players are killed when the last clone of the registry is dropped.
+///
+/// Streams whose player was closed through the registry are snoozed, they are not played again
+/// until they go offline. While paused no stream is played.
+#[derive(Debug, Clone, Default)]
+pub struct ActivePlayers {
+    players: Arc<Mutex<HashMap<String, PlayerGuard>>>,
+    snoozed: Arc<Mutex<HashSet<String>>>,
+    paused: Arc<AtomicBool>,
+}
+
+impl ActivePlayers {
+    /// Register a player for `name`, returns the reciever of the close signal or `None` if a
+    /// player is already active for `name`.
+    pub(crate) fn start(&self, name: &str) -> Option<oneshot::Receiver<()>> {
+        let (close_sender, close_reciever) = oneshot::channel();
+        match self
+            .players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .entry(name.to_string())
+        {
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => {
+                entry.insert(PlayerGuard {
+                    close_sender: Some(close_sender),
+                });
+                Some(close_reciever)
+            }
+        }
+    }
+
+    /// A handle to the registry that does not keep the players running.
+    fn downgrade(&self) -> Weak<Mutex<HashMap<String, PlayerGuard>>> {
+        Arc::downgrade(&self.players)
+    }
+
+    fn finish(players: &Weak<Mutex<HashMap<String, PlayerGuard>>>, name: &str) {
+        let Some(players) = players.upgrade() else {
+            return;
+        };
+        let mut players = players.lock().expect("Mutex lock poisoned");
+        // The reciever of the finished player is gone, an open entry belongs to a player started
+        // after the entry of the finished player was cleared
+        if players.get(name).is_some_and(PlayerGuard::is_closed) {
+            players.remove(name);
+        }
+    }
+
+    /// The names of the streams with an active player, sorted.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(ActivePlayers::default().names().is_empty());
+    /// ```
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Remove the entry of `name`, or of all streams, without closing the players and return the
+    /// removed names. Stale entries left by players that are no longer running keep the stream
+    /// from being played again, a cleared stream is played on its next live event.
+    ///
+    /// # Errors
+    /// Will return an error if no player is active for `name`.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// let active_players = ActivePlayers::default();
+    /// assert!(active_players.clear(None).unwrap().is_empty());
+    /// assert!(active_players.clear(Some("kaicenat")).is_err());
+    /// ```
+    pub fn clear(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut players = self.players.lock().expect("Mutex lock poisoned");
+        let Some(name) = name else {
+            let mut names: Vec<String> = players
+                .drain()
+                .map(|(name, guard)| {
+                    guard.release();
+                    name
+                })
+                .collect();
+            drop(players);
+            names.sort();
+            return Ok(names);
+        };
+        let guard = players.remove(name);
+        drop(players);
+        guard
+            .ok_or_else(|| format!("No player is active for {name}"))?
+            .release();
+        Ok(vec![name.to_string()])
+    }
+
+    /// Close every player without snoozing its stream and return the closed names, sorted.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(ActivePlayers::default().close_all().is_empty());
+    /// ```
+    #[must_use]
+    pub fn close_all(&self) -> Vec<String> {
+        // Dropping the guards signals the players to close
+        let mut names: Vec<String> = self
+            .players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .drain()
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Check if a player is active for `name`.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(!ActivePlayers::default().is_active("kaicenat"));
+    /// ```
+    #[must_use]
+    pub fn is_active(&self, name: &str) -> bool {
+        self.players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .contains_key(name)
+    }
+
+    /// Kill the player of `name` and snooze the stream. The player is closed intentionally so it
+    /// is not restarted, or played again before the stream goes offline.
+    ///
+    /// # Errors
+    /// Will return an error if no player is active for `name`.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// let error = ActivePlayers::default().close("kaicenat").unwrap_err();
+    /// assert_eq!("No player is active for kaicenat", error.to_string());
+    /// ```
+    pub fn close(&self, name: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let guard = self
+            .players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .remove(name)
+            .ok_or_else(|| format!("No player is active for {name}"))?;
+        // Dropping the guard signals the player to close, the player already exited when the
+        // reciever is gone
+        drop(guard);
+        self.snoozed
+            .lock()
+            .expect("Mutex lock poisoned")
+            .insert(name.to_string());
+        Ok(())
+    }
+
+    /// Kill the player of `name` without snoozing the stream, so it can be played again right
+    /// away. Returns if a player was active.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(!ActivePlayers::default().stop("kaicenat"));
+    /// ```
+    #[must_use]
+    pub fn stop(&self, name: &str) -> bool {
+        // Dropping the guard signals the player to close
+        self.players
+            .lock()
+            .expect("Mutex lock poisoned")
+            .remove(name)
+            .is_some()
+    }
+
+    /// Check if `name` was closed and has not gone offline since.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(!ActivePlayers::default().is_snoozed("kaicenat"));
+    /// ```
+    #[must_use]
+    pub fn is_snoozed(&self, name: &str) -> bool {
+        self.snoozed
+            .lock()
+            .expect("Mutex lock poisoned")
+            .contains(name)
+    }
+
+    /// Let `name` be played again, called when the stream goes offline. Returns false if the
+    /// stream was not snoozed.
+    ///
+    /// # Panics
+    /// If the mutex lock is poisoned the function will panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(!ActivePlayers::default().unsnooze("kaicenat"));
+    /// ```
+    #[must_use]
+    pub fn unsnooze(&self, name: &str) -> bool {
+        self.snoozed
+            .lock()
+            .expect("Mutex lock poisoned")
+            .remove(name)
+    }
+
+    /// Stop playing streams going live until `resume` is called, the active players keep
+    /// playing. Returns false if already paused.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// let active_players = ActivePlayers::default();
+    ///
+    /// assert!(active_players.pause());
+    /// assert!(active_players.is_paused());
+    /// assert!(!active_players.pause());
+    /// ```
+    #[must_use]
+    pub fn pause(&self) -> bool {
+        !self.paused.swap(true, Ordering::SeqCst)
+    }
+
+    /// Play streams going live again after `pause`. Returns false if not paused.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::tasks_handler::ActivePlayers;
+    ///
+    /// assert!(!ActivePlayers::default().resume());
+    /// ```
+    #[must_use]
+    pub fn resume(&self) -> bool {
+        self.paused.swap(false, Ordering::SeqCst)
+    }
+
+    /// Check if playing streams is paused.
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
+
+/// The stream of a closed player, the player and the exit status of the player.
+pub type PlayerExit = (
+    StreamTask,
+    Player,
+    Result<std::process::ExitStatus, std::io::Error>,
+);
+
 /// Spawn video player. The configuration is based on what is recieved from `event_handler`. The
-/// result is sent to `exit_handler`.
+/// result is sent to `exit_handler`. The start and exit of each player is sent to
+/// `notification_handler`.
+///
+/// When `details_lookup` is given the viewer count and start time of the stream are looked up
+/// and attached to the live notification, the player is started without waiting for them.
+///
+/// The players are launched as set by the result of `launch`, which is called for every player so
+/// changed settings apply to the following players. Tasks with their own player are launched
+/// with `PlayerLaunch::with_player`.
+///
+/// Tasks for a stream that is already playing in `active_players` are ignored. Players closed
+/// through `active_players` are not sent to `exit_handler`.
+///
+/// On a `shutdown` signal no more streams are played, the players in `active_players` are killed
+/// and the function finishes once the players it started have exited.
+///
+/// # Panics
+/// If the mutex lock is poisoned the function will panic.
 ///
 /// # Examples
 /// ```no_run
-/// use stream_cave::tasks_handler::task_spawner;
-/// use stream_cave::Player;
+/// use stream_cave::tasks_handler::{task_spawner, ActivePlayers, PlayerLaunch, StreamTask};
+/// use stream_cave::{Latency, Player};
+/// use stream_cave::quality::Quality;
 ///
-/// use tokio::sync::mpsc;
+/// use tokio::sync::{broadcast, mpsc};
 ///
 /// #[tokio::main]
 /// async fn main() {
 ///     let (tx1, rx1) = mpsc::channel(5);
 ///     let (tx2, mut rx2) = mpsc::channel(5);
+///     let (notification_sender, _notification_reciever) = mpsc::channel(5);
 ///
-///     tx1.send((String::from("jynxzi"), 720)).await.unwrap();
-///     task_spawner(rx1, tx2, Player::Mpv, String::from("https://www.twitch.tv/")).await;
+///     let task = StreamTask {
+///         name: String::from("jynxzi"),
+///         display_name: String::from("jynxzi"),
+///         id: 411_377_640,
+///         quality: Quality::Height(720),
+///         player_args: Vec::new(),
+///         player: None,
+///         record: false,
+///         resume: false,
+///         latency: Latency::Normal,
+///     };
+///     tx1.send(task).await.unwrap();
+///     task_spawner(
+///         rx1,
+///         tx2,
+///         notification_sender,
+///         || PlayerLaunch::new(Player::Mpv, String::from("https://www.twitch.tv/")),
+///         None,
+///         ActivePlayers::default(),
+///         broadcast::channel(1).1,
+///     )
+///     .await;
 ///
 ///     let status = rx2.recv().await;
 /// }
 /// ```
 pub async fn task_spawner(
-    mut task_spawner_event_handler_reciever: Receiver<(String, u16)>,
-    task_spawner_exit_handler_sender: Sender<(
-        String,
-        Result<std::process::ExitStatus, std::io::Error>,
-    )>,
-    player: Player,
-    website: String,
+    mut task_spawner_event_handler_reciever: Receiver<StreamTask>,
+    task_spawner_exit_handler_sender: Sender<PlayerExit>,
+    task_spawner_notification_handler_sender: Sender<StreamEvent>,
+    launch: impl Fn() -> PlayerLaunch + Send,
+    details_lookup: Option<LiveDetailsLookup>,
+    active_players: ActivePlayers,
+    mut shutdown: broadcast::Receiver<()>,
 ) {
-    while let Some((streamer_name, quality)) = task_spawner_event_handler_reciever.recv().await {
-        let stream = format!("{}{}", website, streamer_name.clone());
-        let player_func = player::get_stream(player, stream, quality).await;
+    let mut player_tasks = task::JoinSet::new();
+    loop {
+        while player_tasks.try_join_next().is_some() {}
+        let stream_task = tokio::select! {
+            stream_task = task_spawner_event_handler_reciever.recv() => stream_task,
+            Ok(()) = shutdown.recv() => {
+                let closed = active_players.close_all();
+                if !closed.is_empty() {
+                    eprintln!("Closing the players of {}", closed.join(", "));
+                }
+                while player_tasks.join_next().await.is_some() {}
+                return;
+            }
+        };
+        let Some(stream_task) = stream_task else {
+            break;
+        };
+        let Some(close_reciever) = active_players.start(&stream_task.name) else {
+            eprintln!("{} is already playing", stream_task.display_name);
+            continue;
+        };
+        let mut launch = launch();
+        if let Some(player) = stream_task.player {
+            launch = launch.with_player(player);
+        }
+        let player = launch.player;
+        let startup_timeout = launch.playback_timeout(&stream_task);
+        let log_file = launch.log_file(&stream_task.name);
         let sender_clone = task_spawner_exit_handler_sender.clone();
-        task::spawn(async move {
+        let notification_sender = task_spawner_notification_handler_sender.clone();
+        let live_notification_sender = notification_sender.clone();
+        let live_task = stream_task.clone();
+        let live_lookup = details_lookup.clone();
+        let players = active_players.downgrade();
+        let live_notification = task::spawn(async move {
+            let details = match &live_lookup {
+                Some(lookup) => live_details(&live_task, lookup).await,
+                None => None,
+            };
+            let _ = live_notification_sender
+                .send(StreamEvent::Live {
+                    name: live_task.display_name,
+                    login: live_task.name,
+                    quality: live_task.quality,
+                    details,
+                })
+                .await;
+        });
+        player_tasks.spawn(async move {
+            let stream = player::playable_stream(
+                player,
+                format!("{}{}", launch.website, stream_task.name),
+                &stream_task.quality,
+                stream_task.latency,
+                &launch.options,
+            )
+            .await;
+            let (mut player_command, segmenter) = launch.command(&stream_task, stream);
+            let result = match player_command.spawn() {
+                Ok(mut child) => {
+                    if let Some(segmenter) = segmenter {
+                        split_recording(&mut child, segmenter, &stream_task.display_name);
+                    }
+                    let startup = startup_timeout
+                        .map(|timeout| (timeout, watch_playback(player, &mut child, log_file)));
+                    wait_for_player(child, close_reciever, startup).await
+                }
+                Err(error) => Some(Err(error)),
+            };
+            let _ = live_notification.await;
+            let Some(result) = result else {
+                let _ = notification_sender
+                    .send(StreamEvent::Closed {
+                        name: stream_task.display_name,
+                        login: stream_task.name,
+                        success: true,
+                    })
+                    .await;
+                return;
+            };
+            ActivePlayers::finish(&players, &stream_task.name);
+            let _ = notification_sender
+                .send(StreamEvent::Closed {
+                    name: stream_task.display_name.clone(),
+                    login: stream_task.name.clone(),
+                    success: result.as_ref().is_ok_and(std::process::ExitStatus::success),
+                })
+                .await;
             sender_clone
-                .send((streamer_name, player_func.await))
+                .send((stream_task, player, result))
                 .await
                 .unwrap_or_else(|error| {
                     eprintln!("Error while attempting to hand over player monitoring: {error}");
                 });
         });
     }
+    // The players keep running when the event handler closes, such as on a reconnect
+    player_tasks.detach_all();
 }
 
 /// Handle player exit. Based on the exit status of the player restart streams that close
 /// unexpectedly.
 ///
+/// Only players classified as `ExitKind::Crash` by `player::classify_exit` are restarted, players
+/// quit by the user or failing to launch are left closed.
+///
+/// The live status of the stream is checked with `streams_api_url` when the broadcaster id is
+/// known, otherwise the stream is looked up by name with `search_api_url`. Live streams are sent
+/// back as `retry` events with `RetryReason::Crash`, streams whose status cannot be checked after
+/// `MAX_STATUS_ATTEMPTS` failed requests with `RetryReason::StatusUnknown`. The attempt of the
+/// event counts the restarts of the stream within `RETRY_WINDOW`, a stream that is not restarted
+/// starts a new window.
+///
 /// # Examples
 /// ```no_run
-/// use stream_cave::tasks_handler::{task_spawner, exit_handler};
+/// use stream_cave::tasks_handler::{
+///     exit_handler, task_spawner, ActivePlayers, PlayerLaunch, StreamTask,
+/// };
 /// use stream_cave::authentication;
-/// use stream_cave::{Player, Settings};
+/// use stream_cave::{Latency, Player, Settings};
+/// use stream_cave::quality::Quality;
 ///
-/// use tokio::sync::mpsc;
+/// use tokio::sync::{broadcast, mpsc};
 /// use std::sync::Arc;
 /// use std::path::Path;
 ///
@@ -76,40 +775,110 @@ pub async fn task_spawner(
 ///     let (tx2, rx2) = mpsc::channel(5);
 ///     let (tx3, mut rx3) = mpsc::channel(5);
 ///     let (restart_sender, _) = mpsc::channel(1);
+///     let (notification_sender, _notification_reciever) = mpsc::channel(5);
 ///
-///     authentication::validate_oauth_token(&mut token, &settings.schedule, false).await.unwrap();
-///     tx1.send((String::from("jynxzi"), 720)).await.unwrap();
-///     task_spawner(rx1, tx2, Player::Mpv, String::from("https://www.twitch.tv/")).await;
+///     authentication::validate_oauth_token(&mut token, &settings.token_file(), false).await.unwrap();
+///     let task = StreamTask {
+///         name: String::from("jynxzi"),
+///         display_name: String::from("jynxzi"),
+///         id: 411_377_640,
+///         quality: Quality::Height(720),
+///         player_args: Vec::new(),
+///         player: None,
+///         record: false,
+///         resume: false,
+///         latency: Latency::Normal,
+///     };
+///     tx1.send(task).await.unwrap();
+///     task_spawner(
+///         rx1,
+///         tx2,
+///         notification_sender,
+///         || PlayerLaunch::new(Player::Mpv, String::from("https://www.twitch.tv/")),
+///         None,
+///         ActivePlayers::default(),
+///         broadcast::channel(1).1,
+///     )
+///     .await;
 ///     let twitch_user_token = Arc::new(token);
-///     exit_handler(rx2, tx3, restart_sender, String::from("https://api.twitch.tv/helix/search/channels"), twitch_user_token , CLIENT_ID).await;
+///     exit_handler(
+///         rx2,
+///         tx3,
+///         restart_sender,
+///         String::from("https://api.twitch.tv/helix/search/channels"),
+///         String::from("https://api.twitch.tv/helix/streams"),
+///         twitch_user_token,
+///         CLIENT_ID,
+///     )
+///     .await;
 ///
 ///     let retry_signal = rx3.recv().await;
 /// }
 /// ```
 pub async fn exit_handler(
-    mut exit_handler_task_spawner_reciever: Receiver<(
-        String,
-        Result<std::process::ExitStatus, std::io::Error>,
-    )>,
-    exit_handler_event_handler_sender: Sender<(String, String)>,
+    mut exit_handler_task_spawner_reciever: Receiver<PlayerExit>,
+    exit_handler_event_handler_sender: Sender<SocketEvent>,
     restart_signal_sender: Sender<u8>,
-    api_url: String,
+    search_api_url: String,
+    streams_api_url: String,
     user_access_token: Arc<Option<UserToken>>,
     client_id: &str,
 ) {
-    while let Some((stream_name, result)) = exit_handler_task_spawner_reciever.recv().await {
+    let mut retry_attempts = HashMap::new();
+    while let Some((stream_task, player, result)) = exit_handler_task_spawner_reciever.recv().await
+    {
         match result {
             Ok(exit_status) => {
-                handle_exit_status(
-                    stream_name,
-                    exit_status,
-                    &exit_handler_event_handler_sender,
-                    &restart_signal_sender,
-                    &api_url,
+                match player::classify_exit(player, exit_status) {
+                    ExitKind::Normal => {
+                        if stream_task.record {
+                            eprintln!("Finished recording {}", stream_task.display_name);
+                        }
+                        continue;
+                    }
+                    ExitKind::UserQuit => {
+                        eprintln!("{} was quit, not restarting", stream_task.display_name);
+                        continue;
+                    }
+                    ExitKind::LaunchFailure => {
+                        eprintln!(
+                            "Error {} failed to launch ({exit_status}), not restarting",
+                            stream_task.display_name
+                        );
+                        continue;
+                    }
+                    ExitKind::Crash => {}
+                }
+                let Some(request) = live_status_request(
+                    &stream_task,
+                    &search_api_url,
+                    &streams_api_url,
                     &user_access_token,
                     client_id,
-                )
-                .await;
+                ) else {
+                    eprintln!("Error attempting to access Twitch oauth2 token. No token found.");
+                    continue;
+                };
+                let Some(reason) =
+                    handle_exit_status(&stream_task, request, &restart_signal_sender).await
+                else {
+                    retry_attempts.remove(&stream_task.name);
+                    continue;
+                };
+                let retry = Retry {
+                    reason,
+                    attempt: next_attempt(&mut retry_attempts, &stream_task.name, Instant::now()),
+                };
+                exit_handler_event_handler_sender
+                    .send(SocketEvent::retry(
+                        &stream_task.name,
+                        &stream_task.display_name,
+                        retry,
+                    ))
+                    .await
+                    .unwrap_or_else(|error| {
+                        eprintln!("Error while attempting to restart stream: {error}");
+                    });
             }
             Err(error) => {
                 eprintln!("Error starting stream: {error}");
@@ -118,96 +887,323 @@ pub async fn exit_handler(
     }
 }
 
-async fn handle_exit_status<'a>(
-    stream_name: String,
-    exit_status: ExitStatus,
-    exit_handler_event_handler_sender: &'a Sender<(String, String)>,
-    restart_signal_sender: &'a Sender<u8>,
-    api_url: &'a String,
-    user_access_token: &'a Arc<Option<UserToken>>,
-    client_id: &'a str,
-) {
-    let Some(user_access_token) = (**user_access_token).as_ref() else {
-        eprintln!("Error attempting to access Twitch oauth2 token. No token found.");
-        return;
+/// Pipe the recording written to the stdout of `player` into `segmenter`. The segmenter is left
+/// running until it read the whole recording, so it finishes the last segment after the player
+/// exits.
+fn split_recording(player: &mut Child, mut segmenter: Command, display_name: &str) {
+    let segmenter = player
+        .stdout
+        .take()
+        .ok_or_else(|| std::io::Error::other("the stdout of the player is not piped"))
+        .and_then(|stdout| -> std::io::Result<std::process::Stdio> { stdout.try_into() })
+        .and_then(|stdout| segmenter.stdin(stdout).spawn());
+    let mut segmenter = match segmenter {
+        Ok(segmenter) => segmenter,
+        Err(error) => {
+            eprintln!("Error while splitting the recording of {display_name}: {error}");
+            return;
+        }
     };
+    let display_name = display_name.to_string();
+    task::spawn(async move {
+        match segmenter.wait().await {
+            Ok(status) if !status.success() => eprintln!(
+                "Error while splitting the recording of {display_name}: ffmpeg exited with {status}"
+            ),
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("Error while splitting the recording of {display_name}: {error}");
+            }
+        }
+    });
+}
 
-    if !exit_status.success() {
-        const MAX_WAIT_TIME: Duration = Duration::from_secs(180);
-        let mut wait_time = Duration::from_secs(1);
-        loop {
-            let request = reqwest::Client::new()
-                .get(api_url)
-                .query(&[("query", &stream_name)])
-                .bearer_auth(user_access_token.access_token.as_str())
-                .header("Client-Id", client_id)
-                .send()
-                .await;
+/// Wait for `player` to exit, or kill it when the close signal is recieved. Returns `None` if the
+/// player was closed by the signal.
+///
+/// With a `startup` timeout and signal the player is also killed when it has not signalled the
+/// start of playback in time, returning the exit status of the killed player.
+pub(crate) async fn wait_for_player(
+    mut player: Child,
+    close_reciever: oneshot::Receiver<()>,
+    startup: Option<(Duration, oneshot::Receiver<()>)>,
+) -> Option<Result<std::process::ExitStatus, std::io::Error>> {
+    let startup_timeout = async {
+        let Some((timeout, started)) = startup else {
+            return std::future::pending().await;
+        };
+        if tokio::time::timeout(timeout, started).await.is_ok() {
+            return std::future::pending().await;
+        }
+        timeout
+    };
+    tokio::select! {
+        result = player.wait() => Some(result),
+        Ok(()) = close_reciever => {
+            if let Err(error) = player.kill().await {
+                eprintln!("Error while closing player: {error}");
+            }
+            None
+        }
+        timeout = startup_timeout => {
+            eprintln!(
+                "Player did not start playing within {} seconds, killing it",
+                timeout.as_secs()
+            );
+            if let Err(error) = player.kill().await {
+                eprintln!("Error while killing player: {error}");
+            }
+            Some(player.wait().await)
+        }
+    }
+}
 
-            match request {
-                Ok(response) => {
-                    if response.status() == 200 {
-                        let search_results = response.json::<api_structs::StreamSearch>().await;
-                        let Ok(json_data) = search_results else {
-                            eprintln!("Error malformed response data recieved when checking stream status after player closed.");
-                            return;
-                        };
-
-                        let Some(stream_status) = json_data
-                            .data
-                            .iter()
-                            .find(|data_set| data_set.broadcaster_login == stream_name)
-                        else {
-                            eprintln!("Unable to find streamer after when checking stream status after player closed.");
-                            return;
-                        };
-                        let is_live = stream_status.is_live;
-
-                        if is_live {
-                            exit_handler_event_handler_sender
-                                .send((String::from("retry"), stream_name))
-                                .await
-                                .unwrap_or_else(|error| {
-                                    eprintln!("Error while attempting to restart stream: {error}");
-                                });
-                            return;
-                        }
-                    } else if response.status() == 401 {
-                        let _ = restart_signal_sender.send(2).await;
-                        return;
-                    } else {
-                        match response.text().await {
-                            Ok(text) => {
-                                eprintln!("Unexpected response: {text}");
-                                return;
-                            }
-                            Err(error) => {
-                                eprintln!("Error when parsing Twitch api respone text: {error}");
-                                return;
-                            }
-                        }
-                    }
+/// Forward the stdout of `child` to `log_file`, or to the stdout of the daemon without a log
+/// file. The returned reciever is signalled when `player` reports the start of playback.
+fn watch_playback(
+    player: Player,
+    child: &mut Child,
+    log_file: Option<PathBuf>,
+) -> oneshot::Receiver<()> {
+    let (started_sender, started_reciever) = oneshot::channel();
+    let Some(stdout) = child.stdout.take() else {
+        return started_reciever;
+    };
+    task::spawn(async move {
+        let mut log = match log_file {
+            Some(path) => tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(path)
+                .await
+                .inspect_err(|error| eprintln!("Error while opening player log: {error}"))
+                .ok(),
+            None => None,
+        };
+        let mut started_sender = Some(started_sender);
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if player::started_playing(player, &line) {
+                if let Some(sender) = started_sender.take() {
+                    let _ = sender.send(());
                 }
-                Err(error) => {
-                    wait_time = match wait_time.cmp(&MAX_WAIT_TIME) {
-                        std::cmp::Ordering::Less => {
-                            let time = wait_time * 2;
-                            if time > MAX_WAIT_TIME {
-                                MAX_WAIT_TIME
-                            } else {
-                                time
-                            }
-                        }
-                        std::cmp::Ordering::Equal => wait_time,
-                        std::cmp::Ordering::Greater => MAX_WAIT_TIME,
-                    };
+            }
+            match &mut log {
+                Some(file) => {
+                    let _ = file.write_all(format!("{line}\n").as_bytes()).await;
+                }
+                None => println!("{line}"),
+            }
+        }
+    });
+    started_reciever
+}
+
+/// Look up the viewer count and start time of the stream of `stream_task`.
+async fn live_details(stream_task: &StreamTask, lookup: &LiveDetailsLookup) -> Option<LiveDetails> {
+    let stream = live_stream(&stream_task.name, stream_task.id, lookup).await?;
+    Some(LiveDetails {
+        viewer_count: stream.viewer_count,
+        started_at: stream.started_at,
+    })
+}
+
+/// The category the stream `name` is live in, `None` if the stream cannot be looked up.
+pub(crate) async fn current_category(
+    name: &str,
+    id: u32,
+    lookup: &LiveDetailsLookup,
+) -> Option<String> {
+    live_stream(name, id, lookup)
+        .await
+        .map(|stream| stream.game_name)
+}
+
+/// Look up the live stream of `name` with the streams api, by the broadcaster `id` when it is
+/// known.
+async fn live_stream(
+    name: &str,
+    id: u32,
+    lookup: &LiveDetailsLookup,
+) -> Option<api_structs::StreamData> {
+    let user_access_token = (*lookup.user_access_token).as_ref()?;
+    let query = if id == 0 {
+        ("user_login", name.to_string())
+    } else {
+        ("user_id", id.to_string())
+    };
+    let response = http::client()
+        .get(&lookup.streams_api_url)
+        .query(&[query])
+        .bearer_auth(user_access_token.access_token.as_str())
+        .header("Client-Id", &lookup.client_id)
+        .send()
+        .await;
+    let data = match response {
+        Ok(response) if response.status() == 200 => {
+            response.json::<api_structs::StreamsResponse>().await
+        }
+        Ok(response) => {
+            eprintln!(
+                "Error {} while looking up details of {name}",
+                response.status()
+            );
+            return None;
+        }
+        Err(error) => {
+            eprintln!("Error while looking up details of {name}: {error}");
+            return None;
+        }
+    };
+    data.ok()?.data.into_iter().next()
+}
+
+fn live_status_request(
+    stream_task: &StreamTask,
+    search_api_url: &str,
+    streams_api_url: &str,
+    user_access_token: &Arc<Option<UserToken>>,
+    client_id: &str,
+) -> Option<reqwest::RequestBuilder> {
+    let user_access_token = (**user_access_token).as_ref()?;
+
+    let request = if stream_task.id == 0 {
+        http::client()
+            .get(search_api_url)
+            .query(&[("query", &stream_task.name)])
+    } else {
+        http::client()
+            .get(streams_api_url)
+            .query(&[("user_id", &stream_task.id.to_string())])
+    };
+
+    Some(
+        request
+            .bearer_auth(user_access_token.access_token.as_str())
+            .header("Client-Id", client_id),
+    )
+}
+
+async fn parse_live_status(stream_task: &StreamTask, response: reqwest::Response) -> Option<bool> {
+    if stream_task.id == 0 {
+        let Ok(json_data) = response.json::<api_structs::StreamSearch>().await else {
+            eprintln!("Error malformed response data recieved when checking stream status after player closed.");
+            return None;
+        };
+
+        let Some(stream_status) = json_data
+            .data
+            .iter()
+            .find(|data_set| data_set.broadcaster_login == stream_task.name)
+        else {
+            eprintln!(
+                "Unable to find streamer after when checking stream status after player closed."
+            );
+            return None;
+        };
+        Some(stream_status.is_live)
+    } else {
+        let Ok(json_data) = response.json::<api_structs::StreamsResponse>().await else {
+            eprintln!("Error malformed response data recieved when checking stream status after player closed.");
+            return None;
+        };
+
+        Some(
+            json_data
+                .data
+                .iter()
+                .any(|stream| stream.user_id == stream_task.id.to_string()),
+        )
+    }
+}
+
+/// The restarts of a stream within the current window.
+struct RetryAttempts {
+    window_start: Instant,
+    count: u32,
+}
+
+/// Record a restart of `name` and return its attempt within the window. The attempts of windows
+/// that have expired are dropped.
+fn next_attempt(
+    retry_attempts: &mut HashMap<String, RetryAttempts>,
+    name: &str,
+    now: Instant,
+) -> u32 {
+    retry_attempts.retain(|_, attempts| now.duration_since(attempts.window_start) <= RETRY_WINDOW);
+    let attempts = retry_attempts
+        .entry(name.to_string())
+        .or_insert(RetryAttempts {
+            window_start: now,
+            count: 0,
+        });
+    attempts.count += 1;
+    attempts.count
+}
+
+/// Check if the crashed stream of `stream_task` is still live with `request`, returns why it is
+/// restarted or `None` if it is not.
+async fn handle_exit_status(
+    stream_task: &StreamTask,
+    request: reqwest::RequestBuilder,
+    restart_signal_sender: &Sender<u8>,
+) -> Option<RetryReason> {
+    const MAX_WAIT_TIME: Duration = Duration::from_mins(3);
+    let mut wait_time = Duration::from_secs(1);
+    let mut attempts = 0;
+    loop {
+        let Some(request) = request.try_clone() else {
+            eprintln!("Error unable to create stream status request.");
+            return None;
+        };
 
+        match request.send().await {
+            Ok(response) => {
+                if response.status() == 200 {
+                    return (parse_live_status(stream_task, response).await == Some(true))
+                        .then_some(RetryReason::Crash);
+                } else if response.status() == 401 {
+                    let _ = restart_signal_sender.send(2).await;
+                    return None;
+                }
+                match response.text().await {
+                    Ok(text) => {
+                        eprintln!("Unexpected response: {text}");
+                        return None;
+                    }
+                    Err(error) => {
+                        eprintln!("Error when parsing Twitch api respone text: {error}");
+                        return None;
+                    }
+                }
+            }
+            Err(error) => {
+                attempts += 1;
+                if attempts >= MAX_STATUS_ATTEMPTS {
                     eprintln!(
-                        "Error {error}\n re-attempting api call for {stream_name}'s stream status in {} secs",
-                        wait_time.as_secs()
+                        "Error {error}\n unable to check {}'s stream status after {attempts} attempts, restarting it anyway",
+                        stream_task.name
                     );
-                    sleep(wait_time).await;
+                    return Some(RetryReason::StatusUnknown);
                 }
+                wait_time = match wait_time.cmp(&MAX_WAIT_TIME) {
+                    std::cmp::Ordering::Less => {
+                        let time = wait_time * 2;
+                        if time > MAX_WAIT_TIME {
+                            MAX_WAIT_TIME
+                        } else {
+                            time
+                        }
+                    }
+                    std::cmp::Ordering::Equal => wait_time,
+                    std::cmp::Ordering::Greater => MAX_WAIT_TIME,
+                };
+
+                eprintln!(
+                    "Error {error}\n re-attempting api call for {}'s stream status in {} secs",
+                    stream_task.name,
+                    wait_time.as_secs()
+                );
+                sleep(wait_time).await;
             }
         }
     }
@@ -218,6 +1214,9 @@ mod tests {
     use std::{str::FromStr, sync::Arc, time::Duration};
 
     use super::*;
+    use crate::cave::mock_api::{
+        helix_response, search_data, stream_data, stream_data_with_viewers, MockApi,
+    };
     use tokio::{
         process::Command,
         sync::mpsc,
@@ -291,29 +1290,294 @@ mod tests {
 
         let (exit_sender, mut exit_reciever) = mpsc::channel(10);
         let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, mut notification_reciever) = mpsc::channel(10);
         let exit_status = Command::new("ls").status().await;
 
-        let fake_streamer_name = String::from("video.mkv");
-        event_sender
-            .send((fake_streamer_name.clone(), 1080))
+        let fake_stream = StreamTask {
+            name: String::from("video.mkv"),
+            display_name: String::from("video.mkv"),
+            id: 0,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        event_sender.send(fake_stream.clone()).await.unwrap();
+
+        drop(event_sender);
+        task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            || PlayerLaunch::new(Player::Mpv, FILE.to_string()),
+            None,
+            ActivePlayers::default(),
+            broadcast::channel(1).1,
+        )
+        .await;
+
+        let (result_task, _, result_status) =
+            timeout(Duration::from_secs(15), exit_reciever.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(fake_stream, result_task);
+        assert_eq!(exit_status.unwrap(), result_status.unwrap());
+        assert_eq!(
+            Some(StreamEvent::Live {
+                name: String::from("video.mkv"),
+                login: String::from("video.mkv"),
+                quality: Quality::Height(1080),
+                details: None,
+            }),
+            notification_reciever.recv().await
+        );
+    }
+
+    /// The next live event, skipping the closed players.
+    async fn next_live(reciever: &mut Receiver<StreamEvent>) -> Option<StreamEvent> {
+        while let Some(event) = reciever.recv().await {
+            if matches!(event, StreamEvent::Live { .. }) {
+                return Some(event);
+            }
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn spawn_again_after_clearing_stale_player() {
+        let (exit_sender, mut exit_reciever) = mpsc::channel(10);
+        let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, mut notification_reciever) = mpsc::channel(10);
+        let active_players = ActivePlayers::default();
+        // A player that was killed without its exit being seen
+        drop(active_players.start("kaicenat").unwrap());
+        let task = |name: &str| StreamTask {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            id: 0,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        task::spawn(task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            || PlayerLaunch::new(Player::Mpv, String::from("tests/resources/missing/")),
+            None,
+            active_players.clone(),
+            broadcast::channel(1).1,
+        ));
+
+        event_sender.send(task("kaicenat")).await.unwrap();
+        event_sender.send(task("jynxzi")).await.unwrap();
+        let first = next_live(&mut notification_reciever).await;
+        let cleared = active_players.clear(Some("kaicenat")).unwrap();
+        event_sender.send(task("kaicenat")).await.unwrap();
+        let second = next_live(&mut notification_reciever).await;
+        let mut exited = Vec::new();
+        for _ in 0..2 {
+            let (exit_task, _, _) = timeout(Duration::from_secs(15), exit_reciever.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            exited.push(exit_task.name);
+        }
+        exited.sort();
+
+        let live = |name: &str| {
+            Some(StreamEvent::Live {
+                name: name.to_string(),
+                login: name.to_string(),
+                quality: Quality::Height(1080),
+                details: None,
+            })
+        };
+        assert_eq!(live("jynxzi"), first);
+        assert_eq!(vec![String::from("kaicenat")], cleared);
+        assert_eq!(live("kaicenat"), second);
+        assert_eq!(
+            vec![String::from("jynxzi"), String::from("kaicenat")],
+            exited
+        );
+    }
+
+    #[tokio::test]
+    async fn shutdown_kills_players_and_stops_spawner() {
+        use crate::cave::player::CustomPlayer;
+
+        let (exit_sender, mut exit_reciever) = mpsc::channel(10);
+        let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, mut notification_reciever) = mpsc::channel(10);
+        let (shutdown_sender, shutdown_reciever) = broadcast::channel(1);
+        let active_players = ActivePlayers::default();
+        let launch = || {
+            let mut launch = PlayerLaunch::new(Player::Custom, String::from("/dev/"));
+            launch.options.custom = Some(CustomPlayer {
+                template: String::from("tail -f {url}"),
+                audio_flag: String::new(),
+            });
+            launch
+        };
+        let task = |name: &str| StreamTask {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            id: 0,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        let spawner = task::spawn(task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            launch,
+            None,
+            active_players.clone(),
+            shutdown_reciever,
+        ));
+
+        event_sender.send(task("null")).await.unwrap();
+        next_live(&mut notification_reciever).await.unwrap();
+        shutdown_sender.send(()).unwrap();
+        timeout(Duration::from_secs(5), spawner)
             .await
+            .unwrap()
             .unwrap();
+        let closed = notification_reciever.recv().await;
 
-        drop(event_sender);
-        task_spawner(event_reciever, exit_sender, Player::Mpv, FILE.to_string()).await;
+        assert_eq!(
+            Some(StreamEvent::Closed {
+                name: String::from("null"),
+                login: String::from("null"),
+                success: true,
+            }),
+            closed
+        );
+        assert!(active_players.names().is_empty());
+        assert!(exit_reciever.recv().await.is_none());
+        assert!(event_sender.send(task("jynxzi")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn back_to_back_tasks_spawn_one_player() {
+        let (exit_sender, mut exit_reciever) = mpsc::channel(10);
+        let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, _notification_reciever) = mpsc::channel(10);
+        let task = StreamTask {
+            name: String::from("kaicenat"),
+            display_name: String::from("kaicenat"),
+            id: 0,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        task::spawn(task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            || PlayerLaunch::new(Player::Mpv, String::from("tests/resources/missing/")),
+            None,
+            ActivePlayers::default(),
+            broadcast::channel(1).1,
+        ));
 
-        let (result_name, result_status) = timeout(Duration::from_secs(15), exit_reciever.recv())
+        // The live event and the retry of the exit handler of a quickly restarted stream
+        event_sender.send(task.clone()).await.unwrap();
+        event_sender.send(task).await.unwrap();
+        let first = timeout(Duration::from_secs(15), exit_reciever.recv())
+            .await
+            .unwrap();
+        let second = timeout(Duration::from_secs(1), exit_reciever.recv()).await;
+
+        assert_eq!("kaicenat", first.unwrap().0.name);
+        assert!(second.is_err());
+    }
+
+    #[tokio::test]
+    async fn dropping_registry_kills_players() {
+        let active_players = ActivePlayers::default();
+        let close_reciever = active_players.start("kaicenat").unwrap();
+        let child = Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let player = task::spawn(wait_for_player(child, close_reciever, None));
+
+        drop(active_players);
+        let result = timeout(Duration::from_secs(5), player)
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(fake_streamer_name, result_name);
-        assert_eq!(exit_status.unwrap(), result_status.unwrap());
+
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn reconnect_does_not_duplicate_running_player() {
+        let active_players = ActivePlayers::default();
+        let task = StreamTask {
+            name: String::from("kaicenat"),
+            display_name: String::from("kaicenat"),
+            id: 0,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        let close_reciever = active_players.start(&task.name).unwrap();
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let running = task::spawn(wait_for_player(child, close_reciever, None));
+        // The pipeline of the new connection, catching up on the stream that is still playing
+        let (exit_sender, mut exit_reciever) = mpsc::channel(10);
+        let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, mut notification_reciever) = mpsc::channel(10);
+
+        event_sender.send(task.clone()).await.unwrap();
+        drop(event_sender);
+        task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            || PlayerLaunch::new(Player::Mpv, String::from("tests/resources/")),
+            None,
+            active_players.clone(),
+            broadcast::channel(1).1,
+        )
+        .await;
+        let spawned = exit_reciever.recv().await;
+        let notified = notification_reciever.recv().await;
+        active_players.close(&task.name).unwrap();
+        let closed = timeout(Duration::from_secs(5), running)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(spawned.is_none());
+        assert!(notified.is_none());
+        assert!(closed.is_none());
     }
 
     #[tokio::test]
     async fn handle_good_exit() {
         const PORT: u16 = 5421;
         let api_url = format!("http://localhost:{PORT}/mock/search/channels");
+        let streams_api_url = format!("http://localhost:{PORT}/mock/streams");
         let (process_sender, process_reciever) = mpsc::channel(10);
         let (exit_sender, exit_reciever) = mpsc::channel(10);
         let (restart_signal_sender, _) = mpsc::channel(1);
@@ -343,6 +1607,7 @@ mod tests {
                 exit_sender,
                 restart_signal_sender,
                 api_url,
+                streams_api_url,
                 user_access_token,
                 &client_id,
             )
@@ -351,7 +1616,18 @@ mod tests {
 
         process_sender
             .send((
-                String::from("fishermarston19"),
+                StreamTask {
+                    name: String::from("fishermarston19"),
+                    display_name: String::from("fishermarston19"),
+                    id: 0,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
                 Command::new("ls").status().await,
             ))
             .await
@@ -366,6 +1642,7 @@ mod tests {
     async fn handle_bad_exit() {
         const PORT: u16 = 5422;
         let api_url = format!("http://localhost:{PORT}/mock/search/channels");
+        let streams_api_url = format!("http://localhost:{PORT}/mock/streams");
         let (process_sender, process_reciever) = mpsc::channel(10);
         let (exit_sender, mut exit_reciever) = mpsc::channel(10);
         let (restart_signal_sender, _) = mpsc::channel(1);
@@ -395,6 +1672,7 @@ mod tests {
                 exit_sender,
                 restart_signal_sender,
                 api_url,
+                streams_api_url,
                 user_access_token,
                 &client_id,
             )
@@ -403,7 +1681,18 @@ mod tests {
 
         process_sender
             .send((
-                String::from("fishermarston19"),
+                StreamTask {
+                    name: String::from("fishermarston19"),
+                    display_name: String::from("fishermarston19"),
+                    id: 0,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
                 Command::new("ls").arg("nonexistent").status().await,
             ))
             .await
@@ -413,7 +1702,11 @@ mod tests {
             timeout(Duration::from_secs(15), exit_reciever.recv())
                 .await
                 .unwrap(),
-            Some((String::from("retry"), String::from("fishermarston19")))
+            Some(SocketEvent::retry(
+                "fishermarston19",
+                "fishermarston19",
+                FIRST_CRASH
+            ))
         );
         child.kill().await.unwrap();
         child.wait().await.unwrap();
@@ -423,6 +1716,7 @@ mod tests {
     async fn handle_bad_exit_stream_ended() {
         const PORT: u16 = 8502;
         let api_url = format!("http://localhost:{PORT}/mock/search/channels");
+        let streams_api_url = format!("http://localhost:{PORT}/mock/streams");
         let (process_sender, process_reciever) = mpsc::channel(10);
         let (exit_sender, exit_reciever) = mpsc::channel(10);
         let (restart_signal_sender, _) = mpsc::channel(1);
@@ -452,6 +1746,7 @@ mod tests {
                 exit_sender,
                 restart_signal_sender,
                 api_url,
+                streams_api_url,
                 user_access_token,
                 &client_id,
             )
@@ -460,7 +1755,18 @@ mod tests {
 
         process_sender
             .send((
-                String::from("gordongordon358"),
+                StreamTask {
+                    name: String::from("gordongordon358"),
+                    display_name: String::from("gordongordon358"),
+                    id: 0,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
                 Command::new("ls").arg("nonexistent").status().await,
             ))
             .await
@@ -475,6 +1781,7 @@ mod tests {
     async fn handle_no_internet_exit() {
         const PORT: u16 = 8423;
         let api_url = format!("http://localhost:{PORT}/mock/search/channels");
+        let streams_api_url = format!("http://localhost:{PORT}/mock/streams");
         let (process_sender, process_reciever) = mpsc::channel(10);
         let (exit_sender, exit_reciever) = mpsc::channel(10);
         let (restart_signal_sender, _) = mpsc::channel(1);
@@ -507,7 +1814,18 @@ mod tests {
 
         process_sender
             .send((
-                String::from("fishermarston19"),
+                StreamTask {
+                    name: String::from("fishermarston19"),
+                    display_name: String::from("fishermarston19"),
+                    id: 0,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
                 Command::new("ls").arg("nonexistent").status().await,
             ))
             .await
@@ -520,6 +1838,7 @@ mod tests {
                 exit_sender,
                 restart_signal_sender,
                 api_url,
+                streams_api_url,
                 user_access_token,
                 &client_id,
             ),
@@ -529,4 +1848,447 @@ mod tests {
 
         assert!(exit_reciever.is_empty());
     }
+
+    const FIRST_CRASH: Retry = Retry {
+        reason: RetryReason::Crash,
+        attempt: 1,
+    };
+
+    fn crashed_task(name: &str, id: u32) -> StreamTask {
+        StreamTask {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            id,
+            quality: Quality::Height(1080),
+            player_args: Vec::new(),
+            player: None,
+            record: false,
+            resume: false,
+            latency: Latency::Normal,
+        }
+    }
+
+    #[test]
+    fn attempts_increase_within_window() {
+        let mut retry_attempts = HashMap::new();
+        let start = Instant::now();
+
+        let attempts: Vec<u32> = (0..3)
+            .map(|second| {
+                next_attempt(
+                    &mut retry_attempts,
+                    "kaicenat",
+                    start + Duration::from_secs(second),
+                )
+            })
+            .collect();
+
+        assert_eq!(vec![1, 2, 3], attempts);
+        assert_eq!(1, next_attempt(&mut retry_attempts, "jynxzi", start));
+        assert_eq!(
+            1,
+            next_attempt(
+                &mut retry_attempts,
+                "kaicenat",
+                start + RETRY_WINDOW + Duration::from_secs(4)
+            )
+        );
+        assert_eq!(vec!["kaicenat"], retry_attempts.keys().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn offline_stream_starts_new_window() {
+        use std::sync::atomic::AtomicBool;
+
+        let live = Arc::new(AtomicBool::new(true));
+        let mock_live = live.clone();
+        let mock = MockApi::start(move |_| {
+            let data = if mock_live.load(Ordering::SeqCst) {
+                vec![stream_data("kaicenat", 641_972_806)]
+            } else {
+                Vec::new()
+            };
+            (200, helix_response(&data))
+        })
+        .await;
+        let (process_sender, mut exit_reciever) = start_exit_handler(&mock);
+        let crash = || async {
+            (
+                crashed_task("kaicenat", 641_972_806),
+                Player::Mpv,
+                Command::new("ls").arg("nonexistent").status().await,
+            )
+        };
+
+        let mut attempts = Vec::new();
+        for _ in 0..2 {
+            process_sender.send(crash().await).await.unwrap();
+            let retry = timeout(Duration::from_secs(15), exit_reciever.recv()).await;
+            attempts.push(retry.unwrap().unwrap().retry.unwrap().attempt);
+        }
+        live.store(false, Ordering::SeqCst);
+        process_sender.send(crash().await).await.unwrap();
+        while mock.requests().len() < 3 {
+            task::yield_now().await;
+        }
+        live.store(true, Ordering::SeqCst);
+        process_sender.send(crash().await).await.unwrap();
+        let retry = timeout(Duration::from_secs(15), exit_reciever.recv()).await;
+        attempts.push(retry.unwrap().unwrap().retry.unwrap().attempt);
+
+        assert_eq!(vec![1, 2, 1], attempts);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_with_unknown_status_after_failed_requests() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let (restart_signal_sender, _) = mpsc::channel(1);
+        let request = http::client().get(format!("http://{address}/streams"));
+
+        let start = Instant::now();
+        let reason = handle_exit_status(
+            &crashed_task("kaicenat", 641_972_806),
+            request,
+            &restart_signal_sender,
+        )
+        .await;
+
+        assert_eq!(Some(RetryReason::StatusUnknown), reason);
+        assert!(start.elapsed() >= Duration::from_secs(30));
+    }
+
+    fn start_exit_handler(
+        mock: &MockApi,
+    ) -> (mpsc::Sender<PlayerExit>, mpsc::Receiver<SocketEvent>) {
+        let (process_sender, process_reciever) = mpsc::channel(10);
+        let (exit_sender, exit_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, _) = mpsc::channel(1);
+        let search_api_url = mock.url("/search/channels");
+        let streams_api_url = mock.url("/streams");
+
+        task::spawn(async move {
+            exit_handler(
+                process_reciever,
+                exit_sender,
+                restart_signal_sender,
+                search_api_url,
+                streams_api_url,
+                Arc::new(Some(crate::cave::mock_api::unchecked_token())),
+                "AAAA",
+            )
+            .await;
+        });
+
+        (process_sender, exit_reciever)
+    }
+
+    #[tokio::test]
+    async fn handle_bad_exit_with_known_id() {
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                helix_response(&[stream_data("testbroadcaster", 30_423_375)]),
+            )
+        })
+        .await;
+        let (process_sender, mut exit_reciever) = start_exit_handler(&mock);
+
+        process_sender
+            .send((
+                StreamTask {
+                    name: String::from("testbroadcaster"),
+                    display_name: String::from("testbroadcaster"),
+                    id: 30_423_375,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
+                Command::new("ls").arg("nonexistent").status().await,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            timeout(Duration::from_secs(15), exit_reciever.recv())
+                .await
+                .unwrap(),
+            Some(SocketEvent::retry(
+                "testbroadcaster",
+                "testbroadcaster",
+                FIRST_CRASH
+            ))
+        );
+        assert_eq!(
+            vec![String::from("GET /streams?user_id=30423375 HTTP/1.1")],
+            mock.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn handle_bad_exit_with_unknown_id() {
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                helix_response(&[search_data("testbroadcaster", 30_423_375, true)]),
+            )
+        })
+        .await;
+        let (process_sender, mut exit_reciever) = start_exit_handler(&mock);
+
+        process_sender
+            .send((
+                StreamTask {
+                    name: String::from("testbroadcaster"),
+                    display_name: String::from("testbroadcaster"),
+                    id: 0,
+                    quality: Quality::Height(1080),
+                    player_args: Vec::new(),
+                    player: None,
+                    record: false,
+                    resume: false,
+                    latency: Latency::Normal,
+                },
+                Player::Mpv,
+                Command::new("ls").arg("nonexistent").status().await,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            timeout(Duration::from_secs(15), exit_reciever.recv())
+                .await
+                .unwrap(),
+            Some(SocketEvent::retry(
+                "testbroadcaster",
+                "testbroadcaster",
+                FIRST_CRASH
+            ))
+        );
+        assert_eq!(
+            vec![String::from(
+                "GET /search/channels?query=testbroadcaster HTTP/1.1"
+            )],
+            mock.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn kill_player_that_does_not_start() {
+        let (_started_sender, started_reciever) = oneshot::channel();
+        let (_close_sender, close_reciever) = oneshot::channel();
+        let hanging = Command::new("sleep").arg("30").spawn().unwrap();
+        let (_close_sender_playing, close_reciever_playing) = oneshot::channel();
+        let mut playing = Command::new("sh")
+            .args(["-c", "echo 'stream-cave: playing'; sleep 1"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let playing_started = watch_playback(Player::Mpv, &mut playing, None);
+
+        let killed = timeout(
+            Duration::from_secs(5),
+            wait_for_player(
+                hanging,
+                close_reciever,
+                Some((Duration::from_millis(200), started_reciever)),
+            ),
+        )
+        .await
+        .unwrap();
+        let finished = timeout(
+            Duration::from_secs(5),
+            wait_for_player(
+                playing,
+                close_reciever_playing,
+                Some((Duration::from_millis(200), playing_started)),
+            ),
+        )
+        .await
+        .unwrap();
+
+        assert!(!killed.unwrap().unwrap().success());
+        assert!(finished.unwrap().unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn recording_is_not_killed_at_startup_timeout() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join("stream-cave-record-timeout");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let streamlink = directory.join("streamlink");
+        std::fs::write(
+            &streamlink,
+            "#!/bin/sh\necho '[cli][info] Writing output to'\nsleep 1\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&streamlink, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut launch = PlayerLaunch::new(Player::Streamlink, String::from("/dev/"));
+        launch.options.path = Some(streamlink);
+        launch.recording_dir = directory.join("recordings");
+        launch.startup_timeout = Some(Duration::from_millis(200));
+        let task = StreamTask {
+            name: String::from("kaicenat"),
+            display_name: String::from("KaiCenat"),
+            id: 0,
+            quality: Quality::Height(720),
+            player_args: Vec::new(),
+            player: None,
+            record: true,
+            resume: false,
+            latency: Latency::Normal,
+        };
+        let (_close_sender, close_reciever) = oneshot::channel();
+
+        let (mut command, _) = launch.command(&task, String::from("/dev/null"));
+        let recording = command
+            .as_std()
+            .get_args()
+            .any(|arg| arg.to_string_lossy().starts_with("--output="));
+        let mut child = command.spawn().unwrap();
+        let started = watch_playback(launch.player, &mut child, None);
+        let startup = launch
+            .playback_timeout(&task)
+            .map(|timeout| (timeout, started));
+        let result = timeout(
+            Duration::from_secs(5),
+            wait_for_player(child, close_reciever, startup),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(recording);
+        assert!(result.unwrap().unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn split_streamlink_recordings() {
+        let directory = std::env::temp_dir().join("stream-cave-split-recording");
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut launch = PlayerLaunch::new(Player::Streamlink, String::from("/dev/"));
+        launch.recording_dir.clone_from(&directory);
+        launch.record_max_minutes = Some(30);
+        launch.startup_timeout = Some(Duration::from_secs(5));
+        let task = StreamTask {
+            name: String::from("kaicenat"),
+            display_name: String::from("KaiCenat"),
+            id: 0,
+            quality: Quality::Height(720),
+            player_args: Vec::new(),
+            player: None,
+            record: true,
+            resume: false,
+            latency: Latency::Normal,
+        };
+
+        let (command, segmenter) = launch.command(&task, String::from("/dev/null"));
+        let args: Vec<_> = command
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let segmenter = segmenter.unwrap();
+        let template = segmenter.as_std().get_args().last().unwrap().to_owned();
+        let output = directory.join("output.ts");
+        let mut player = Command::new("sh")
+            .args(["-c", "echo segment"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut copy = Command::new("sh");
+        copy.arg("-c").arg(format!("cat > {}", output.display()));
+        split_recording(&mut player, copy, "KaiCenat");
+        player.wait().await.unwrap();
+        timeout(Duration::from_secs(5), async {
+            while std::fs::read_to_string(&output).unwrap_or_default() != "segment\n" {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(args.contains(&String::from("--stdout")));
+        assert!(!args.iter().any(|arg| arg.starts_with("--output")));
+        assert!(launch.playback_timeout(&task).is_none());
+        assert_eq!("ffmpeg", segmenter.as_std().get_program());
+        assert!(template.to_string_lossy().ends_with("-%04d.ts"));
+        assert!(std::path::Path::new(&template).starts_with(&directory));
+    }
+
+    #[tokio::test]
+    async fn live_event_with_details() {
+        let mock = MockApi::start(|_| {
+            (
+                200,
+                helix_response(&[stream_data_with_viewers(
+                    "testbroadcaster",
+                    30_423_375,
+                    4321,
+                )]),
+            )
+        })
+        .await;
+        let (exit_sender, _exit_reciever) = mpsc::channel(10);
+        let (event_sender, event_reciever) = mpsc::channel(10);
+        let (notification_sender, mut notification_reciever) = mpsc::channel(10);
+
+        event_sender
+            .send(StreamTask {
+                name: String::from("testbroadcaster"),
+                display_name: String::from("TestBroadcaster"),
+                id: 30_423_375,
+                quality: Quality::Height(720),
+                player_args: Vec::new(),
+                player: None,
+                record: false,
+                resume: false,
+                latency: Latency::Normal,
+            })
+            .await
+            .unwrap();
+        drop(event_sender);
+        task_spawner(
+            event_reciever,
+            exit_sender,
+            notification_sender,
+            || PlayerLaunch::new(Player::Streamlink, mock.url("/")),
+            Some(LiveDetailsLookup {
+                streams_api_url: mock.url("/streams"),
+                user_access_token: Arc::new(Some(crate::cave::mock_api::unchecked_token())),
+                client_id: String::from("AAAA"),
+            }),
+            ActivePlayers::default(),
+            broadcast::channel(1).1,
+        )
+        .await;
+
+        assert_eq!(
+            Some(StreamEvent::Live {
+                name: String::from("TestBroadcaster"),
+                login: String::from("testbroadcaster"),
+                quality: Quality::Height(720),
+                details: Some(LiveDetails {
+                    viewer_count: 4321,
+                    started_at: String::from("2024-01-01T00:00:00Z"),
+                }),
+            }),
+            timeout(Duration::from_secs(15), notification_reciever.recv())
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![String::from("GET /streams?user_id=30423375 HTTP/1.1")],
+            mock.requests()
+        );
+    }
 }
@@ -1,6 +1,8 @@
 pub mod api_structs;
 
+use super::{control::SocketEvent, http, Streams};
 use std::{
+    net::SocketAddr,
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -9,27 +11,281 @@ use twitch_oauth2::UserToken;
 use futures_util::StreamExt;
 use tokio::{
     net::TcpStream,
-    sync::mpsc::{self, Receiver, Sender},
+    sync::{
+        broadcast,
+        mpsc::{self, Receiver, Sender},
+    },
     task::{self, yield_now},
     time::sleep,
 };
 use tokio_tungstenite::{
-    connect_async_tls_with_config,
-    tungstenite::{http::Response, Error, Message},
+    client_async_tls_with_config,
+    tungstenite::{
+        client::IntoClientRequest,
+        http::{
+            header::{HeaderValue, USER_AGENT},
+            Response, Uri,
+        },
+        Error, Message,
+    },
     MaybeTlsStream, WebSocketStream,
 };
 
+/// The cost of a subscription to the events of a broadcaster that has not authorized the app.
+pub const STREAM_ONLINE_COST: u32 = 1;
+
+/// The default maximum total cost of the subscriptions of a client id and user.
+pub const DEFAULT_MAX_SUBSCRIPTION_COST: u32 = 10;
+
+/// The percentage of the subscription cost limit over which a warning is logged.
+pub const COST_WARNING_PERCENT: u32 = 80;
+
+/// The total cost of the subscriptions and its limit as last reported by Twitch, shared by the
+/// tasks of the daemon.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionCost {
+    reported: Arc<Mutex<Option<(u32, u32)>>>,
+}
+
+impl SubscriptionCost {
+    /// The total cost and the maximum total cost of the subscriptions, `None` until Twitch
+    /// reported them for a created subscription.
+    ///
+    /// # Panics
+    /// Mutex lock poisoning will cause this function to panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::twitch_socket::SubscriptionCost;
+    ///
+    /// assert_eq!(None, SubscriptionCost::default().current());
+    /// ```
+    #[must_use]
+    pub fn current(&self) -> Option<(u32, u32)> {
+        *self.reported.lock().expect("Mutex lock poisoned")
+    }
+
+    /// Check that subscriptions costing `cost` can be added within the limit, always `true` while
+    /// the cost is unknown.
+    ///
+    /// # Panics
+    /// Mutex lock poisoning will cause this function to panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use stream_cave::twitch_socket::{SubscriptionCost, STREAM_ONLINE_COST};
+    ///
+    /// assert!(SubscriptionCost::default().allows(STREAM_ONLINE_COST));
+    /// ```
+    #[must_use]
+    pub fn allows(&self, cost: u32) -> bool {
+        self.current().is_none_or(|(total_cost, max_total_cost)| {
+            total_cost.saturating_add(cost) <= max_total_cost
+        })
+    }
+
+    /// Keep the costs reported by Twitch. Returns a warning the first time the total cost goes
+    /// over `COST_WARNING_PERCENT` of the limit.
+    fn update(&self, total_cost: u32, max_total_cost: u32) -> Option<String> {
+        let near_limit = |(total_cost, max_total_cost): (u32, u32)| {
+            u64::from(total_cost) * 100
+                > u64::from(max_total_cost) * u64::from(COST_WARNING_PERCENT)
+        };
+        let previous = self
+            .reported
+            .lock()
+            .expect("Mutex lock poisoned")
+            .replace((total_cost, max_total_cost));
+        (near_limit((total_cost, max_total_cost)) && !previous.is_some_and(near_limit)).then(|| {
+            format!(
+                "The subscriptions cost {total_cost} of the limit of {max_total_cost}, channels \
+                over the limit will not be watched"
+            )
+        })
+    }
+}
+
+/// Check that the subscriptions of every stream in `streams` stay within `max_total_cost`.
+/// Returns a warning for the user when the subscriptions would exceed the limit.
+///
+/// # Examples
+/// ```
+/// use stream_cave::{twitch_socket, Streams};
+///
+/// let streams = Streams::new();
+///
+/// assert!(twitch_socket::subscription_cost_warning(&streams, 10).is_none());
+/// ```
+#[must_use]
+pub fn subscription_cost_warning(streams: &Streams, max_total_cost: u32) -> Option<String> {
+    let channels = u32::try_from(streams.streams.len()).unwrap_or(u32::MAX);
+    let total_cost = streams.subscription_cost();
+    (total_cost > max_total_cost).then(|| {
+        format!(
+            "The schedule has {channels} channels costing {total_cost} but the subscription cost \
+            limit is {max_total_cost}, only some channels will be watched. Reduce the number of \
+            channels, split them across multiple sessions or raise `max_subscription_cost` if \
+            the limit of the app was raised"
+        )
+    })
+}
+
+/// Send a live event to `event_handler` for every stream of `streams` that is already live.
+///
+/// Stream online events are only sent on the transition to live, this catches up on the streams
+/// that went live before the daemon started. Returns the number of events sent.
+///
+/// # Errors
+/// Will return an error on failure of the streams request or if the event handler is closed.
+///
+/// # Examples
+/// ```no_run
+/// use tokio::sync::mpsc;
+/// use std::path::Path;
+/// use stream_cave::{authentication, twitch_socket, Settings, Streams};
+///
+/// #[tokio::main]
+/// async fn main(){
+///     const STREAMS_API: &str = "https://api.twitch.tv/helix/streams";
+///     const CLIENT_ID: &str = "AAAAAAAAA";
+///     let (event_handler_sender, event_handler_reciever) = mpsc::channel(5);
+///
+///     let mut token = None;
+///     let settings = Settings::new(&Path::new("./"));
+///     authentication::validate_oauth_token(&mut token, &settings.token_file(), false).await.unwrap();
+///     let streams = Streams::read_streams(&settings.schedule);
+///
+///     let launched = twitch_socket::catch_up_live_streams(&streams, &event_handler_sender,
+///     STREAMS_API, &token.unwrap(), CLIENT_ID).await.unwrap();
+/// }
+/// ```
+pub async fn catch_up_live_streams(
+    streams: &Streams,
+    twitch_websocket_event_handler_sender: &Sender<SocketEvent>,
+    streams_api_url: &str,
+    twitch_user_access_token: &UserToken,
+    client_id: &str,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let live_streams = streams
+        .live_streams(streams_api_url, client_id, twitch_user_access_token)
+        .await?;
+    for (stream, data) in &live_streams {
+        twitch_websocket_event_handler_sender
+            .send(SocketEvent::new(
+                api_structs::EventType::Live.as_str(),
+                &stream.name,
+                &data.user_name,
+            ))
+            .await?;
+    }
+    Ok(live_streams.len())
+}
+
+/// Delete the subscriptions listed at `api_url` that no longer send events, such as the ones of a
+/// disconnected websocket session. Returns the number of subscriptions deleted.
+///
+/// Inactive subscriptions still count towards the subscription cost limit until they are deleted.
+///
+/// # Errors
+/// Will return an error on failure of a request or on failure to deserialize the subscriptions.
+///
+/// # Examples
+/// ```no_run
+/// use std::path::Path;
+/// use stream_cave::{authentication, twitch_socket, Settings};
+///
+/// #[tokio::main]
+/// async fn main(){
+///     const TWITCH_API_URL: &str = "https://api.twitch.tv/helix/eventsub/subscriptions";
+///     const CLIENT_ID: &str = "AAAAAAAAA";
+///
+///     let mut token = None;
+///     let settings = Settings::new(&Path::new("./"));
+///     authentication::validate_oauth_token(&mut token, &settings.token_file(), false).await.unwrap();
+///
+///     let deleted = twitch_socket::delete_inactive_subscriptions(TWITCH_API_URL,
+///     &token.unwrap(), CLIENT_ID).await.unwrap();
+/// }
+/// ```
+pub async fn delete_inactive_subscriptions(
+    api_url: &str,
+    twitch_user_access_token: &UserToken,
+    client_id: &str,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let mut inactive = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut request = http::client()
+            .get(api_url)
+            .bearer_auth(twitch_user_access_token.access_token.as_str())
+            .header("Client-Id", client_id);
+        if let Some(cursor) = &cursor {
+            request = request.query(&[("after", cursor)]);
+        }
+        let response = request.send().await?;
+        if response.status() != 200 {
+            return Err(format!(
+                "Error {} while listing subscriptions: {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+
+        let page = response.json::<api_structs::SubscriptionList>().await?;
+        inactive.extend(
+            page.data
+                .into_iter()
+                .filter(|subscription| {
+                    subscription.status != api_structs::SubscriptionStatus::Enabled
+                        || subscription.transport.disconnected_at.is_some()
+                })
+                .map(|subscription| subscription.id),
+        );
+        cursor = page.pagination.and_then(|pagination| pagination.cursor);
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    for id in &inactive {
+        let response = http::client()
+            .delete(api_url)
+            .query(&[("id", id)])
+            .bearer_auth(twitch_user_access_token.access_token.as_str())
+            .header("Client-Id", client_id)
+            .send()
+            .await?;
+        if response.status() != 204 {
+            return Err(format!(
+                "Error {} while deleting subscription {id}: {}",
+                response.status(),
+                response.text().await?
+            )
+            .into());
+        }
+    }
+    Ok(inactive.len())
+}
+
 /// Create and manage twitch websocket connections and subscribe to twitch streamer live events.
 ///
-/// Uses id's recieved from `file_watcher` to subscribe to events. When encountering a websocket
-/// error or an invalid token it will send a signal through `restart_signal_sender`.
+/// Uses id's recieved from `file_watcher` to subscribe to events, after deleting the inactive
+/// subscriptions of earlier sessions. When encountering a websocket error or an invalid token it
+/// will send a signal through `restart_signal_sender`: `1` to reconnect, `2` when the token was
+/// rejected and `3` when the websocket could not be reached within `max_reconnect_attempts`,
+/// which retries forever when `None`.
+///
+/// The costs reported for the created subscriptions are kept in `subscription_cost`, events
+/// that would go over the limit are not subscribed to. The websocket is closed on a `shutdown`
+/// signal.
 ///
 /// # Panics
 /// Mutex lock poisoning will cause this function to panic.
 ///
 /// # Examples
 /// ```no_run
-/// use tokio::sync::mpsc;
+/// use tokio::sync::{broadcast, mpsc};
 /// use std::sync::Arc;
 /// use std::path::Path;
 /// use stream_cave::{Settings, twitch_socket, authentication};
@@ -49,17 +305,23 @@ use tokio_tungstenite::{
 ///
 ///     let twitch_user_access_token = Arc::new(token);
 ///     twitch_socket::twitch_websocket(file_watcher_reciever, event_handler_sender,
-///     restart_signal_sender, TWITCH_WEBSOCKET_URL, TWITCH_API_URL, twitch_user_access_token, CLIENT_ID);
+///     restart_signal_sender, TWITCH_WEBSOCKET_URL.to_string(), TWITCH_API_URL.to_string(),
+///     twitch_user_access_token, CLIENT_ID, settings.max_reconnect_attempts,
+///     twitch_socket::SubscriptionCost::default(), broadcast::channel(1).1);
 /// }
 /// ```
+#[allow(clippy::too_many_arguments)]
 pub fn twitch_websocket<'a: 'static>(
-    mut twitch_socket_file_watcher_reciever: Receiver<u32>,
-    twitch_websocket_event_handler_sender: Sender<(String, String)>,
+    mut twitch_socket_file_watcher_reciever: Receiver<(u32, Vec<&'static str>)>,
+    twitch_websocket_event_handler_sender: Sender<SocketEvent>,
     restart_signal_sender: Sender<u8>,
-    twitch_websocket_url: &'a str,
-    twitch_api_url: &'a str,
+    twitch_websocket_url: String,
+    twitch_api_url: String,
     twitch_user_access_token: Arc<Option<UserToken>>,
     client_id: &'a str,
+    max_reconnect_attempts: Option<u32>,
+    subscription_cost: SubscriptionCost,
+    shutdown: broadcast::Receiver<()>,
 ) {
     let restart_signal_sender_clone = restart_signal_sender.clone();
     let (first_name_signal_sender, mut first_name_signal_reciever) = mpsc::channel(1);
@@ -70,10 +332,13 @@ pub fn twitch_websocket<'a: 'static>(
     task::spawn(async move {
         if first_name_signal_reciever.recv().await == Some(()) {
             parse_stream_message(
-                twitch_websocket_url,
+                &twitch_websocket_url,
                 websocket_session_id,
                 twitch_websocket_event_handler_sender,
                 &restart_signal_sender,
+                SIGNAL_TIMEOUT,
+                max_reconnect_attempts,
+                shutdown,
             )
             .await;
         }
@@ -92,44 +357,76 @@ pub fn twitch_websocket<'a: 'static>(
             {
                 sleep(Duration::from_secs(1)).await;
             }
-            while let Some(id) = twitch_socket_file_watcher_reciever.recv().await {
-                subscribe_to_event(
-                    &restart_signal_sender_clone,
-                    twitch_api_url,
-                    &twitch_user_access_token,
-                    id,
-                    &session_id_clone,
-                    client_id,
-                )
-                .await;
+            if let Some(token) = (*twitch_user_access_token).as_ref() {
+                match delete_inactive_subscriptions(&twitch_api_url, token, client_id).await {
+                    Ok(0) => {}
+                    Ok(deleted) => eprintln!("Deleted {deleted} inactive subscriptions"),
+                    Err(error) => eprintln!("Error while deleting inactive subscriptions: {error}"),
+                }
+            }
+            while let Some((id, subscription_types)) =
+                twitch_socket_file_watcher_reciever.recv().await
+            {
+                for subscription_type in subscription_types {
+                    if !subscription_cost.allows(STREAM_ONLINE_COST) {
+                        eprintln!(
+                            "Not subscribing to {subscription_type} events of {id}, the \
+                            subscription cost limit is reached"
+                        );
+                        continue;
+                    }
+                    subscribe_to_event(
+                        &restart_signal_sender_clone,
+                        &twitch_api_url,
+                        &twitch_user_access_token,
+                        (id, subscription_type),
+                        &session_id_clone,
+                        client_id,
+                        &subscription_cost,
+                    )
+                    .await;
+                }
             }
         }
     });
 }
 
+/// How long to wait for a message, keepalives included, before reconnecting.
+const SIGNAL_TIMEOUT: Duration = Duration::new(15, 0);
+
 async fn parse_stream_message(
     websocket_url: &str,
     websocket_session_id: Arc<Mutex<String>>,
-    twitch_websocket_event_handler_sender: Sender<(String, String)>,
+    twitch_websocket_event_handler_sender: Sender<SocketEvent>,
     restart_signal_sender: &Sender<u8>,
+    signal_timeout: Duration,
+    max_reconnect_attempts: Option<u32>,
+    mut shutdown: broadcast::Receiver<()>,
 ) {
-    let connector = tokio_tungstenite::Connector::NativeTls(
-        native_tls::TlsConnector::new().expect("Unable to find TLS configuration"),
-    );
-    let (mut ws_stream, _) =
-        match connect_async_tls_with_config(websocket_url, None, false, Some(connector)).await {
-            Ok(conect) => conect,
-            Err(error) => {
-                eprintln!("Error: {error}, reconnecting");
-                reconnect_websocket(websocket_url).await
-            }
-        };
+    let (mut ws_stream, _) = match connect_websocket(websocket_url).await {
+        Ok(conect) => conect,
+        Err(error) => {
+            eprintln!("Error: {error}, reconnecting");
+            let Some(connection) = reconnect_websocket(websocket_url, max_reconnect_attempts).await
+            else {
+                let _ = restart_signal_sender.send(3).await;
+                return;
+            };
+            connection
+        }
+    };
 
     loop {
-        if let Some(connection) = tokio::time::timeout(Duration::from_secs(15), ws_stream.next())
-            .await
-            .unwrap_or(None)
-        {
+        let next_message = tokio::select! {
+            next_message = tokio::time::timeout(signal_timeout, ws_stream.next()) => next_message,
+            Ok(()) = shutdown.recv() => {
+                if let Err(error) = ws_stream.close(None).await {
+                    eprintln!("Error while closing websocket: {error}");
+                }
+                return;
+            }
+        };
+        if let Some(connection) = next_message.unwrap_or(None) {
             let result = parse_twitch_webocket_messages(
                 connection,
                 &mut ws_stream,
@@ -153,7 +450,7 @@ async fn parse_twitch_webocket_messages<'a>(
     connection: Result<Message, Error>,
     ws_stream: &'a mut WebSocketStream<MaybeTlsStream<TcpStream>>,
     websocket_session_id: &'a Arc<Mutex<String>>,
-    twitch_websocket_event_handler_sender: &'a Sender<(String, String)>,
+    twitch_websocket_event_handler_sender: &'a Sender<SocketEvent>,
     restart_signal_sender: &'a Sender<u8>,
 ) -> Result<(), i8> {
     match connection {
@@ -163,8 +460,19 @@ async fn parse_twitch_webocket_messages<'a>(
                     eprintln!("Error while parsing websocket message");
                     return Err(-2);
                 };
-                let parsed: api_structs::TwitchApi = serde_json::from_str(message)
-                    .unwrap_or_else(|_| panic!("Unable to parse json response: \n{message}"));
+                if is_keepalive(message) {
+                    return Ok(());
+                }
+                let parsed: api_structs::TwitchApi = match serde_json::from_str(message) {
+                    Ok(parsed) => parsed,
+                    Err(error) => {
+                        eprintln!(
+                            "Error: {error} while parsing websocket message:\n{message}\nreconnecting"
+                        );
+                        let _ = restart_signal_sender.send(1).await;
+                        return Err(-2);
+                    }
+                };
 
                 parse_twitch_websocket_json(
                     parsed.metadata,
@@ -186,11 +494,19 @@ async fn parse_twitch_webocket_messages<'a>(
     Ok(())
 }
 
+/// Check if `message` is a keepalive by its message type alone, so keepalives are never parsed
+/// as another payload whatever their shape.
+fn is_keepalive(message: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(message).is_ok_and(|message| {
+        message["metadata"]["message_type"] == serde_json::json!("session_keepalive")
+    })
+}
+
 async fn parse_twitch_websocket_json<'a>(
     metadata: api_structs::WebsocketMetadata,
     payload: api_structs::WebsocketPayload,
     websocket_session_id: &'a Arc<Mutex<String>>,
-    twitch_websocket_event_handler_sender: &'a Sender<(String, String)>,
+    twitch_websocket_event_handler_sender: &'a Sender<SocketEvent>,
     restart_signal_sender: &'a Sender<u8>,
     ws_stream: &'a mut WebSocketStream<MaybeTlsStream<TcpStream>>,
 ) {
@@ -242,20 +558,15 @@ async fn parse_connection_reply_message<'a>(
                 eprintln!("Error expecting reconnect message, got:\n{payload:?}");
                 return;
             };
+            let Some(reconnect_url) = &reconnect.session.reconnect_url else {
+                eprintln!(
+                    "Error reconnect message without reconnect url:\n{reconnect:?}\nreconnecting"
+                );
+                let _ = restart_signal_sender.send(1).await;
+                return;
+            };
 
-            let connector = tokio_tungstenite::Connector::NativeTls(
-                native_tls::TlsConnector::new().expect("Unable to find TLS configuration"),
-            );
-            let connection_result = connect_async_tls_with_config(
-                &reconnect
-                    .session
-                    .reconnect_url
-                    .expect("Expected reconect url"),
-                None,
-                false,
-                Some(connector),
-            )
-            .await;
+            let connection_result = connect_websocket(reconnect_url).await;
 
             match connection_result {
                 Ok(websocket) => {
@@ -281,7 +592,7 @@ async fn parse_connection_reply_message<'a>(
 async fn parse_connection_notification_message(
     notification: api_structs::NotificationMetadata,
     payload: api_structs::WebsocketPayload,
-    twitch_websocket_event_handler_sender: &Sender<(String, String)>,
+    twitch_websocket_event_handler_sender: &Sender<SocketEvent>,
     restart_signal_sender: &Sender<u8>,
 ) {
     match notification.message_type {
@@ -292,12 +603,12 @@ async fn parse_connection_notification_message(
                 );
                 return;
             };
-            if subscription.subscription.subscritpion_type == "stream.online"
-                && subscription.event.event_type == api_structs::EventType::Live
-            {
-                let stream_name = subscription.event.broadcaster_user_login;
+            if let Some(message) = event_message(
+                &subscription.subscription.subscritpion_type,
+                subscription.event,
+            ) {
                 twitch_websocket_event_handler_sender
-                    .send((String::from("live"), stream_name))
+                    .send(message)
                     .await
                     .unwrap_or_else(|error| {
                         eprintln!(
@@ -342,68 +653,258 @@ async fn parse_connection_notification_message(
     }
 }
 
+/// The message sent to `event_handler` for an event of `subscription_type`, `update` events carry
+/// the new category.
+fn event_message(
+    subscription_type: &str,
+    event: api_structs::WebsocketEvent,
+) -> Option<SocketEvent> {
+    let event_type = match subscription_type {
+        "stream.online" => event
+            .event_type
+            .unwrap_or(api_structs::EventType::Live)
+            .as_str(),
+        "stream.offline" => "offline",
+        "channel.update" => "update",
+        _ => return None,
+    };
+    Some(SocketEvent {
+        category: (event_type == "update").then(|| event.category_name.unwrap_or_default()),
+        ..SocketEvent::new(
+            event_type,
+            &event.broadcaster_user_login,
+            &event.broadcaster_user_name,
+        )
+    })
+}
+
+/// Double the wait `time` after a failed attempt, up to `max_wait`.
+fn next_wait(time: Duration, max_wait: Duration) -> Duration {
+    match time.cmp(&max_wait) {
+        std::cmp::Ordering::Less => {
+            let time = time * 2;
+            if time > max_wait {
+                max_wait
+            } else {
+                time
+            }
+        }
+        std::cmp::Ordering::Greater => max_wait,
+        std::cmp::Ordering::Equal => time,
+    }
+}
+
+/// Retry connecting to the websocket at `websocket_url` with increasing waits, giving up with
+/// `None` after `max_attempts` failed attempts.
 async fn reconnect_websocket(
     websocket_url: &str,
-) -> (
+    max_attempts: Option<u32>,
+) -> Option<(
     WebSocketStream<MaybeTlsStream<TcpStream>>,
     Response<Option<Vec<u8>>>,
-) {
+)> {
     const MAX_WAIT: Duration = Duration::new(180, 0);
-    let connector = tokio_tungstenite::Connector::NativeTls(
-        native_tls::TlsConnector::new().expect("Unable to find TLS configuration"),
-    );
+    let start = tokio::time::Instant::now();
     let mut time = Duration::new(1, 0);
-    let mut new_stream =
-        connect_async_tls_with_config(websocket_url, None, false, Some(connector)).await;
+    let mut attempts = 1;
+    let mut new_stream = connect_websocket(websocket_url).await;
 
-    while new_stream.is_err() {
-        let connector = tokio_tungstenite::Connector::NativeTls(
-            native_tls::TlsConnector::new().expect("Unable to find TLS configuration"),
-        );
-        time = match time.cmp(&MAX_WAIT) {
-            std::cmp::Ordering::Less => {
-                let time = time * 2;
-                if time > MAX_WAIT {
-                    MAX_WAIT
-                } else {
-                    time
-                }
-            }
-            std::cmp::Ordering::Greater => MAX_WAIT,
-            std::cmp::Ordering::Equal => time,
+    while let Err(error) = new_stream {
+        if max_attempts.is_some_and(|max_attempts| attempts >= max_attempts) {
+            eprintln!(
+                "EventSub unreachable for {} minutes after {attempts} attempts: {error}",
+                start.elapsed().as_secs() / 60
+            );
+            return None;
+        }
+        attempts += 1;
+        time = next_wait(time, MAX_WAIT);
+        let wait = if error.failure == ConnectFailure::Dns {
+            time.max(DNS_FAILURE_WAIT)
+        } else {
+            time
         };
         eprintln!(
-            "Connection failed, re-attempting in {} secs.",
-            time.as_secs()
+            "Connection failed: {error}, re-attempting in {} secs.",
+            wait.as_secs()
         );
-        sleep(time).await;
-        new_stream =
-            connect_async_tls_with_config(websocket_url, None, false, Some(connector)).await;
+        sleep(wait).await;
+        new_stream = connect_websocket(websocket_url).await;
     }
     println!("reconnected successfully");
 
-    new_stream.expect("Expected websocket connection, but found an error")
+    new_stream.ok()
+}
+
+/// How long to wait at least before retrying after the host of the websocket could not be
+/// resolved, a lookup failing usually means the network is down.
+const DNS_FAILURE_WAIT: Duration = Duration::new(30, 0);
+
+/// How long a connection attempt may take before it is abandoned.
+const CONNECT_TIMEOUT: Duration = Duration::new(30, 0);
+
+/// Why connecting to the websocket failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectFailure {
+    Dns,
+    Tls,
+    Refused,
+    Timeout,
+    Other,
+}
+
+impl ConnectFailure {
+    fn of(error: &Error) -> Self {
+        match error {
+            Error::Tls(_) => Self::Tls,
+            Error::Io(error) => match error.kind() {
+                std::io::ErrorKind::ConnectionRefused => Self::Refused,
+                std::io::ErrorKind::TimedOut => Self::Timeout,
+                _ => Self::Other,
+            },
+            _ => Self::Other,
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Dns => write!(f, "DNS lookup failed"),
+            Self::Tls => write!(f, "TLS handshake failed"),
+            Self::Refused => write!(f, "Connection refused"),
+            Self::Timeout => write!(f, "Connection timed out"),
+            Self::Other => write!(f, "Connection error"),
+        }
+    }
+}
+
+/// A failed connection to the websocket with the category of the failure.
+#[derive(Debug)]
+struct ConnectError {
+    failure: ConnectFailure,
+    message: String,
+}
+
+impl ConnectError {
+    const fn other(message: String) -> Self {
+        Self {
+            failure: ConnectFailure::Other,
+            message,
+        }
+    }
+}
+
+impl From<Error> for ConnectError {
+    fn from(error: Error) -> Self {
+        Self {
+            failure: ConnectFailure::of(&error),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.failure, self.message)
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+/// Connect to the websocket at `websocket_url`. The host is resolved before connecting so that
+/// a failed lookup is reported as `ConnectFailure::Dns`. Failing to create the TLS configuration
+/// is returned as an error like any other connection failure.
+async fn connect_websocket(
+    websocket_url: &str,
+) -> Result<
+    (
+        WebSocketStream<MaybeTlsStream<TcpStream>>,
+        Response<Option<Vec<u8>>>,
+    ),
+    ConnectError,
+> {
+    let connector = native_tls::TlsConnector::new().map_err(|error| ConnectError {
+        failure: ConnectFailure::Tls,
+        message: format!("Unable to create TLS configuration: {error}"),
+    })?;
+    let mut request = websocket_url.into_client_request()?;
+    request.headers_mut().insert(
+        USER_AGENT,
+        HeaderValue::from_str(http::user_agent())
+            .map_err(|error| ConnectError::other(error.to_string()))?,
+    );
+    let connection = async {
+        let addresses = resolve_host(request.uri()).await?;
+        let stream = TcpStream::connect(addresses.as_slice())
+            .await
+            .map_err(Error::Io)?;
+        Ok(client_async_tls_with_config(
+            request,
+            stream,
+            None,
+            Some(tokio_tungstenite::Connector::NativeTls(connector)),
+        )
+        .await?)
+    };
+    tokio::time::timeout(CONNECT_TIMEOUT, connection)
+        .await
+        .unwrap_or_else(|_| {
+            Err(ConnectError {
+                failure: ConnectFailure::Timeout,
+                message: format!("No response after {} secs", CONNECT_TIMEOUT.as_secs()),
+            })
+        })
+}
+
+/// Resolve the addresses of the host of the websocket `uri`.
+async fn resolve_host(uri: &Uri) -> Result<Vec<SocketAddr>, ConnectError> {
+    let host = uri
+        .host()
+        .ok_or_else(|| ConnectError::other(format!("No host in {uri}")))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = uri.port_u16().unwrap_or_else(|| {
+        if uri.scheme_str() == Some("ws") {
+            80
+        } else {
+            443
+        }
+    });
+    let dns_error = |message: String| ConnectError {
+        failure: ConnectFailure::Dns,
+        message,
+    };
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|error| dns_error(error.to_string()))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(dns_error(format!("No addresses found for {host}")));
+    }
+    Ok(addresses)
 }
 
 async fn subscribe_to_event(
     restart_signal_sender: &Sender<u8>,
     api_url: &str,
     user_access_token: &Arc<Option<UserToken>>,
-    id: u32,
+    (id, subscription_type): (u32, &str),
     session_id: &Arc<Mutex<String>>,
     client_id: &str,
+    subscription_cost: &SubscriptionCost,
 ) {
     const MAX_WAIT: Duration = Duration::new(180, 0);
-    let time = Duration::new(1, 0);
+    let mut time = Duration::new(1, 0);
     let session_id = session_id
         .lock()
         .as_ref()
         .expect("Mutex lock poisoned")
         .to_string();
-    let subscription = api_structs::SubscriptionBody::new_live_sub(id, session_id);
+    let subscription = api_structs::SubscriptionBody::new(subscription_type, id, session_id);
 
     loop {
-        let subscriber = reqwest::Client::new()
+        let subscriber = http::client()
             .post(api_url)
             .bearer_auth(
                 (**user_access_token)
@@ -435,7 +936,19 @@ async fn subscribe_to_event(
                     }
                 } else {
                     match response.text().await {
-                        Ok(text) => println!("Subscribed to event:\n{text}"),
+                        Ok(text) => {
+                            println!("Subscribed to event:\n{text}");
+                            let warning =
+                                serde_json::from_str::<api_structs::SubscriptionResponse>(&text)
+                                    .ok()
+                                    .and_then(|costs| {
+                                        subscription_cost
+                                            .update(costs.total_cost, costs.max_total_cost)
+                                    });
+                            if let Some(warning) = warning {
+                                eprintln!("Warning: {warning}");
+                            }
+                        }
                         Err(error) => eprintln!(
                             "Error while attempting to display subscription response:\n{error}"
                         ),
@@ -444,18 +957,7 @@ async fn subscribe_to_event(
                 break;
             }
             Err(error) => {
-                match time.cmp(&MAX_WAIT) {
-                    std::cmp::Ordering::Less => {
-                        let time = time * 2;
-                        if time > MAX_WAIT {
-                            MAX_WAIT
-                        } else {
-                            time
-                        }
-                    }
-                    std::cmp::Ordering::Greater => MAX_WAIT,
-                    std::cmp::Ordering::Equal => time,
-                };
+                time = next_wait(time, MAX_WAIT);
                 eprintln!("Error: {}, retrying in {} secs", error, time.as_secs());
                 sleep(time).await;
             }
@@ -473,6 +975,13 @@ mod tests {
 
     use tokio::{process, time::timeout};
     use twitch_oauth2::AccessToken;
+
+    fn default_settings() -> crate::SharedSettings {
+        Arc::new(std::sync::RwLock::new(crate::Settings::new(
+            std::path::Path::new("./"),
+        )))
+    }
+
     #[tokio::test]
     async fn recieve_and_send() {
         const TWITCH_WEBSOCKET_URL: &str = "ws://127.0.0.1:3200/ws";
@@ -515,14 +1024,20 @@ mod tests {
                 id_reciever,
                 socket_sender,
                 restart_signal_sender,
-                TWITCH_WEBSOCKET_URL,
-                TWITCH_API_URL,
+                TWITCH_WEBSOCKET_URL.to_string(),
+                TWITCH_API_URL.to_string(),
                 twitch_user_access_token,
                 "AAAA",
+                None,
+                SubscriptionCost::default(),
+                broadcast::channel(1).1,
             );
         });
 
-        id_sender.send(30_423_375).await.unwrap();
+        id_sender
+            .send((30_423_375, vec!["stream.online"]))
+            .await
+            .unwrap();
 
         sleep(Duration::from_secs(5)).await;
         match process::Command::new("twitch-cli")
@@ -544,9 +1059,529 @@ mod tests {
             timeout(Duration::from_secs(15), socket_reciever.recv())
                 .await
                 .unwrap(),
-            Some((String::from("live"), String::from("testBroadcaster")))
+            Some(SocketEvent::new(
+                "live",
+                "testBroadcaster",
+                "testBroadcaster"
+            ))
         );
         child.kill().await.unwrap();
         child.wait().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn keepalive_resets_signal_timeout() {
+        use futures_util::SinkExt;
+
+        const KEEPALIVE: &str = r#"{
+            "metadata": {
+                "message_id": "84c1e79a-2a4b-4c13-ba0b-4312293e9308",
+                "message_type": "session_keepalive"
+            },
+            "payload": null
+        }"#;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for _ in 0..4 {
+                websocket.send(Message::text(KEEPALIVE)).await.unwrap();
+                sleep(Duration::from_millis(300)).await;
+            }
+            sleep(Duration::from_secs(30)).await;
+        });
+        let (socket_sender, mut socket_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, mut restart_signal_reciever) = mpsc::channel(1);
+        let start = tokio::time::Instant::now();
+
+        let reader = task::spawn(async move {
+            parse_stream_message(
+                &format!("ws://{address}/ws"),
+                Arc::default(),
+                socket_sender,
+                &restart_signal_sender,
+                Duration::from_millis(500),
+                None,
+                broadcast::channel(1).1,
+            )
+            .await;
+        });
+        let signal = timeout(Duration::from_secs(5), restart_signal_reciever.recv())
+            .await
+            .unwrap();
+
+        assert_eq!(Some(1), signal);
+        assert!(start.elapsed() >= Duration::from_millis(1200));
+        assert!(reader.await.is_ok());
+        assert!(socket_reciever.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reconnect_on_malformed_message() {
+        use futures_util::SinkExt;
+
+        const MALFORMED: &str = r#"{"metadata": {"message_type": "unknown"}}"#;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            websocket.send(Message::text(MALFORMED)).await.unwrap();
+            sleep(Duration::from_secs(30)).await;
+        });
+        let (socket_sender, mut socket_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, mut restart_signal_reciever) = mpsc::channel(1);
+
+        let reader = task::spawn(async move {
+            parse_stream_message(
+                &format!("ws://{address}/ws"),
+                Arc::default(),
+                socket_sender,
+                &restart_signal_sender,
+                SIGNAL_TIMEOUT,
+                None,
+                broadcast::channel(1).1,
+            )
+            .await;
+        });
+        let signal = timeout(Duration::from_secs(5), restart_signal_reciever.recv())
+            .await
+            .unwrap();
+        let finished = timeout(Duration::from_secs(5), reader).await.unwrap();
+
+        assert_eq!(Some(1), signal);
+        assert!(finished.is_ok());
+        assert!(socket_reciever.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn reconnect_without_reconnect_url() {
+        use futures_util::SinkExt;
+
+        const RECONNECT: &str = r#"{
+            "metadata": {
+                "message_id": "84c1e79a-2a4b-4c13-ba0b-4312293e9308",
+                "message_type": "session_reconnect",
+                "message_timestamp": "2024-03-02T18:20:05.000000000Z"
+            },
+            "payload": {
+                "session": {
+                    "id": "AQoQexAWVYKSTIu4ec_2VAxyuhAB",
+                    "status": "reconnecting",
+                    "connected_at": "2024-03-02T18:00:00.000000000Z",
+                    "keepalive_timeout_seconds": null
+                }
+            }
+        }"#;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut websocket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            websocket.send(Message::text(RECONNECT)).await.unwrap();
+            sleep(Duration::from_secs(30)).await;
+        });
+        let (socket_sender, _socket_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, mut restart_signal_reciever) = mpsc::channel(1);
+
+        let reader = task::spawn(async move {
+            parse_stream_message(
+                &format!("ws://{address}/ws"),
+                Arc::default(),
+                socket_sender,
+                &restart_signal_sender,
+                SIGNAL_TIMEOUT,
+                None,
+                broadcast::channel(1).1,
+            )
+            .await;
+        });
+        let signal = timeout(Duration::from_secs(5), restart_signal_reciever.recv())
+            .await
+            .unwrap();
+        reader.abort();
+        let ended = reader.await;
+
+        assert_eq!(Some(1), signal);
+        assert!(ended.is_err_and(|error| error.is_cancelled()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unreachable_after_max_reconnect_attempts() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let (socket_sender, _socket_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, mut restart_signal_reciever) = mpsc::channel(1);
+
+        parse_stream_message(
+            &format!("ws://{address}/ws"),
+            Arc::default(),
+            socket_sender,
+            &restart_signal_sender,
+            SIGNAL_TIMEOUT,
+            Some(3),
+            broadcast::channel(1).1,
+        )
+        .await;
+
+        assert_eq!(Some(3), restart_signal_reciever.recv().await);
+    }
+
+    #[tokio::test]
+    async fn connection_failure_is_an_error() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let result = connect_websocket(&format!("wss://{address}/ws")).await;
+
+        assert_eq!(ConnectFailure::Refused, result.unwrap_err().failure);
+    }
+
+    #[tokio::test]
+    async fn categorize_dns_failure() {
+        let error = connect_websocket("wss://stream-cave.invalid/ws")
+            .await
+            .unwrap_err();
+
+        assert_eq!(ConnectFailure::Dns, error.failure);
+        assert!(error.to_string().starts_with("DNS lookup failed: "));
+    }
+
+    #[test]
+    fn update_message_keeps_display_name_and_category() {
+        let event: api_structs::WebsocketEvent = serde_json::from_str(
+            r#"{
+                "broadcaster_user_id": "641972806",
+                "broadcaster_user_login": "kaicenat",
+                "broadcaster_user_name": "KaiCenat",
+                "category_name": "Just Chatting"
+            }"#,
+        )
+        .unwrap();
+
+        let message = event_message("channel.update", event).unwrap();
+
+        assert_eq!("KaiCenat", message.display_name);
+        assert_eq!(Some("Just Chatting"), message.category.as_deref());
+    }
+
+    #[test]
+    fn retry_wait_increases_up_to_max() {
+        const MAX_WAIT: Duration = Duration::new(180, 0);
+        let mut time = Duration::new(1, 0);
+
+        let waits: Vec<u64> = (0..9)
+            .map(|_| {
+                time = next_wait(time, MAX_WAIT);
+                time.as_secs()
+            })
+            .collect();
+
+        assert_eq!(vec![2, 4, 8, 16, 32, 64, 128, 180, 180], waits);
+    }
+
+    #[test]
+    fn warn_on_subscription_cost_over_limit() {
+        let streams = Streams::read_streams(std::path::Path::new("./tests/resources"));
+        let channels = streams.streams.len();
+
+        let warning = subscription_cost_warning(&streams, 1).unwrap();
+
+        assert!(channels > 1);
+        assert!(warning.contains(&format!("has {channels} channels")));
+        assert!(subscription_cost_warning(&streams, DEFAULT_MAX_SUBSCRIPTION_COST).is_none());
+    }
+
+    #[tokio::test]
+    async fn display_name_reaches_task_spawner() {
+        let message: api_structs::TwitchApi = serde_json::from_str(
+            r#"{
+                "metadata": {
+                    "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+                    "message_type": "notification",
+                    "message_timestamp": "2022-11-16T10:11:12.464757833Z",
+                    "subscription_type": "stream.online",
+                    "subscription_version": "1"
+                },
+                "payload": {
+                    "subscription": {
+                        "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+                        "status": "enabled",
+                        "type": "stream.online",
+                        "version": "1",
+                        "cost": 1,
+                        "condition": { "broadcaster_user_id": "641972806" },
+                        "transport": {
+                            "method": "websocket",
+                            "session_id": "AQoQexAWVYKSTIu4ec_2VAxyuhAB"
+                        },
+                        "created_at": "2022-11-16T10:11:12.464757833Z"
+                    },
+                    "event": {
+                        "id": "9001",
+                        "broadcaster_user_id": "641972806",
+                        "broadcaster_user_login": "kaicenat",
+                        "broadcaster_user_name": "KaiCenat",
+                        "type": "live",
+                        "started_at": "2022-11-16T10:11:12.464757833Z"
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let api_structs::WebsocketMetadata::Notification(notification) = message.metadata else {
+            panic!("Expected notification metadata");
+        };
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, mut task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, _restart_signal_reciever) = mpsc::channel(1);
+
+        crate::event_handler::event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            default_settings(),
+            crate::cave::tasks_handler::ActivePlayers::default(),
+            crate::event_handler::LaunchPolicy::default(),
+        );
+        parse_connection_notification_message(
+            notification,
+            message.payload,
+            &socket_sender,
+            &restart_signal_sender,
+        )
+        .await;
+
+        let task = task_reciever.recv().await.unwrap();
+        assert_eq!(String::from("kaicenat"), task.name);
+        assert_eq!(String::from("KaiCenat"), task.display_name);
+    }
+
+    #[tokio::test]
+    async fn close_player_on_category_change() {
+        let update = |category: &str| -> api_structs::TwitchApi {
+            serde_json::from_str(&format!(
+                r#"{{
+                    "metadata": {{
+                        "message_id": "befa7b53-d79d-478f-86b9-120f112b044e",
+                        "message_type": "notification",
+                        "message_timestamp": "2022-11-16T10:11:12.464757833Z",
+                        "subscription_type": "channel.update",
+                        "subscription_version": "2"
+                    }},
+                    "payload": {{
+                        "subscription": {{
+                            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+                            "status": "enabled",
+                            "type": "channel.update",
+                            "version": "2",
+                            "cost": 1,
+                            "condition": {{ "broadcaster_user_id": "641972806" }},
+                            "transport": {{
+                                "method": "websocket",
+                                "session_id": "AQoQexAWVYKSTIu4ec_2VAxyuhAB"
+                            }},
+                            "created_at": "2022-11-16T10:11:12.464757833Z"
+                        }},
+                        "event": {{
+                            "broadcaster_user_id": "641972806",
+                            "broadcaster_user_login": "kaicenat",
+                            "broadcaster_user_name": "KaiCenat",
+                            "title": "test",
+                            "language": "en",
+                            "category_id": "509658",
+                            "category_name": "{category}",
+                            "content_classification_labels": []
+                        }}
+                    }}
+                }}"#
+            ))
+            .unwrap()
+        };
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, _task_reciever) = mpsc::channel(10);
+        let (_exit_sender, exit_reciever) = mpsc::channel(10);
+        let (file_sender, file_reciever) = mpsc::channel(10);
+        let (restart_signal_sender, _restart_signal_reciever) = mpsc::channel(1);
+        let active_players = crate::cave::tasks_handler::ActivePlayers::default();
+        let close_reciever = active_players.start("kaicenat").unwrap();
+        let mut streams = Streams::read_streams(std::path::Path::new("./tests/resources"));
+        let kaicenat = streams.get_mut("kaicenat").unwrap();
+        kaicenat
+            .close_when
+            .push(crate::CloseRule::Category(String::from("Just Chatting")));
+        let kaicenat = kaicenat.clone();
+
+        crate::event_handler::event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            default_settings(),
+            active_players.clone(),
+            crate::event_handler::LaunchPolicy::default(),
+        );
+        file_sender.send(kaicenat.clone()).await.unwrap();
+        while file_sender.capacity() < file_sender.max_capacity() {
+            task::yield_now().await;
+        }
+        for category in ["Fortnite", "Just Chatting"] {
+            let message = update(category);
+            let api_structs::WebsocketMetadata::Notification(notification) = message.metadata
+            else {
+                panic!("Expected notification metadata");
+            };
+            parse_connection_notification_message(
+                notification,
+                message.payload,
+                &socket_sender,
+                &restart_signal_sender,
+            )
+            .await;
+            while socket_sender.capacity() < socket_sender.max_capacity() {
+                task::yield_now().await;
+            }
+            assert_eq!(category == "Fortnite", active_players.is_active("kaicenat"));
+        }
+        timeout(Duration::from_secs(5), close_reciever)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            vec!["stream.online", "stream.offline", "channel.update"],
+            kaicenat.subscription_types()
+        );
+        assert!(!active_players.is_active("kaicenat"));
+    }
+
+    #[test]
+    fn warn_once_when_cost_nears_limit() {
+        let cost = SubscriptionCost::default();
+
+        let below = cost.update(8, 10);
+        let crossed = cost.update(9, 10);
+        let still_over = cost.update(10, 10);
+
+        assert!(below.is_none());
+        assert_eq!(
+            Some(String::from(
+                "The subscriptions cost 9 of the limit of 10, channels over the limit will not be \
+                watched"
+            )),
+            crossed
+        );
+        assert!(still_over.is_none());
+        assert_eq!(Some((10, 10)), cost.current());
+        assert!(!cost.allows(STREAM_ONLINE_COST));
+    }
+
+    #[tokio::test]
+    async fn delete_inactive_subscriptions_of_every_page() {
+        use crate::cave::mock_api::{unchecked_token, MockApi};
+
+        let subscription = |id: &str, status: &str, disconnected_at: &str| {
+            format!(
+                r#"{{"id":"{id}","status":"{status}","type":"stream.online","version":"1","cost":1,"condition":{{"broadcaster_user_id":"641972806"}},"transport":{{"method":"websocket","session_id":"AQoQ","connected_at":"2024-03-02T18:00:00Z"{disconnected_at}}},"created_at":"2024-03-02T18:00:00Z"}}"#
+            )
+        };
+        let first_page = format!(
+            r#"{{"data":[{},{}],"total":3,"total_cost":3,"max_total_cost":10,"pagination":{{"cursor":"next"}}}}"#,
+            subscription("enabled", "enabled", ""),
+            subscription("disconnected", "websocket_disconnected", ""),
+        );
+        let second_page = format!(
+            r#"{{"data":[{}],"total":3,"total_cost":3,"max_total_cost":10,"pagination":{{}}}}"#,
+            subscription(
+                "dead-session",
+                "enabled",
+                r#","disconnected_at":"2024-03-02T19:00:00Z""#
+            ),
+        );
+        let mock = MockApi::start(move |request| {
+            if request.starts_with("DELETE") {
+                (204, String::new())
+            } else if request.contains("after=next") {
+                (200, second_page.clone())
+            } else {
+                (200, first_page.clone())
+            }
+        })
+        .await;
+
+        let deleted = delete_inactive_subscriptions(
+            &mock.url("/eventsub/subscriptions"),
+            &unchecked_token(),
+            "AAAA",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(2, deleted);
+        assert_eq!(
+            vec![
+                "GET /eventsub/subscriptions HTTP/1.1",
+                "GET /eventsub/subscriptions?after=next HTTP/1.1",
+                "DELETE /eventsub/subscriptions?id=disconnected HTTP/1.1",
+                "DELETE /eventsub/subscriptions?id=dead-session HTTP/1.1",
+            ],
+            mock.requests()
+        );
+    }
+
+    #[tokio::test]
+    async fn launch_streams_live_at_startup() {
+        use crate::cave::mock_api::{helix_response, stream_data, unchecked_token, MockApi};
+
+        let mock = MockApi::start(|request| {
+            let data = if request.contains("user_login=kaicenat") {
+                vec![stream_data("kaicenat", 641_972_806)]
+            } else {
+                Vec::new()
+            };
+            (200, helix_response(&data))
+        })
+        .await;
+        let mut streams = Streams::new();
+        for (name, id) in [("kaicenat", 641_972_806), ("jynxzi", 411_377_640)] {
+            streams.streams.push(crate::StreamConfig::new(name, id));
+        }
+        let (socket_sender, socket_reciever) = mpsc::channel(10);
+        let (task_sender, mut task_reciever) = mpsc::channel(10);
+        let (_, exit_reciever) = mpsc::channel(10);
+        let (_, file_reciever) = mpsc::channel(10);
+
+        crate::event_handler::event_handler(
+            socket_reciever,
+            exit_reciever,
+            file_reciever,
+            task_sender,
+            default_settings(),
+            crate::cave::tasks_handler::ActivePlayers::default(),
+            crate::event_handler::LaunchPolicy::default(),
+        );
+        let launched = catch_up_live_streams(
+            &streams,
+            &socket_sender,
+            &mock.url("/streams"),
+            &unchecked_token(),
+            "AAAA",
+        )
+        .await
+        .unwrap();
+
+        let task = timeout(Duration::from_secs(15), task_reciever.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(1, launched);
+        assert_eq!(String::from("kaicenat"), task.name);
+        assert!(task_reciever.try_recv().is_err());
+    }
 }
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug)]
@@ -100,6 +101,10 @@ pub enum SubscriptionStatus {
     AuthorizationRevoked,
     UserRemoved,
     VersionRemoved,
+    /// Any other status of a subscription that no longer sends events, such as
+    /// `websocket_disconnected`
+    #[serde(other)]
+    Inactive,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -110,21 +115,34 @@ pub struct Condition {
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Transport {
     pub method: String,
+    /// Only sent with websocket subscriptions
+    #[serde(default)]
     pub session_id: String,
+    /// Set when the websocket of the subscription disconnected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disconnected_at: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct WebsocketEvent {
+    /// Only sent with stream.online events
+    #[serde(default)]
     pub id: String,
     pub broadcaster_user_id: String,
     pub broadcaster_user_login: String,
     pub broadcaster_user_name: String,
-    #[serde(rename = "type")]
-    pub event_type: EventType,
+    /// Only sent with stream.online events
+    #[serde(rename = "type", default)]
+    pub event_type: Option<EventType>,
+    /// Only sent with stream.online events
+    #[serde(default)]
     pub started_at: String,
+    /// Only sent with channel.update events
+    #[serde(default)]
+    pub category_name: Option<String>,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     Live,
@@ -134,6 +152,20 @@ pub enum EventType {
     Rerun,
 }
 
+impl EventType {
+    /// The name of the event type as sent by Twitch.
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Live => "live",
+            Self::Playlist => "playlist",
+            Self::WatchParty => "watch_party",
+            Self::Premiere => "premiere",
+            Self::Rerun => "rerun",
+        }
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct SubscriptionBody {
     #[serde(rename = "type")]
@@ -146,15 +178,33 @@ pub struct SubscriptionBody {
 impl SubscriptionBody {
     #[must_use]
     pub fn new_live_sub(id: u32, session_id: String) -> Self {
+        Self::new("stream.online", id, session_id)
+    }
+
+    #[must_use]
+    pub fn new_offline_sub(id: u32, session_id: String) -> Self {
+        Self::new("stream.offline", id, session_id)
+    }
+
+    /// A subscription to `subscription_type` events of the broadcaster `id`.
+    #[must_use]
+    pub fn new(subscription_type: &str, id: u32, session_id: String) -> Self {
+        // Version 1 of channel.update is deprecated
+        let version = if subscription_type == "channel.update" {
+            "2"
+        } else {
+            "1"
+        };
         Self {
-            subscritpion_type: String::from("stream.online"),
-            version: String::from("1"),
+            subscritpion_type: subscription_type.to_string(),
+            version: version.to_string(),
             condition: Condition {
                 broadcaster_user_id: id.to_string(),
             },
             transport: Transport {
                 method: String::from("websocket"),
                 session_id,
+                disconnected_at: None,
             },
         }
     }
@@ -168,7 +218,20 @@ pub struct StreamSearch {
 
 #[derive(Deserialize, Serialize)]
 pub struct Pagination {
-    cursor: String,
+    pub cursor: Option<String>,
+}
+
+/// The costs sent with the response to a created subscription.
+#[derive(Deserialize)]
+pub struct SubscriptionResponse {
+    pub total_cost: u32,
+    pub max_total_cost: u32,
+}
+
+#[derive(Deserialize)]
+pub struct SubscriptionList {
+    pub data: Vec<WebsocketSubscription>,
+    pub pagination: Option<Pagination>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -186,3 +249,61 @@ pub struct SearchData {
     title: String,
     started_at: String,
 }
+
+#[derive(Deserialize, Serialize)]
+pub struct StreamsResponse {
+    pub data: Vec<StreamData>,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct StreamData {
+    pub id: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    pub game_id: String,
+    pub game_name: String,
+    #[serde(rename = "type")]
+    pub stream_type: String,
+    pub title: String,
+    pub viewer_count: u32,
+    pub started_at: String,
+    language: String,
+    thumbnail_url: String,
+    tags: Option<Vec<String>>,
+    is_mature: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScheduleResponse {
+    pub data: ScheduleData,
+    pagination: Option<Pagination>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ScheduleData {
+    /// `None` when no segments are scheduled, such as during a vacation
+    pub segments: Option<Vec<ScheduleSegment>>,
+    pub broadcaster_id: String,
+    pub broadcaster_name: String,
+    pub broadcaster_login: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleSegment {
+    pub id: String,
+    pub start_time: String,
+    pub end_time: Option<String>,
+    pub title: String,
+    /// Set when the segment was canceled
+    pub canceled_until: Option<String>,
+    pub category: Option<ScheduleCategory>,
+    pub is_recurring: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleCategory {
+    pub id: String,
+    pub name: String,
+}
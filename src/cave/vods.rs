@@ -0,0 +1,168 @@
+use super::quality::Quality;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::{sync::mpsc::Sender, time::sleep};
+
+/// A VOD or clip opened once at a set time, independent of the live events.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledVod {
+    /// The url played by the player
+    pub url: String,
+    /// The time to open the VOD in UTC, such as `2024-03-02T18:00:00Z`
+    pub at: String,
+    /// The quality to play at, the quality of the profile when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<Quality>,
+}
+
+impl ScheduledVod {
+    /// How long to wait from `now` until the VOD is opened, `None` if the time already passed or
+    /// cannot be read.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use stream_cave::vods::{parse_utc_time, ScheduledVod};
+    ///
+    /// let vod = ScheduledVod {
+    ///     url: String::from("https://www.twitch.tv/videos/2086734668"),
+    ///     at: String::from("2024-03-02T18:00:00Z"),
+    ///     quality: None,
+    /// };
+    /// let now = parse_utc_time("2024-03-02T17:59:00Z").unwrap();
+    ///
+    /// assert_eq!(Some(Duration::from_mins(1)), vod.delay(now));
+    /// ```
+    #[must_use]
+    pub fn delay(&self, now: SystemTime) -> Option<Duration> {
+        parse_utc_time(&self.at)?.duration_since(now).ok()
+    }
+}
+
+/// Read a UTC time formatted as `YYYY-MM-DDTHH:MM:SSZ`, fractions of seconds are ignored.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+/// use stream_cave::vods::parse_utc_time;
+///
+/// assert_eq!(Some(UNIX_EPOCH + Duration::from_secs(86_400)), parse_utc_time("1970-01-02T00:00:00Z"));
+/// assert_eq!(None, parse_utc_time("tomorrow"));
+/// ```
+#[must_use]
+pub fn parse_utc_time(time: &str) -> Option<SystemTime> {
+    let (date, time) = time.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let time = time.split_once('.').map_or(time, |(time, _)| time);
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// The days since the unix epoch of a date of the proleptic gregorian calendar.
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Send each of `vods` to `launch_sender` at its time, counting from `now`. VODs whose time
+/// already passed are skipped. Finishes after the last VOD was sent.
+///
+/// # Examples
+/// ```
+/// use std::time::SystemTime;
+/// use stream_cave::vods::{vod_scheduler, ScheduledVod};
+/// use tokio::sync::mpsc;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (launch_sender, mut launch_reciever) = mpsc::channel(5);
+///     let vod = ScheduledVod {
+///         url: String::from("https://www.twitch.tv/videos/2086734668"),
+///         at: String::from("2000-01-01T00:00:00Z"),
+///         quality: None,
+///     };
+///
+///     vod_scheduler(vec![vod], SystemTime::now(), launch_sender).await;
+///
+///     assert!(launch_reciever.recv().await.is_none());
+/// }
+/// ```
+pub async fn vod_scheduler(
+    vods: Vec<ScheduledVod>,
+    now: SystemTime,
+    launch_sender: Sender<ScheduledVod>,
+) {
+    let mut scheduled: Vec<(Duration, ScheduledVod)> = Vec::new();
+    for vod in vods {
+        match vod.delay(now) {
+            Some(delay) => scheduled.push((delay, vod)),
+            None => eprintln!("Skipping the VOD {} scheduled at {}", vod.url, vod.at),
+        }
+    }
+    scheduled.sort_by_key(|(delay, _)| *delay);
+
+    let start = tokio::time::Instant::now();
+    for (delay, vod) in scheduled {
+        sleep(delay.saturating_sub(start.elapsed())).await;
+        if launch_sender.send(vod).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{sync::mpsc, task, time::Instant};
+
+    #[tokio::test(start_paused = true)]
+    async fn launch_vod_at_scheduled_time() {
+        let now = parse_utc_time("2024-03-02T17:59:00Z").unwrap();
+        let vod = |at: &str| ScheduledVod {
+            url: format!("https://www.twitch.tv/videos/{at}"),
+            at: at.to_string(),
+            quality: None,
+        };
+        let (launch_sender, mut launch_reciever) = mpsc::channel(5);
+        let start = Instant::now();
+
+        task::spawn(vod_scheduler(
+            vec![
+                vod("2024-03-02T18:30:00Z"),
+                vod("2024-03-02T18:00:00Z"),
+                vod("2024-03-02T17:00:00Z"),
+            ],
+            now,
+            launch_sender,
+        ));
+        let first = launch_reciever.recv().await.unwrap();
+        let first_elapsed = start.elapsed();
+        let second = launch_reciever.recv().await.unwrap();
+        let second_elapsed = start.elapsed();
+
+        assert_eq!("2024-03-02T18:00:00Z", first.at);
+        assert_eq!(Duration::from_mins(1), first_elapsed);
+        assert_eq!("2024-03-02T18:30:00Z", second.at);
+        assert_eq!(Duration::from_mins(31), second_elapsed);
+        assert!(launch_reciever.recv().await.is_none());
+    }
+}
@@ -6,22 +6,68 @@ pub use crate::cave::authentication;
 #[doc(inline)]
 pub use crate::cave::authentication::create_oauth_token;
 #[doc(inline)]
+pub use crate::cave::check_live_status;
+#[doc(inline)]
+pub use crate::cave::control;
+#[doc(inline)]
 pub use crate::cave::event_handler;
 #[doc(inline)]
+pub use crate::cave::event_log;
+#[doc(inline)]
+pub use crate::cave::events;
+#[doc(inline)]
 pub use crate::cave::file_watcher;
 #[doc(inline)]
+pub use crate::cave::http;
+#[doc(inline)]
+pub use crate::cave::keyring;
+#[doc(inline)]
+pub use crate::cave::notifier;
+#[doc(inline)]
+pub use crate::cave::player;
+#[doc(inline)]
 pub use crate::cave::player::get_stream;
 #[doc(inline)]
+pub use crate::cave::quality;
+#[doc(inline)]
+pub use crate::cave::report;
+#[doc(inline)]
+pub use crate::cave::resolve_broadcaster_id;
+#[doc(inline)]
+pub use crate::cave::schema;
+#[doc(inline)]
 pub use crate::cave::tasks_handler;
 #[doc(inline)]
 pub use crate::cave::twitch_socket;
 #[doc(inline)]
+pub use crate::cave::vods;
+#[doc(inline)]
+pub use crate::cave::CloseRule;
+#[doc(inline)]
+pub use crate::cave::Latency;
+#[doc(inline)]
+pub use crate::cave::LivePlan;
+#[doc(inline)]
+pub use crate::cave::MergeStrategy;
+#[doc(inline)]
 pub use crate::cave::Player;
 #[doc(inline)]
+pub use crate::cave::ScheduleLock;
+#[doc(inline)]
 pub use crate::cave::Settings;
 #[doc(inline)]
+pub use crate::cave::SharedSettings;
+#[doc(inline)]
 pub use crate::cave::StreamConfig;
 #[doc(inline)]
+pub use crate::cave::StreamGroup;
+#[doc(inline)]
 pub use crate::cave::Streams;
 #[doc(inline)]
+pub use crate::cave::TokenStorage;
+#[doc(inline)]
 pub use crate::cave::UserData;
+#[doc(inline)]
+pub use crate::cave::DEFAULT_LIVE_CHECK_CONCURRENCY;
+#[doc(inline)]
+pub use crate::cave::DEFAULT_PROFILE;